@@ -4,19 +4,56 @@
 #extension GL_ARB_shading_language_450pack : enable
 
 layout(set = 0, binding = 0) uniform sampler2D tex;
+// tangent space normals, 128 for 0 - a single flat texel for materials without a normal map
+layout(set = 0, binding = 2) uniform sampler2D normal_tex;
+// highlight color, and shininess / 1000 in alpha - a single black texel without a specular map
+layout(set = 0, binding = 3) uniform sampler2D specular_tex;
 
 layout(location = 0) in vec3 v_normal;
 layout(location = 1) in vec2 v_uv;
+layout(location = 2) in vec3 v_position;
 
 layout(location = 0) out vec4 f_color;
 
 const vec3 LIGHT = vec3(0.5, 0.5, 0.5);
 
+// never darker than 0.7, the color textures are drawn unlit
+float brightness(vec3 normal) {
+    return mix(0.7, 1.0, max(dot(normal, normalize(LIGHT)), 0.0));
+}
+
+// the normal map's normal, in view space - the directions texture coordinates grow in along
+// the surface come from their screen space derivatives
+vec3 mapped_normal(vec3 normal) {
+    vec3 mapped = (texture(normal_tex, v_uv).xyz * 255.0 - 128.0) / 127.0;
+    vec3 dp1 = dFdx(v_position);
+    vec3 dp2 = dFdy(v_position);
+    vec2 duv1 = dFdx(v_uv);
+    vec2 duv2 = dFdy(v_uv);
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if (scale == 0.0) {
+        return normal;
+    }
+    scale = inversesqrt(scale);
+    return normalize(mat3(tangent * scale, bitangent * scale, normal) * mapped);
+}
+
 void main() {
-    float brightness = dot(normalize(v_normal), normalize(LIGHT));
-    vec3 dark_color = vec3(0.7, 0.7, 0.7);
-    vec3 regular_color = vec3(1.0, 1.0, 1.0);
+    vec3 normal = normalize(v_normal);
+    vec3 bumped = mapped_normal(normal);
+    vec4 color = texture(tex, v_uv);
+
+    // relief is shaded relative to the surface, so a flat normal map leaves the color as is
+    float relief = brightness(bumped) / brightness(normal);
+
+    vec4 specular = texture(specular_tex, v_uv);
+    vec3 half_vector = normalize(normalize(LIGHT) + normalize(-v_position));
+    float exponent = max(specular.a * 1000.0, 1.0);
+    vec3 highlight = specular.rgb * pow(max(dot(bumped, half_vector), 0.0), exponent);
 
-    f_color = texture(tex, v_uv);// * vec4(mix(dark_color, regular_color, brightness), 1.0);
-    //f_color = vec4(1.0, 1.0, 1.0, 1.0);
+    f_color = vec4(color.rgb * relief + highlight, color.a);
 }
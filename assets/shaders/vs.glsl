@@ -17,10 +17,13 @@ layout(location = 2) in vec2 uv;
 
 layout(location = 0) out vec3 v_normal;
 layout(location = 1) out vec2 v_uv;
+layout(location = 2) out vec3 v_position;
 
 void main() {
     mat4 mat = push_constants.model_mat;
+    vec4 view_position = mat * vec4(position, 1.0);
     v_normal = transpose(inverse(mat3(mat))) * normal;
-    gl_Position = uniforms.proj * mat * vec4(position, 1.0);
+    v_position = view_position.xyz;
+    gl_Position = uniforms.proj * view_position;
     v_uv = uv;
 }
@@ -1,5 +1,121 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
+// Fingerprint this build of game_state for the mod ABI handshake (see src/abi.rs).
+// Anything that may change the layout of State - the crate version, the compiler, the
+// source itself and our dependencies - goes into the hash. Dependencies are hashed as declared
+// in Cargo.toml, and as resolved in the Cargo.lock of the package being built - the host or a
+// mod - so a `cargo update` on either side changes the hash.
 fn main() {
-    println!("Don't forget about me! (build.rs)");
+    let mut hasher = DefaultHasher::new();
 
+    env::var("CARGO_PKG_VERSION").unwrap().hash(&mut hasher);
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    rustc_version.hash(&mut hasher);
+
+    fs::read("Cargo.toml").unwrap().hash(&mut hasher);
+    hash_dir(Path::new("src"), &mut hasher);
+    if let Some(lockfile) = find_lockfile() {
+        let contents = fs::read_to_string(&lockfile).unwrap();
+        resolved_dependencies(&contents).hash(&mut hasher);
+        println!("cargo:rerun-if-changed={}", lockfile.display());
+    }
+
+    println!("cargo:rustc-env=GAME_STATE_BUILD_HASH={}", hasher.finish());
+    println!("cargo:rustc-env=GAME_STATE_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+}
+
+fn hash_dir(dir: &Path, hasher: &mut DefaultHasher) {
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            hash_dir(&path, hasher);
+        } else {
+            path.to_string_lossy().hash(hasher);
+            fs::read(&path).unwrap().hash(hasher);
+        }
+    }
+}
+
+// The Cargo.lock next to the target directory this is built in - the package being built
+// uses the default target directory. The lockfile of game_state itself otherwise.
+fn find_lockfile() -> Option<PathBuf> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    out_dir
+        .ancestors()
+        .skip_while(|dir| !dir.ends_with("target"))
+        .nth(1)
+        .map(|root| root.join("Cargo.lock"))
+        .filter(|lockfile| lockfile.exists())
+        .or_else(|| Some(PathBuf::from("Cargo.lock")).filter(|lockfile| lockfile.exists()))
+}
+
+// The [[package]] entries of a Cargo.lock that game_state depends on, directly or not
+fn resolved_dependencies(lockfile: &str) -> Vec<&str> {
+    let packages = lockfile
+        .split("[[package]]")
+        .skip(1)
+        // up to the next section - the last entry is followed by [metadata] in old lockfiles
+        .map(|entry| entry.split("\n[").next().unwrap_or("").trim())
+        .collect::<Vec<_>>();
+    let field = |entry: &str, key: &str| {
+        entry
+            .lines()
+            .find(|line| line.starts_with(key))
+            .map(|line| line[key.len()..].trim_matches(|c| c == ' ' || c == '=' || c == '"'))
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let mut resolved = Vec::new();
+    let mut pending = vec!["game_state".to_string()];
+    while let Some(dependency) = pending.pop() {
+        // "name", or "name version", or "name version (source)"
+        let mut words = dependency.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let version = words.next();
+        for entry in packages.iter() {
+            let matches = field(entry, "name") == name
+                && version
+                    .iter()
+                    .all(|version| field(entry, "version") == *version);
+            if !matches || resolved.contains(entry) {
+                continue;
+            }
+            resolved.push(*entry);
+            let mut in_dependencies = false;
+            for line in entry.lines() {
+                if line.starts_with("dependencies") {
+                    in_dependencies = true;
+                } else if in_dependencies && line.trim() == "]" {
+                    in_dependencies = false;
+                } else if in_dependencies {
+                    pending.push(
+                        line.trim()
+                            .trim_end_matches(',')
+                            .trim_matches('"')
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+    resolved.sort();
+    resolved
 }
@@ -0,0 +1,170 @@
+use std::ffi::CString;
+use std::fmt;
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::state::State;
+
+///
+/// Fingerprint of the game_state build, computed by build.rs
+///
+pub const BUILD_HASH: &str = env!("GAME_STATE_BUILD_HASH");
+
+///
+/// Version of the compiler game_state was built with
+///
+pub const RUSTC_VERSION: &str = env!("GAME_STATE_RUSTC_VERSION");
+
+///
+/// ModAbi - describes the game_state a mod (or the host) was compiled against.
+///
+/// Mods are handed a `&mut State` across a dynamic library boundary, so the host and every mod
+/// must agree on its exact layout. Each mod is required to export:
+///
+/// #[no_mangle]
+/// pub extern "C" fn mod_<name>_abi() -> ModAbi {
+///     ModAbi::current()
+/// }
+///
+/// and the host refuses to load a mod whose ModAbi differs from its own.
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ModAbi {
+    pub build_hash: u64,
+    pub state_size: u64,
+    pub state_align: u64,
+}
+
+impl ModAbi {
+    pub fn current() -> Self {
+        ModAbi {
+            build_hash: BUILD_HASH.parse().expect("invalid GAME_STATE_BUILD_HASH"),
+            state_size: mem::size_of::<State>() as u64,
+            state_align: mem::align_of::<State>() as u64,
+        }
+    }
+}
+
+impl fmt::Display for ModAbi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "build {:016x}, State size {} align {}",
+            self.build_hash, self.state_size, self.state_align
+        )
+    }
+}
+
+///
+/// Outcome of a v2 mod lifecycle call
+///
+/// - Ok: carry on
+/// - Retry: the call failed, but may succeed later - a failed "load" is attempted again on the
+///   next scan, a failed "update" skips the frame
+/// - Error: the mod can't continue, and is not updated again until a new build is loaded or
+///   the host retries it
+///
+/// A mod returning anything but Ok from "load" must leave State as it found it, as it is not
+/// sent "unload".
+///
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModStatus {
+    Ok = 0,
+    Retry = 1,
+    Error = 2,
+}
+
+///
+/// ModResult - returned by the v2 lifecycle functions:
+///
+/// #[no_mangle]
+/// pub extern "C-unwind" fn mod_<name>_load_v2(state: &mut State) -> ModResult
+/// #[no_mangle]
+/// pub extern "C-unwind" fn mod_<name>_update_v2(state: &mut State, dt: &Duration) -> ModResult
+/// #[no_mangle]
+/// pub extern "C-unwind" fn mod_<name>_unload_v2(state: &mut State) -> ModResult
+///
+/// The host prefers these over the v1 functions returning (), which are still supported.
+///
+#[repr(C)]
+#[derive(Debug)]
+pub struct ModResult {
+    pub status: ModStatus,
+    message: *mut c_char,
+}
+
+impl ModResult {
+    pub fn ok() -> Self {
+        ModResult {
+            status: ModStatus::Ok,
+            message: ptr::null_mut(),
+        }
+    }
+
+    pub fn retry<M: Into<String>>(message: M) -> Self {
+        Self::with_message(ModStatus::Retry, message.into())
+    }
+
+    pub fn error<M: Into<String>>(message: M) -> Self {
+        Self::with_message(ModStatus::Error, message.into())
+    }
+
+    fn with_message(status: ModStatus, message: String) -> Self {
+        let message = CString::new(message.replace('\0', ""))
+            .expect("interior nul bytes were removed")
+            .into_raw();
+        ModResult { status, message }
+    }
+
+    ///
+    /// Take the status and message, for the host
+    ///
+    pub fn into_parts(mut self) -> (ModStatus, Option<String>) {
+        let message = self.take_message();
+        (self.status, message)
+    }
+
+    fn take_message(&mut self) -> Option<String> {
+        if self.message.is_null() {
+            return None;
+        }
+        let message = unsafe { CString::from_raw(self.message) };
+        self.message = ptr::null_mut();
+        Some(message.to_string_lossy().into_owned())
+    }
+}
+
+impl<E: fmt::Display> From<Result<(), E>> for ModResult {
+    fn from(result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => ModResult::ok(),
+            Err(err) => ModResult::error(err.to_string()),
+        }
+    }
+}
+
+impl Drop for ModResult {
+    fn drop(&mut self) {
+        self.take_message();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mod_result_carries_message() {
+        assert_eq!(ModResult::ok().into_parts(), (ModStatus::Ok, None));
+
+        let (status, message) = ModResult::error("no\0 device").into_parts();
+        assert_eq!(status, ModStatus::Error);
+        assert_eq!(message.as_deref(), Some("no device"));
+
+        let result: ModResult = Err::<(), _>("window not ready").into();
+        assert_eq!(result.status, ModStatus::Error);
+    }
+}
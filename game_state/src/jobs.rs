@@ -0,0 +1,373 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+
+use futures::future::BoxFuture;
+use futures::task::{waker_ref, ArcWake};
+use futures::FutureExt;
+
+use crate::logging::Logger;
+
+const LOG_TARGET: &str = "jobs";
+
+pub const DEFAULT_THREADS: usize = 2;
+
+///
+/// Jobs - a cheaply cloneable handle to the host's job executor.
+///
+/// Mods spawn futures to run on the executor's worker threads, and check the returned handle
+/// for the result on a later update:
+///
+/// let handle = state.get_jobs().spawn("asset_loader", async move { Model::load(path, mx) });
+/// ...
+/// if let Some(models) = handle.try_take() { ... }
+///
+/// Every job has an owner - by convention the name of the mod spawning it. A job's future is
+/// code from its mod's library, so the host cancels every job of a mod when it is unloaded,
+/// before the library is dropped: a cancelled job's future is dropped, and never polled again.
+///
+/// Worker threads are started by the host (see `StateBuilder::job_threads`) - a thread started
+/// from a mod would be running code from a library that may be unloaded.
+///
+#[derive(Clone)]
+pub struct Jobs {
+    inner: Arc<Executor>,
+}
+
+struct Executor {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    logger: Logger,
+}
+
+struct Queue {
+    ready: VecDeque<Arc<Task>>,
+    tasks: HashMap<u64, Arc<Task>>,
+    next_id: u64,
+    workers: Vec<JoinHandle<()>>,
+    shutdown: bool,
+}
+
+struct Task {
+    id: u64,
+    owner: String,
+    // None once the job has completed or was cancelled
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    executor: Weak<Executor>,
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if let Some(executor) = arc_self.executor.upgrade() {
+            executor.schedule(arc_self.clone());
+        }
+    }
+}
+
+// A panic caught in a job may poison a lock - the data behind it is still usable
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Executor {
+    fn schedule(&self, task: Arc<Task>) {
+        let mut queue = lock(&self.queue);
+        if !queue.shutdown {
+            queue.ready.push_back(task);
+            self.ready.notify_one();
+        }
+    }
+
+    fn next_ready(&self) -> Option<Arc<Task>> {
+        lock(&self.queue).ready.pop_front()
+    }
+
+    // Poll a job once. The job's lock is held throughout, so cancel() waits for the poll.
+    fn poll(&self, task: &Arc<Task>) {
+        let mut future = lock(&task.future);
+        let finished = match future.as_mut() {
+            None => return,
+            Some(job) => {
+                let waker = waker_ref(task);
+                let mut cx = Context::from_waker(&waker);
+                match panic::catch_unwind(AssertUnwindSafe(|| job.as_mut().poll(&mut cx))) {
+                    Ok(Poll::Pending) => false,
+                    Ok(Poll::Ready(())) => true,
+                    Err(_) => {
+                        self.logger.error(
+                            LOG_TARGET,
+                            format!("a job of {} panicked, and was dropped", task.owner),
+                        );
+                        true
+                    }
+                }
+            }
+        };
+        if finished {
+            *future = None;
+            drop(future);
+            lock(&self.queue).tasks.remove(&task.id);
+        }
+    }
+
+    fn work(self: Arc<Self>) {
+        loop {
+            let task = {
+                let mut queue = lock(&self.queue);
+                loop {
+                    if queue.shutdown {
+                        return;
+                    }
+                    if let Some(task) = queue.ready.pop_front() {
+                        break task;
+                    }
+                    queue = self
+                        .ready
+                        .wait(queue)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+            };
+            self.poll(&task);
+        }
+    }
+}
+
+impl Jobs {
+    ///
+    /// An executor without worker threads - jobs only run when `run_pending` is called
+    ///
+    pub fn new(logger: Logger) -> Self {
+        Jobs {
+            inner: Arc::new(Executor {
+                queue: Mutex::new(Queue {
+                    ready: VecDeque::new(),
+                    tasks: HashMap::new(),
+                    next_id: 0,
+                    workers: Vec::new(),
+                    shutdown: false,
+                }),
+                ready: Condvar::new(),
+                logger,
+            }),
+        }
+    }
+
+    ///
+    /// Start `count` worker threads. Only the host should start workers.
+    ///
+    pub fn start_workers(&self, count: usize) -> std::io::Result<()> {
+        let mut queue = lock(&self.inner.queue);
+        for _ in 0..count {
+            let executor = self.inner.clone();
+            let name = format!("sg-jobs-{}", queue.workers.len());
+            let worker = thread::Builder::new()
+                .name(name)
+                .spawn(move || executor.work())?;
+            queue.workers.push(worker);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Run `future` in the background, on behalf of `owner`
+    ///
+    pub fn spawn<F, T>(&self, owner: &str, future: F) -> JobHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let slot = result.clone();
+        let job = future.map(move |value| *lock(&slot) = Some(value)).boxed();
+
+        let mut queue = lock(&self.inner.queue);
+        if queue.shutdown {
+            drop(queue);
+            drop(job);
+            return JobHandle {
+                result,
+                taken: false,
+            };
+        }
+        let id = queue.next_id;
+        queue.next_id += 1;
+        let task = Arc::new(Task {
+            id,
+            owner: owner.to_string(),
+            future: Mutex::new(Some(job)),
+            executor: Arc::downgrade(&self.inner),
+        });
+        queue.tasks.insert(id, task.clone());
+        queue.ready.push_back(task);
+        self.inner.ready.notify_one();
+        JobHandle {
+            result,
+            taken: false,
+        }
+    }
+
+    ///
+    /// Cancel every job of `owner`, waiting for any being polled right now. Returns the number
+    /// of jobs cancelled.
+    ///
+    pub fn cancel(&self, owner: &str) -> usize {
+        let cancelled = {
+            let mut queue = lock(&self.inner.queue);
+            let ids = queue
+                .tasks
+                .values()
+                .filter(|task| task.owner == owner)
+                .map(|task| task.id)
+                .collect::<Vec<_>>();
+            ids.iter()
+                .filter_map(|id| queue.tasks.remove(id))
+                .collect::<Vec<_>>()
+        };
+        for task in cancelled.iter() {
+            // dropped outside of the job's lock - dropping a future may wake other jobs
+            let future = lock(&task.future).take();
+            drop(future);
+        }
+        cancelled.len()
+    }
+
+    ///
+    /// Number of jobs of `owner` that haven't completed yet
+    ///
+    pub fn pending(&self, owner: &str) -> usize {
+        lock(&self.inner.queue)
+            .tasks
+            .values()
+            .filter(|task| task.owner == owner)
+            .count()
+    }
+
+    ///
+    /// Poll every job that is ready to make progress, on the calling thread. Returns the number
+    /// of polls made.
+    ///
+    pub fn run_pending(&self) -> usize {
+        let mut polls = 0;
+        while let Some(task) = self.inner.next_ready() {
+            self.inner.poll(&task);
+            polls += 1;
+        }
+        polls
+    }
+
+    ///
+    /// Stop the workers, then drop every remaining job
+    ///
+    pub fn shutdown(&self) {
+        let workers = {
+            let mut queue = lock(&self.inner.queue);
+            queue.shutdown = true;
+            queue.ready.clear();
+            self.inner.ready.notify_all();
+            queue.workers.drain(..).collect::<Vec<_>>()
+        };
+        for worker in workers {
+            if worker.thread().id() != thread::current().id() {
+                let _ = worker.join();
+            }
+        }
+        let remaining = lock(&self.inner.queue)
+            .tasks
+            .drain()
+            .map(|(_, task)| task)
+            .collect::<Vec<_>>();
+        for task in remaining {
+            let future = lock(&task.future).take();
+            drop(future);
+        }
+    }
+}
+
+///
+/// JobHandle - the result of a spawned job, once it has completed
+///
+pub struct JobHandle<T> {
+    result: Arc<Mutex<Option<T>>>,
+    taken: bool,
+}
+
+impl<T> JobHandle<T> {
+    ///
+    /// The job's result, if it has completed. The result is only returned once.
+    ///
+    pub fn try_take(&mut self) -> Option<T> {
+        let value = lock(&self.result).take();
+        self.taken |= value.is_some();
+        value
+    }
+
+    ///
+    /// Returns true if the job was dropped without a result - cancelled, or it panicked
+    ///
+    pub fn is_cancelled(&self) -> bool {
+        // once the job is dropped, only the handle holds the result
+        !self.taken && Arc::strong_count(&self.result) == 1 && lock(&self.result).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+
+    #[test]
+    fn runs_jobs_on_workers() {
+        let jobs = Jobs::new(Logger::default());
+        jobs.start_workers(2).unwrap();
+        let mut handles = (0..8u64)
+            .map(|i| jobs.spawn("test", async move { i * 2 }))
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        while results.len() < handles.len() {
+            for handle in handles.iter_mut() {
+                results.extend(handle.try_take());
+            }
+            thread::yield_now();
+        }
+        results.sort();
+        assert_eq!(results, vec![0, 2, 4, 6, 8, 10, 12, 14]);
+        assert_eq!(jobs.pending("test"), 0);
+        jobs.shutdown();
+    }
+
+    #[test]
+    fn cancels_jobs_by_owner() {
+        let jobs = Jobs::new(Logger::default());
+        let (_sender, receiver) = oneshot::channel::<u32>();
+        let (sender, other) = oneshot::channel::<u32>();
+        let mut waiting = jobs.spawn("unloading", receiver);
+        let mut kept = jobs.spawn("staying", other);
+        jobs.run_pending();
+        assert_eq!(jobs.pending("unloading"), 1);
+
+        assert_eq!(jobs.cancel("unloading"), 1);
+        assert!(waiting.is_cancelled());
+        assert_eq!(waiting.try_take(), None);
+
+        sender.send(7).unwrap();
+        jobs.run_pending();
+        assert!(!kept.is_cancelled());
+        assert_eq!(kept.try_take(), Some(Ok(7)));
+        assert!(!kept.is_cancelled());
+    }
+
+    #[test]
+    fn drops_panicking_jobs() {
+        let jobs = Jobs::new(Logger::new(4));
+        let handle = jobs.spawn("test", async { panic!("job failed") });
+        jobs.run_pending();
+        assert!(handle.is_cancelled());
+        assert_eq!(jobs.pending("test"), 0);
+    }
+}
@@ -1,8 +1,17 @@
 // opinion here? reexport winit or import in other libs...?
+pub extern crate image;
 pub extern crate nalgebra;
 pub extern crate sdl2;
 
+pub mod abi;
+pub mod jobs;
+pub mod logging;
 pub mod model;
+pub mod mtl;
+pub mod openddl;
+pub mod profiler;
+pub mod scene;
+pub mod software;
 pub mod state;
 pub mod tree;
 
@@ -15,6 +24,7 @@ pub mod utils;
 use std::sync::Arc;
 use std::time::Duration;
 
+use model::Model;
 use state::SceneGraph;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use thing::CameraFacet;
@@ -46,6 +56,18 @@ pub trait Renderer: Identifyable {
     /// present()
     /// Actually render the image, compositing render layers in the order they were queued
     fn present(&mut self, camera: &CameraFacet);
+
+    /// model_updated()
+    /// A model was replaced by a new version with the same identity - drop anything made from
+    /// the old one, eg. GPU buffers, so the new one is used from the next frame
+    fn model_updated(&mut self, _model: &Arc<Model>) {}
+
+    /// capture_frame()
+    /// Read back the last frame presented, for renderers that support it - see
+    /// `software::OffscreenRenderer`
+    fn capture_frame(&mut self) -> Option<image::RgbaImage> {
+        None
+    }
 }
 
 pub trait Behavior {
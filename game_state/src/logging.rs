@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+///
+/// Severity of a log record, from most to least severe
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("unknown log level '{}'", s)),
+        }
+    }
+}
+
+///
+/// A single log message. `seq` increases by one for every record logged, so a gap in the
+/// sequence means records were dropped from the ring buffer.
+///
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub seq: u64,
+    pub time: SystemTime,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+///
+/// Destination for log records, e.g. the terminal or a file.
+///
+/// Sinks are installed by the host: a sink's code must outlive every library that logs, so a
+/// mod should never install one.
+///
+pub trait LogSink: Send {
+    fn write(&mut self, record: &Record);
+    fn flush(&mut self) {}
+}
+
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+///
+/// Logger - a cheaply cloneable handle to the shared log.
+///
+/// Records are filtered by level, per target (by convention the mod name, or the engine
+/// subsystem), written to every sink and kept in a ring buffer of the most recent records.
+///
+#[derive(Clone)]
+pub struct Logger {
+    inner: Arc<Mutex<Log>>,
+}
+
+struct Log {
+    records: VecDeque<Record>,
+    capacity: usize,
+    next_seq: u64,
+    level: Level,
+    target_levels: HashMap<String, Level>,
+    sinks: Vec<Box<dyn LogSink>>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Logger {
+    pub fn new(capacity: usize) -> Self {
+        Logger {
+            inner: Arc::new(Mutex::new(Log {
+                records: VecDeque::with_capacity(capacity),
+                capacity,
+                next_seq: 0,
+                level: Level::Info,
+                target_levels: HashMap::new(),
+                sinks: Vec::new(),
+            })),
+        }
+    }
+
+    // A panic caught at the mod boundary may poison the lock - the log is still usable
+    fn lock(&self) -> MutexGuard<'_, Log> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    ///
+    /// Returns true if a record with this level and target would be logged
+    ///
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        let log = self.lock();
+        level <= *log.target_levels.get(target).unwrap_or(&log.level)
+    }
+
+    pub fn log<M: Into<String>>(&self, level: Level, target: &str, message: M) {
+        let mut log = self.lock();
+        if level > *log.target_levels.get(target).unwrap_or(&log.level) {
+            return;
+        }
+        let record = Record {
+            seq: log.next_seq,
+            time: SystemTime::now(),
+            level,
+            target: target.to_string(),
+            message: message.into(),
+        };
+        log.next_seq += 1;
+        for sink in log.sinks.iter_mut() {
+            sink.write(&record);
+        }
+        if log.capacity == 0 {
+            return;
+        }
+        if log.records.len() == log.capacity {
+            log.records.pop_front();
+        }
+        log.records.push_back(record);
+    }
+
+    pub fn error<M: Into<String>>(&self, target: &str, message: M) {
+        self.log(Level::Error, target, message)
+    }
+
+    pub fn warn<M: Into<String>>(&self, target: &str, message: M) {
+        self.log(Level::Warn, target, message)
+    }
+
+    pub fn info<M: Into<String>>(&self, target: &str, message: M) {
+        self.log(Level::Info, target, message)
+    }
+
+    pub fn debug<M: Into<String>>(&self, target: &str, message: M) {
+        self.log(Level::Debug, target, message)
+    }
+
+    pub fn trace<M: Into<String>>(&self, target: &str, message: M) {
+        self.log(Level::Trace, target, message)
+    }
+
+    ///
+    /// Set the level for targets without a level of their own
+    ///
+    pub fn set_level(&self, level: Level) {
+        self.lock().level = level;
+    }
+
+    ///
+    /// Set the level for one target, or None to fall back to the default level
+    ///
+    pub fn set_target_level(&self, target: &str, level: Option<Level>) {
+        let mut log = self.lock();
+        match level {
+            Some(level) => log.target_levels.insert(target.to_string(), level),
+            None => log.target_levels.remove(target),
+        };
+    }
+
+    ///
+    /// Apply a filter spec such as "warn,input=debug,libloader=info": a bare level sets the
+    /// default, target=level sets the level of a single target.
+    ///
+    pub fn apply_filter(&self, spec: &str) -> Result<(), String> {
+        let mut level = None;
+        let mut target_levels = Vec::new();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.find('=') {
+                Some(pos) => target_levels.push((
+                    directive[..pos].trim(),
+                    directive[pos + 1..].trim().parse::<Level>()?,
+                )),
+                None => level = Some(directive.parse::<Level>()?),
+            }
+        }
+        if let Some(level) = level {
+            self.set_level(level);
+        }
+        for (target, level) in target_levels {
+            self.set_target_level(target, Some(level));
+        }
+        Ok(())
+    }
+
+    pub fn add_sink(&self, sink: Box<dyn LogSink>) {
+        self.lock().sinks.push(sink);
+    }
+
+    ///
+    /// Drop every sink, flushing them first
+    ///
+    pub fn clear_sinks(&self) {
+        let mut log = self.lock();
+        for sink in log.sinks.iter_mut() {
+            sink.flush();
+        }
+        log.sinks.clear();
+    }
+
+    pub fn flush(&self) {
+        for sink in self.lock().sinks.iter_mut() {
+            sink.flush();
+        }
+    }
+
+    ///
+    /// Records still held in the ring buffer with a sequence number of at least `seq`, oldest
+    /// first. Pass the sequence number following the last record seen to poll for new ones.
+    ///
+    pub fn records_since(&self, seq: u64) -> Vec<Record> {
+        self.lock()
+            .records
+            .iter()
+            .filter(|r| r.seq >= seq)
+            .cloned()
+            .collect()
+    }
+
+    ///
+    /// The sequence number the next record will be given
+    ///
+    pub fn next_seq(&self) -> u64 {
+        self.lock().next_seq
+    }
+}
+
+///
+/// Log a formatted message, only formatting it if the level is enabled for the target:
+///
+/// log!(state.get_logger(), Level::Debug, "input", "pos {:?}", camera.pos);
+///
+#[macro_export]
+macro_rules! log {
+    ($logger:expr, $level:expr, $target:expr, $($arg:tt)+) => {{
+        let logger: &$crate::logging::Logger = &$logger;
+        if logger.enabled($level, $target) {
+            logger.log($level, $target, format!($($arg)+));
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($logger:expr, $target:expr, $($arg:tt)+) => {
+        $crate::log!($logger, $crate::logging::Level::Error, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($logger:expr, $target:expr, $($arg:tt)+) => {
+        $crate::log!($logger, $crate::logging::Level::Warn, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($logger:expr, $target:expr, $($arg:tt)+) => {
+        $crate::log!($logger, $crate::logging::Level::Info, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($logger:expr, $target:expr, $($arg:tt)+) => {
+        $crate::log!($logger, $crate::logging::Level::Debug, $target, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($logger:expr, $target:expr, $($arg:tt)+) => {
+        $crate::log!($logger, $crate::logging::Level::Trace, $target, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_target() {
+        let logger = Logger::new(16);
+        logger.apply_filter("warn,input=debug").unwrap();
+
+        logger.info("libloader", "hidden");
+        logger.warn("libloader", "shown");
+        logger.debug("input", "shown");
+        logger.trace("input", "hidden");
+
+        let records = logger.records_since(0);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.message == "shown"));
+        assert!(logger.apply_filter("input=loud").is_err());
+    }
+
+    #[test]
+    fn ring_buffer_keeps_most_recent() {
+        let logger = Logger::new(2);
+        for i in 0..5 {
+            info!(logger, "test", "record {}", i);
+        }
+
+        let records = logger.records_since(0);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, 3);
+        assert_eq!(records[1].message, "record 4");
+        assert_eq!(logger.records_since(4).len(), 1);
+        assert_eq!(logger.next_seq(), 5);
+    }
+}
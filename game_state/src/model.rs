@@ -1,5 +1,8 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use nalgebra::Matrix4;
 
@@ -7,17 +10,269 @@ use nalgebra::Matrix4;
 use nom_obj::model::{Interleaved, Obj};
 
 use crate::create_next_identity;
+use crate::mtl::{self, MtlMaterial};
+use crate::state::SceneGraph;
 use crate::Identifyable;
 use crate::Identity;
 
+pub mod gltf;
+pub mod ogex;
+
+///
+/// A texture map, and the file it was read from. The image is shared by every material using
+/// the same file - see `TextureCache`.
+///
+#[derive(Clone)]
+pub struct TextureMap {
+    pub path: PathBuf,
+    pub image: Arc<image::DynamicImage>,
+}
+
+impl TextureMap {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(path)
+            .map_err(|err| format!("unable to open texture {:?}: {}", path, err))?;
+        Ok(TextureMap {
+            path: path.to_path_buf(),
+            image: Arc::new(image),
+        })
+    }
+}
+
+///
+/// TextureCache - texture maps already read, by path, so a texture used by several objects
+/// or materials is decoded once
+///
+/// `Model::load` uses a cache of its own. Pass one to `Model::load_with_cache` to share it
+/// between files too - and drop it to read changed textures again.
+///
+#[derive(Default)]
+pub struct TextureCache {
+    maps: HashMap<PathBuf, TextureMap>,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<TextureMap, Box<dyn Error>> {
+        if let Some(map) = self.maps.get(path) {
+            return Ok(map.clone());
+        }
+        let map = TextureMap::load(path)?;
+        self.maps.insert(path.to_path_buf(), map.clone());
+        Ok(map)
+    }
+
+    ///
+    /// How many textures were decoded
+    ///
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+}
+
+///
+/// Material - how a model's surface looks, read from its MTL file or glTF material
+///
+/// Colors are RGB, from 0 to 1. A map, when present, takes the place of the matching color.
+/// Models without a material get `Material::default()`, which has the defaults of the MTL
+/// format: a light grey, without maps.
+///
+/// `metallic`, `roughness` and the `metallic_roughness_map` (metalness in blue, roughness in
+/// green) are glTF's - MTL materials are rough and non-metallic.
+///
 #[derive(Clone)]
 pub struct Material {
-    pub diffuse_map: image::DynamicImage,
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    pub opacity: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub diffuse_map: Option<TextureMap>,
+    pub normal_map: Option<TextureMap>,
+    pub specular_map: Option<TextureMap>,
+    pub emissive_map: Option<TextureMap>,
+    pub metallic_roughness_map: Option<TextureMap>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        let mtl = MtlMaterial::new("default");
+        Material {
+            name: mtl.name,
+            ambient: mtl.ambient,
+            diffuse: mtl.diffuse,
+            specular: mtl.specular,
+            emissive: mtl.emissive,
+            shininess: mtl.shininess,
+            opacity: mtl.opacity,
+            metallic: 0.0,
+            roughness: 1.0,
+            diffuse_map: None,
+            normal_map: None,
+            specular_map: None,
+            emissive_map: None,
+            metallic_roughness_map: None,
+        }
+    }
+}
+
+impl Material {
+    ///
+    /// Load the texture maps of a material read from an MTL file
+    ///
+    pub fn load(mtl: &MtlMaterial, textures: &mut TextureCache) -> Result<Self, Box<dyn Error>> {
+        let mut load = |path: &Option<PathBuf>| path.as_ref().map(|p| textures.load(p)).transpose();
+        Ok(Material {
+            name: mtl.name.clone(),
+            ambient: mtl.ambient,
+            diffuse: mtl.diffuse,
+            specular: mtl.specular,
+            emissive: mtl.emissive,
+            shininess: mtl.shininess,
+            opacity: mtl.opacity,
+            diffuse_map: load(&mtl.diffuse_map)?,
+            normal_map: load(&mtl.normal_map)?,
+            specular_map: load(&mtl.specular_map)?,
+            emissive_map: load(&mtl.emissive_map)?,
+            ..Default::default()
+        })
+    }
+
+    ///
+    /// The maps present, in the order diffuse, normal, specular, emissive, metallic-roughness
+    ///
+    pub fn maps(&self) -> impl Iterator<Item = &TextureMap> {
+        self.diffuse_map
+            .iter()
+            .chain(self.normal_map.iter())
+            .chain(self.specular_map.iter())
+            .chain(self.emissive_map.iter())
+            .chain(self.metallic_roughness_map.iter())
+    }
+
+    ///
+    /// The texture unlit renderers draw the material with: the diffuse map (or color), plus
+    /// the emissive map (or color), with the opacity in alpha. It is the size of the diffuse
+    /// map, or a single texel without one.
+    ///
+    pub fn unlit_texture(&self) -> image::RgbaImage {
+        let (width, height, mut texels) = match &self.diffuse_map {
+            Some(map) => {
+                let image = map.image.to_rgba();
+                let (width, height) = image.dimensions();
+                (width, height, image.into_raw())
+            }
+            None => {
+                let [r, g, b] = self.diffuse;
+                (1, 1, vec![to_byte(r), to_byte(g), to_byte(b), 255])
+            }
+        };
+        let emissive_map = self.emissive_map.as_ref().map(|map| {
+            let image = map.image.to_rgba();
+            let (width, height) = image.dimensions();
+            (width, height, image.into_raw())
+        });
+        let emissive = self
+            .emissive
+            .iter()
+            .map(|c| to_byte(*c))
+            .collect::<Vec<_>>();
+
+        for y in 0..height {
+            for x in 0..width {
+                let texel = ((y * width + x) * 4) as usize;
+                // the emissive map is sampled at the same texture coordinates
+                let glow = match &emissive_map {
+                    Some((map_width, map_height, map)) => {
+                        let mx = x * map_width / width;
+                        let my = y * map_height / height;
+                        let i = ((my * map_width + mx) * 4) as usize;
+                        &map[i..i + 3]
+                    }
+                    None => &emissive[..],
+                };
+                for c in 0..3 {
+                    texels[texel + c] = texels[texel + c].saturating_add(glow[c]);
+                }
+                let alpha = texels[texel + 3] as f32 / 255.0 * self.opacity;
+                texels[texel + 3] = to_byte(alpha);
+            }
+        }
+        image::RgbaImage::from_raw(width, height, texels).expect("texels match the size")
+    }
+
+    ///
+    /// The normal map renderers shade relief with, or a single flat texel - pointing straight
+    /// out of the surface, and so leaving it unchanged - without one. Texels are tangent space
+    /// normals, with 128 for 0.
+    ///
+    pub fn normal_texture(&self) -> image::RgbaImage {
+        match &self.normal_map {
+            Some(map) => map.image.to_rgba(),
+            None => image::RgbaImage::from_raw(1, 1, FLAT_NORMAL.to_vec()).expect("one texel"),
+        }
+    }
+
+    ///
+    /// The specular map renderers add highlights with, with the shininess in alpha - from 0
+    /// to 1000 like `Ns`, scaled to 0 to 255 - or a single black texel, without highlights,
+    /// without one
+    ///
+    pub fn specular_texture(&self) -> image::RgbaImage {
+        let shininess = to_byte(self.shininess / MAX_SHININESS);
+        match &self.specular_map {
+            Some(map) => {
+                let mut image = map.image.to_rgba();
+                for texel in image.pixels_mut() {
+                    texel.data[3] = shininess;
+                }
+                image
+            }
+            None => image::RgbaImage::from_raw(1, 1, vec![0, 0, 0, shininess]).expect("one texel"),
+        }
+    }
+}
+
+///
+/// A tangent space normal map texel pointing straight out of the surface
+///
+pub const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+///
+/// The shininess `Material::specular_texture` scales to 255
+///
+pub const MAX_SHININESS: f32 = 1000.0;
+
+fn to_byte(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+///
+/// ModelScene - a model file's node hierarchy, as a render layer: a scene graph node for each
+/// node of the file, with its transform, and the models they show, each loaded once
+///
+pub struct ModelScene {
+    pub graph: SceneGraph,
+    pub models: Vec<Arc<Model>>,
 }
 
 #[derive(Clone)]
 pub struct Model {
     pub filename: String,
+    /// which of the models `load()` reads from the file this is - to match it up with its new
+    /// version when the file is loaded again
+    pub object: usize,
     pub id: Identity,
     pub model_mat: Matrix4<f32>,
     pub world_mat: Matrix4<f32>,
@@ -26,11 +281,47 @@ pub struct Model {
 }
 
 impl Model {
+    ///
+    /// Load each object of a model file as a model, with the material it uses
+    ///
+    /// The format is picked by extension: `.gltf` and `.glb` files are read as glTF - see
+    /// `model::gltf` - `.ogex` files as OpenGEX - see `model::ogex` - and anything else as OBJ.
+    ///
     pub fn load(filename: &str, model_mat: Matrix4<f32>) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::load_with_cache(filename, model_mat, &mut TextureCache::new())
+    }
+
+    ///
+    /// `load()`, reading textures through a cache that may already hold them
+    ///
+    pub fn load_with_cache(
+        filename: &str,
+        model_mat: Matrix4<f32>,
+        textures: &mut TextureCache,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut models = if gltf::is_gltf(Path::new(filename)) {
+            gltf::load(filename, model_mat, textures)?
+        } else if ogex::is_ogex(Path::new(filename)) {
+            ogex::load(filename, model_mat, textures)?
+        } else {
+            Self::load_obj(filename, model_mat, textures)?
+        };
+        for (object, model) in models.iter_mut().enumerate() {
+            model.object = object;
+        }
+        Ok(models)
+    }
+
+    fn load_obj(
+        filename: &str,
+        model_mat: Matrix4<f32>,
+        textures: &mut TextureCache,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
         let obj = Obj::read_file(filename)?;
+        let mut materials = ObjMaterials::load(Path::new(filename))?;
 
         let mut models = Vec::new();
-        for o in obj.objects.iter() {
+        for (i, o) in obj.objects.iter().enumerate() {
             let Interleaved { v_vt_vn, idx } = o.interleaved();
 
             let verts = v_vt_vn
@@ -42,29 +333,134 @@ impl Model {
                 return Err("model has no vertices".into());
             }
 
-            let indices = idx.iter().map(|x: &usize| *x as u16).collect::<Vec<_>>();
-
-            let diffuse_map_filename = &obj.objects[0]
-                .material
-                .as_ref()
-                .ok_or_else(|| format!("no diffuse map (map_Kd) defined in model {}", filename))?
-                .diffuse_map;
-
-            let material_path = Path::new(diffuse_map_filename);
-            let diffuse_map = image::open(material_path)?;
+            let mesh = Mesh::create(verts, &idx)
+                .map_err(|err| format!("object {} of {}: {}", i, filename, err))?;
 
             models.push(Model {
                 filename: filename.to_string(),
+                object: i,
                 id: create_next_identity(),
                 model_mat,
                 world_mat: Matrix4::<f32>::identity(),
-                mesh: Mesh::create(verts, indices),
-                material: Material { diffuse_map },
+                mesh,
+                material: materials.for_object(i, textures)?,
             })
         }
 
         Ok(models)
     }
+
+    ///
+    /// The files this model is read from - the OBJ and the material libraries it names, the
+    /// glTF and its buffers, or the OpenGEX file, and the texture maps of its material - for
+    /// watching them for changes
+    ///
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let path = PathBuf::from(&self.filename);
+        let mut files = vec![path.clone()];
+        if gltf::is_gltf(&path) {
+            files.extend(gltf::buffer_files(&path));
+        } else if !ogex::is_ogex(&path) {
+            if let Ok(materials) = ObjMaterials::read(&path) {
+                files.extend(materials.libraries);
+            }
+        }
+        for map in self.material.maps() {
+            // embedded textures are read from the model's own files
+            if !files.contains(&map.path) {
+                files.push(map.path.clone());
+            }
+        }
+        files
+    }
+}
+
+// The materials an OBJ file refers to: the material libraries it names, and the material each
+// of its objects uses
+struct ObjMaterials {
+    obj: PathBuf,
+    // relative to the working directory, from being relative to the OBJ
+    libraries: Vec<PathBuf>,
+    // by object, in the order of the `o` and `g` statements - a single object when there are
+    // none
+    used: Vec<Option<String>>,
+    defined: Vec<MtlMaterial>,
+    loaded: HashMap<String, Material>,
+}
+
+impl ObjMaterials {
+    fn load(obj: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut materials = Self::read(obj)?;
+        for library in materials.libraries.iter() {
+            materials.defined.extend(mtl::load(library)?);
+        }
+        Ok(materials)
+    }
+
+    // Find the material statements, without reading the libraries
+    fn read(obj: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(obj)?;
+        let dir = obj.parent().unwrap_or_else(|| Path::new(""));
+        let mut libraries = Vec::new();
+        let mut used = Vec::new();
+        // the material in use carries over to the next object, until one sets its own
+        let mut in_use = None;
+        // An object is counted from its first face, and drawn with the material in use there -
+        // so a `usemtl` ahead of the first `o` or `g` goes to the first object, and an object
+        // with several materials is drawn with its first one
+        let mut object_has_faces = false;
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("mtllib") => libraries.extend(words.map(|name| dir.join(name))),
+                Some("o") | Some("g") => object_has_faces = false,
+                Some("usemtl") => in_use = Some(words.collect::<Vec<_>>().join(" ")),
+                Some("f") if !object_has_faces => {
+                    used.push(in_use.clone());
+                    object_has_faces = true;
+                }
+                _ => {}
+            }
+        }
+        Ok(ObjMaterials {
+            obj: obj.to_path_buf(),
+            libraries,
+            used,
+            defined: Vec::new(),
+            loaded: HashMap::new(),
+        })
+    }
+
+    // The material of the nth object - the first one defined when it uses none by name, and
+    // the default material when there are none
+    fn for_object(
+        &mut self,
+        object: usize,
+        textures: &mut TextureCache,
+    ) -> Result<Material, Box<dyn Error>> {
+        let mtl = match self.used.get(object).cloned().flatten() {
+            Some(name) => self
+                .defined
+                .iter()
+                .find(|m| m.name == name)
+                .ok_or_else(|| {
+                    format!(
+                        "material {:?}, used by {:?}, isn't in its material libraries",
+                        name, self.obj
+                    )
+                })?,
+            None => match self.defined.first() {
+                Some(mtl) => mtl,
+                None => return Ok(Material::default()),
+            },
+        };
+        if let Some(material) = self.loaded.get(&mtl.name) {
+            return Ok(material.clone());
+        }
+        let material = Material::load(mtl, textures)?;
+        self.loaded.insert(mtl.name.clone(), material.clone());
+        Ok(material)
+    }
 }
 
 #[test]
@@ -121,15 +517,77 @@ impl Vertex {
     }
 }
 
+///
+/// Indices - a mesh's triangles, as indices into its vertices. 16 bits wide when they can
+/// address every vertex, 32 otherwise.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> usize {
+        match self {
+            Indices::U16(indices) => indices[i] as usize,
+            Indices::U32(indices) => indices[i] as usize,
+        }
+    }
+
+    ///
+    /// The vertex indices of each whole triangle
+    ///
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (0..self.len() / 3)
+            .map(move |t| [self.get(t * 3), self.get(t * 3 + 1), self.get(t * 3 + 2)])
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
-    pub indices: Vec<u16>,
+    pub indices: Indices,
 }
 
 impl Mesh {
-    pub fn create(vertices: Vec<Vertex>, indices: Vec<u16>) -> Self {
-        Mesh { vertices, indices }
+    ///
+    /// Create a mesh, with indices as narrow as its vertex count allows. Fails if an index is
+    /// out of range, or there are more vertices than 32-bit indices can address.
+    ///
+    pub fn create(vertices: Vec<Vertex>, indices: &[usize]) -> Result<Self, Box<dyn Error>> {
+        if let Some(index) = indices.iter().find(|&&index| index >= vertices.len()) {
+            return Err(format!(
+                "index {} is out of range of the {} vertices",
+                index,
+                vertices.len()
+            )
+            .into());
+        }
+        let max_index = vertices.len().saturating_sub(1);
+        let indices = if max_index <= u16::MAX as usize {
+            Indices::U16(indices.iter().map(|&index| index as u16).collect())
+        } else if max_index <= u32::MAX as usize {
+            Indices::U32(indices.iter().map(|&index| index as u32).collect())
+        } else {
+            return Err(format!(
+                "{} vertices are more than 32-bit indices can address",
+                vertices.len()
+            )
+            .into());
+        };
+        Ok(Mesh { vertices, indices })
     }
 }
 
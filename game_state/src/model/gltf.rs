@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::gltf::buffer::Source as BufferSource;
+use ::gltf::image::Source as ImageSource;
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use ::gltf::{Document, Gltf};
+use nalgebra::Matrix4;
+
+use super::{Material, Mesh, Model, ModelScene, TextureCache, TextureMap, Vertex};
+use crate::create_next_identity;
+use crate::state::{SceneGraph, SceneNode};
+use crate::tree::{Node, RcNode};
+
+///
+/// Whether a file is read as glTF - `.gltf` (JSON) or `.glb` (binary)
+///
+pub fn is_gltf(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb"),
+        None => false,
+    }
+}
+
+///
+/// Load every mesh primitive placed by the glTF file's scene as a model. The nodes'
+/// transforms are multiplied into each model's `model_mat`, so a mesh used by several nodes
+/// gives a copy of its models for each.
+///
+/// Texture coordinates are flipped to the OBJ convention, with v going up. Only triangle
+/// primitives are read. Skins and animations aren't.
+///
+pub fn load(
+    filename: &str,
+    model_mat: Matrix4<f32>,
+    textures: &mut TextureCache,
+) -> Result<Vec<Model>, Box<dyn Error>> {
+    let mut file = GltfFile::open(filename)?;
+    let document = file.document.clone();
+    let scene = file.scene(&document)?;
+
+    let mut models = Vec::new();
+    // a stack, so in reverse to place the nodes in file order
+    let mut nodes = scene
+        .nodes()
+        .map(|node| (node, model_mat))
+        .collect::<Vec<_>>();
+    nodes.reverse();
+    while let Some((node, parent_mat)) = nodes.pop() {
+        let world = parent_mat * node_matrix(&node);
+        if let Some(mesh) = node.mesh() {
+            for model in file.mesh_models(&mesh, textures)? {
+                let mut model = model.clone();
+                model.id = create_next_identity();
+                model.model_mat = world;
+                models.push(model);
+            }
+        }
+        for child in node.children().collect::<Vec<_>>().into_iter().rev() {
+            nodes.push((child, world));
+        }
+    }
+    Ok(models)
+}
+
+///
+/// Load the glTF file's scene - the default one, or the first - keeping its node hierarchy
+///
+/// A node showing a mesh of several primitives gets a child node for each primitive after the
+/// first, like scenes do for multi-object OBJ files.
+///
+pub fn load_scene(
+    filename: &str,
+    textures: &mut TextureCache,
+) -> Result<ModelScene, Box<dyn Error>> {
+    let mut file = GltfFile::open(filename)?;
+    let document = file.document.clone();
+    let scene = file.scene(&document)?;
+
+    let root = Node::create(SceneNode::default(), None);
+    let mut nodes = scene
+        .nodes()
+        .map(|node| (node, root.clone()))
+        .collect::<Vec<_>>();
+    nodes.reverse();
+    let mut shared: HashMap<usize, Vec<Arc<Model>>> = HashMap::new();
+    while let Some((node, parent)) = nodes.pop() {
+        let objects = match node.mesh() {
+            Some(mesh) => match shared.get(&mesh.index()) {
+                Some(objects) => objects.clone(),
+                None => {
+                    let objects = file
+                        .mesh_models(&mesh, textures)?
+                        .iter()
+                        .cloned()
+                        .map(Arc::new)
+                        .collect::<Vec<_>>();
+                    shared.insert(mesh.index(), objects.clone());
+                    objects
+                }
+            },
+            None => Vec::new(),
+        };
+        let scene_node: RcNode<SceneNode> = Node::create(
+            SceneNode::new(objects.first().cloned(), node_matrix(&node)),
+            Some(&parent),
+        );
+        for object in objects.iter().skip(1) {
+            Node::create(
+                SceneNode::new(Some(object.clone()), Matrix4::identity()),
+                Some(&scene_node),
+            );
+        }
+        for child in node.children().collect::<Vec<_>>().into_iter().rev() {
+            nodes.push((child, scene_node.clone()));
+        }
+    }
+
+    let mut models = Vec::new();
+    let mut meshes = shared.into_iter().collect::<Vec<_>>();
+    meshes.sort_by_key(|(mesh, _)| *mesh);
+    for (_mesh, objects) in meshes {
+        models.extend(objects);
+    }
+    Ok(ModelScene {
+        graph: SceneGraph { root },
+        models,
+    })
+}
+
+///
+/// The files other than the glTF file its buffers are read from, for watching them for
+/// changes. Embedded buffers aren't included.
+///
+pub fn buffer_files(path: &Path) -> Vec<PathBuf> {
+    let gltf = match Gltf::open(path) {
+        Ok(gltf) => gltf,
+        Err(_) => return Vec::new(),
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    gltf.document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            BufferSource::Uri(uri) if !uri.starts_with("data:") => Some(dir.join(uri)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn node_matrix(node: &::gltf::Node) -> Matrix4<f32> {
+    Matrix4::from(node.transform().matrix())
+}
+
+// A glTF file and the contents of its buffers, with the models and materials read from it so
+// far
+struct GltfFile {
+    path: PathBuf,
+    document: Document,
+    // where each buffer was read from - the glTF file itself when embedded - and its contents
+    buffers: Vec<(PathBuf, Vec<u8>)>,
+    // by mesh index, one model for each primitive
+    meshes: HashMap<usize, Vec<Model>>,
+    // by material index, None being glTF's default material
+    materials: HashMap<Option<usize>, Material>,
+    // images embedded in the file, by image index
+    images: HashMap<usize, TextureMap>,
+}
+
+impl GltfFile {
+    fn open(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let path = PathBuf::from(filename);
+        let Gltf { document, mut blob } =
+            Gltf::open(&path).map_err(|err| format!("unable to read {:?}: {}", path, err))?;
+
+        let mut buffers = Vec::new();
+        for buffer in document.buffers() {
+            let (source, data) = match buffer.source() {
+                BufferSource::Bin => {
+                    let data = blob.take().ok_or_else(|| {
+                        format!("buffer {}: {:?} has no binary chunk", buffer.index(), path)
+                    })?;
+                    (path.clone(), data)
+                }
+                BufferSource::Uri(uri) => read_uri(&path, uri)
+                    .map_err(|err| format!("buffer {}: {}", buffer.index(), err))?,
+            };
+            if data.len() < buffer.length() {
+                return Err(format!(
+                    "buffer {}: expected {} bytes, {:?} has {}",
+                    buffer.index(),
+                    buffer.length(),
+                    source,
+                    data.len()
+                )
+                .into());
+            }
+            buffers.push((source, data));
+        }
+
+        Ok(GltfFile {
+            path,
+            document,
+            buffers,
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            images: HashMap::new(),
+        })
+    }
+
+    fn scene<'a>(&self, document: &'a Document) -> Result<::gltf::Scene<'a>, Box<dyn Error>> {
+        document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| format!("{:?} has no scene", self.path).into())
+    }
+
+    // The models of a mesh, one for each primitive, placed at the origin
+    fn mesh_models(
+        &mut self,
+        mesh: &::gltf::Mesh,
+        textures: &mut TextureCache,
+    ) -> Result<&[Model], Box<dyn Error>> {
+        if !self.meshes.contains_key(&mesh.index()) {
+            let mut models = Vec::new();
+            for primitive in mesh.primitives() {
+                let entry = format!(
+                    "{:?}, mesh {} primitive {}",
+                    self.path,
+                    mesh.index(),
+                    primitive.index()
+                );
+                let model = self
+                    .primitive_model(&primitive, textures)
+                    .map_err(|err| format!("{}: {}", entry, err))?;
+                models.push(model);
+            }
+            self.meshes.insert(mesh.index(), models);
+        }
+        Ok(&self.meshes[&mesh.index()])
+    }
+
+    fn primitive_model(
+        &mut self,
+        primitive: &::gltf::Primitive,
+        textures: &mut TextureCache,
+    ) -> Result<Model, Box<dyn Error>> {
+        if primitive.mode() != Mode::Triangles {
+            return Err(format!("{:?} primitives aren't supported", primitive.mode()).into());
+        }
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].1[..]));
+
+        let positions = reader
+            .read_positions()
+            .ok_or("primitive has no positions")?
+            .collect::<Vec<_>>();
+        if positions.is_empty() {
+            return Err("primitive has no vertices".into());
+        }
+        let normals = reader
+            .read_normals()
+            .map(|normals| normals.collect::<Vec<_>>())
+            .unwrap_or_default();
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect::<Vec<_>>(),
+        };
+
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &[x, y, z])| {
+                let [nx, ny, nz] = normals.get(i).cloned().unwrap_or([0.0, 0.0, 0.0]);
+                let [u, v] = uvs.get(i).cloned().unwrap_or([0.0, 0.0]);
+                Vertex::new((x, y, z), (u, 1.0 - v, 0.0), (nx, ny, nz))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Model {
+            filename: self.path.to_string_lossy().into_owned(),
+            object: 0,
+            id: create_next_identity(),
+            model_mat: Matrix4::identity(),
+            world_mat: Matrix4::identity(),
+            material: self.material(&primitive.material(), textures)?,
+            mesh: Mesh::create(vertices, &indices)?,
+        })
+    }
+
+    fn material(
+        &mut self,
+        material: &::gltf::Material,
+        textures: &mut TextureCache,
+    ) -> Result<Material, Box<dyn Error>> {
+        if let Some(loaded) = self.materials.get(&material.index()) {
+            return Ok(loaded.clone());
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, a] = pbr.base_color_factor();
+        let mut map = |texture: Option<::gltf::Texture>| {
+            texture
+                .map(|texture| self.image_map(&texture.source(), textures))
+                .transpose()
+        };
+        let loaded = Material {
+            name: match (material.name(), material.index()) {
+                (Some(name), _) => name.to_string(),
+                (None, Some(index)) => format!("material{}", index),
+                (None, None) => "default".to_string(),
+            },
+            diffuse: [r, g, b],
+            emissive: material.emissive_factor(),
+            // the alpha of opaque materials is ignored
+            opacity: match material.alpha_mode() {
+                AlphaMode::Opaque => 1.0,
+                _ => a,
+            },
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            diffuse_map: map(pbr.base_color_texture().map(|info| info.texture()))?,
+            normal_map: map(material.normal_texture().map(|normal| normal.texture()))?,
+            emissive_map: map(material.emissive_texture().map(|info| info.texture()))?,
+            metallic_roughness_map: map(pbr
+                .metallic_roughness_texture()
+                .map(|info| info.texture()))?,
+            ..Default::default()
+        };
+        self.materials.insert(material.index(), loaded.clone());
+        Ok(loaded)
+    }
+
+    // Image files go through the texture cache. Images in a buffer or data URI are decoded
+    // once per file.
+    fn image_map(
+        &mut self,
+        image: &::gltf::Image,
+        textures: &mut TextureCache,
+    ) -> Result<TextureMap, Box<dyn Error>> {
+        if let Some(map) = self.images.get(&image.index()) {
+            return Ok(map.clone());
+        }
+        let (path, data) = match image.source() {
+            ImageSource::Uri { uri, .. } if !uri.starts_with("data:") => {
+                let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+                return textures.load(&dir.join(uri));
+            }
+            ImageSource::Uri { uri, .. } => read_uri(&self.path, uri)?,
+            ImageSource::View { view, .. } => {
+                let (source, buffer) = &self.buffers[view.buffer().index()];
+                let bytes = buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or_else(|| format!("image {} is outside its buffer", image.index()))?;
+                (source.clone(), bytes.to_vec())
+            }
+        };
+        let decoded = image::load_from_memory(&data)
+            .map_err(|err| format!("unable to decode image {}: {}", image.index(), err))?;
+        let map = TextureMap {
+            path,
+            image: Arc::new(decoded),
+        };
+        self.images.insert(image.index(), map.clone());
+        Ok(map)
+    }
+}
+
+// Read a buffer or image URI - a base64 data URI, or a file relative to the glTF file.
+// Returns the file the data is from.
+fn read_uri(gltf: &Path, uri: &str) -> Result<(PathBuf, Vec<u8>), Box<dyn Error>> {
+    if uri.starts_with("data:") {
+        let encoded = match uri.find(";base64,") {
+            Some(start) => &uri[start + ";base64,".len()..],
+            None => return Err("only base64 data URIs are supported".into()),
+        };
+        let data = base64::decode(encoded).map_err(|err| format!("bad data URI: {}", err))?;
+        return Ok((gltf.to_path_buf(), data));
+    }
+    let path = gltf.parent().unwrap_or_else(|| Path::new("")).join(uri);
+    let data = fs::read(&path).map_err(|err| format!("unable to read {:?}: {}", path, err))?;
+    Ok((path, data))
+}
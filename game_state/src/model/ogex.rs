@@ -0,0 +1,570 @@
+use std::error::Error;
+use std::f32::consts::FRAC_PI_2;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use nalgebra::{Matrix4, Quaternion, Unit, UnitQuaternion, Vector3};
+
+use super::{Material, Mesh, Model, ModelScene, TextureCache, Vertex};
+use crate::create_next_identity;
+use crate::openddl::{self, Structure, Value};
+use crate::state::{SceneGraph, SceneNode};
+use crate::tree::Node;
+
+// the structures forming the node hierarchy
+const NODE_TYPES: &[&str] = &[
+    "Node",
+    "BoneNode",
+    "GeometryNode",
+    "CameraNode",
+    "LightNode",
+];
+
+///
+/// Whether a file is read as OpenGEX - `.ogex`
+///
+pub fn is_ogex(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("ogex"),
+        None => false,
+    }
+}
+
+///
+/// Load the geometry of every geometry node in an OpenGEX file as models - one for each index
+/// array of its mesh, with the material the node gives it. The nodes' transforms are
+/// multiplied into each model's `model_mat`.
+///
+/// The scene is scaled by its distance metric, and turned from z-up to y-up when it is z-up,
+/// like Blender exports. Only triangle meshes are read, and only their first level of detail.
+/// Skins, morph targets and animations aren't read.
+///
+pub fn load(
+    filename: &str,
+    model_mat: Matrix4<f32>,
+    textures: &mut TextureCache,
+) -> Result<Vec<Model>, Box<dyn Error>> {
+    let file = OgexFile::open(filename)?;
+    let mut loaded = Loaded::default();
+
+    let mut models = Vec::new();
+    // a stack, so in reverse to place the nodes in file order
+    let root_mat = model_mat * file.root_transform();
+    let mut nodes = file
+        .roots()
+        .map(|node| (node, root_mat))
+        .collect::<Vec<_>>();
+    nodes.reverse();
+    while let Some((node, parent_mat)) = nodes.pop() {
+        let at_node = |err: String| format!("{:?}: {}: {}", file.path, node.describe(), err);
+        let world = parent_mat * file.node_transform(node).map_err(at_node)?;
+        for model in file
+            .node_models(node, textures, &mut loaded)
+            .map_err(at_node)?
+        {
+            let mut model = (*model).clone();
+            model.id = create_next_identity();
+            model.model_mat = world;
+            models.push(model);
+        }
+        for child in node_children(node).rev() {
+            nodes.push((child, world));
+        }
+    }
+    Ok(models)
+}
+
+///
+/// Load an OpenGEX file keeping its node hierarchy. The root node holds the metric transform.
+///
+/// A geometry node of several index arrays gets a child node for each one after the first,
+/// like scenes do for multi-object OBJ files.
+///
+pub fn load_scene(
+    filename: &str,
+    textures: &mut TextureCache,
+) -> Result<ModelScene, Box<dyn Error>> {
+    let file = OgexFile::open(filename)?;
+    let mut loaded = Loaded::default();
+
+    let root = Node::create(SceneNode::new(None, file.root_transform()), None);
+    let mut nodes = file
+        .roots()
+        .map(|node| (node, root.clone()))
+        .collect::<Vec<_>>();
+    nodes.reverse();
+    while let Some((node, parent)) = nodes.pop() {
+        let at_node = |err: String| format!("{:?}: {}: {}", file.path, node.describe(), err);
+        let local = file.node_transform(node).map_err(at_node)?;
+        let objects = file
+            .node_models(node, textures, &mut loaded)
+            .map_err(at_node)?;
+        let scene_node = Node::create(
+            SceneNode::new(objects.first().cloned(), local),
+            Some(&parent),
+        );
+        for object in objects.iter().skip(1) {
+            Node::create(
+                SceneNode::new(Some(object.clone()), Matrix4::identity()),
+                Some(&scene_node),
+            );
+        }
+        for child in node_children(node).rev() {
+            nodes.push((child, scene_node.clone()));
+        }
+    }
+
+    let models = loaded
+        .geometry
+        .into_iter()
+        .flat_map(|(_key, objects)| objects)
+        .collect();
+    Ok(ModelScene {
+        graph: SceneGraph { root },
+        models,
+    })
+}
+
+fn node_children(node: &Structure) -> impl DoubleEndedIterator<Item = &Structure> {
+    node.children
+        .iter()
+        .filter(|child| NODE_TYPES.contains(&child.identifier.as_str()))
+}
+
+// a geometry object, and the materials a node gives it
+type GeometryKey = (String, Vec<Option<String>>);
+
+// The models and materials read so far
+#[derive(Default)]
+struct Loaded {
+    // in the order they were loaded
+    geometry: Vec<(GeometryKey, Vec<Arc<Model>>)>,
+    materials: Vec<(String, Material)>,
+}
+
+struct OgexFile {
+    path: PathBuf,
+    structures: Vec<Structure>,
+    // from the Metric structures
+    distance: f32,
+    angle: f32,
+    z_up: bool,
+}
+
+impl OgexFile {
+    fn open(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let path = PathBuf::from(filename);
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| format!("unable to read {:?}: {}", path, err))?;
+        let structures = openddl::parse(&contents).map_err(|err| format!("{:?}: {}", path, err))?;
+
+        let mut file = OgexFile {
+            path,
+            structures,
+            distance: 1.0,
+            angle: 1.0,
+            z_up: true,
+        };
+        for metric in file.structures.iter().filter(|s| s.identifier == "Metric") {
+            let at_metric = |err: String| format!("{:?}: Metric: {}", file.path, err);
+            match metric.string_property("key", "") {
+                "distance" => file.distance = first_float(metric).map_err(at_metric)?,
+                "angle" => file.angle = first_float(metric).map_err(at_metric)?,
+                "up" => {
+                    file.z_up = match metric.strings().first() {
+                        Some(&"z") => true,
+                        Some(&"y") => false,
+                        other => return Err(at_metric(format!("bad up axis {:?}", other)).into()),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(file)
+    }
+
+    fn roots(&self) -> impl DoubleEndedIterator<Item = &Structure> {
+        self.structures
+            .iter()
+            .filter(|s| NODE_TYPES.contains(&s.identifier.as_str()))
+    }
+
+    fn named(&self, name: &str) -> Result<&Structure, String> {
+        self.structures
+            .iter()
+            .find(|s| s.name.as_deref() == Some(name))
+            .ok_or_else(|| format!("nothing is named {}", name))
+    }
+
+    // Scales the scene to the metric distance, and turns a z-up scene to y-up
+    fn root_transform(&self) -> Matrix4<f32> {
+        let up = if self.z_up {
+            Matrix4::from_axis_angle(&Vector3::x_axis(), -FRAC_PI_2)
+        } else {
+            Matrix4::identity()
+        };
+        up * Matrix4::new_scaling(self.distance)
+    }
+
+    // The product of a node's transforms, in the order they are given
+    fn node_transform(&self, node: &Structure) -> Result<Matrix4<f32>, String> {
+        let mut transform = Matrix4::identity();
+        for child in &node.children {
+            let matrix = match child.identifier.as_str() {
+                "Transform" => {
+                    let values = floats(child)?;
+                    if values.is_empty() || values.len() % 16 != 0 {
+                        return Err(format!("Transform has {} values", values.len()));
+                    }
+                    values.chunks(16).fold(Matrix4::identity(), |m, columns| {
+                        m * Matrix4::from_column_slice(columns)
+                    })
+                }
+                "Translation" => {
+                    let [x, y, z] = vector(child, child.string_property("kind", "xyz"), 0.0)?;
+                    Matrix4::new_translation(&Vector3::new(x, y, z))
+                }
+                "Scale" => {
+                    let [x, y, z] = vector(child, child.string_property("kind", "xyz"), 1.0)?;
+                    Matrix4::new_nonuniform_scaling(&Vector3::new(x, y, z))
+                }
+                "Rotation" => self.rotation(child, child.string_property("kind", "axis"))?,
+                _ => continue,
+            };
+            if child.property("object") == Some(&Value::Bool(true)) {
+                return Err(format!(
+                    "{} applies to the object only, which isn't supported",
+                    child.identifier
+                ));
+            }
+            transform *= matrix;
+        }
+        Ok(transform)
+    }
+
+    fn rotation(&self, rotation: &Structure, kind: &str) -> Result<Matrix4<f32>, String> {
+        let values = floats(rotation)?;
+        let expected = match kind {
+            "x" | "y" | "z" => 1,
+            "axis" | "quaternion" => 4,
+            _ => return Err(format!("unknown Rotation kind {:?}", kind)),
+        };
+        if values.len() != expected {
+            return Err(format!(
+                "Rotation (kind = {:?}) has {} values, where {} were expected",
+                kind,
+                values.len(),
+                expected
+            ));
+        }
+        let angle = values[0] * self.angle;
+        Ok(match kind {
+            "x" => Matrix4::from_axis_angle(&Vector3::x_axis(), angle),
+            "y" => Matrix4::from_axis_angle(&Vector3::y_axis(), angle),
+            "z" => Matrix4::from_axis_angle(&Vector3::z_axis(), angle),
+            "axis" => {
+                let axis = Unit::new_normalize(Vector3::new(values[1], values[2], values[3]));
+                Matrix4::from_axis_angle(&axis, angle)
+            }
+            _ => {
+                let [x, y, z, w] = [values[0], values[1], values[2], values[3]];
+                UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)).to_homogeneous()
+            }
+        })
+    }
+
+    // The models of a geometry node, placed at the origin - none for other nodes
+    fn node_models(
+        &self,
+        node: &Structure,
+        textures: &mut TextureCache,
+        loaded: &mut Loaded,
+    ) -> Result<Vec<Arc<Model>>, String> {
+        if node.identifier != "GeometryNode" {
+            return Ok(Vec::new());
+        }
+        let geometry = node
+            .child("ObjectRef")
+            .and_then(|object| object.reference())
+            .ok_or("GeometryNode has no ObjectRef")?;
+
+        let mut materials: Vec<Option<String>> = Vec::new();
+        for material_ref in node.children_with("MaterialRef") {
+            let index = match material_ref.property("index") {
+                Some(Value::Number(index)) => *index as usize,
+                _ => 0,
+            };
+            if materials.len() <= index {
+                materials.resize(index + 1, None);
+            }
+            materials[index] = material_ref.reference().map(str::to_string);
+        }
+
+        let key = (geometry.to_string(), materials);
+        if let Some((_, models)) = loaded.geometry.iter().find(|(k, _)| *k == key) {
+            return Ok(models.clone());
+        }
+        let models = self
+            .geometry_models(geometry, &key.1, textures, loaded)?
+            .into_iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        loaded.geometry.push((key, models.clone()));
+        Ok(models)
+    }
+
+    fn geometry_models(
+        &self,
+        name: &str,
+        materials: &[Option<String>],
+        textures: &mut TextureCache,
+        loaded: &mut Loaded,
+    ) -> Result<Vec<Model>, String> {
+        let geometry = self.named(name)?;
+        let at_geometry = |err: String| format!("{}: {}", geometry.describe(), err);
+        let mesh = geometry
+            .child("Mesh")
+            .ok_or_else(|| at_geometry("has no Mesh".to_string()))?;
+        let primitive = mesh.string_property("primitive", "triangles");
+        if primitive != "triangles" {
+            return Err(at_geometry(format!(
+                "{} meshes aren't supported",
+                primitive
+            )));
+        }
+
+        let mut positions = None;
+        let mut normals = None;
+        let mut uvs = None;
+        for array in mesh.children_with("VertexArray") {
+            match array.string_property("attrib", "") {
+                "position" => positions = Some(vectors(array, 3).map_err(at_geometry)?),
+                "normal" => normals = Some(vectors(array, 3).map_err(at_geometry)?),
+                "texcoord" | "texcoord[0]" => uvs = Some(vectors(array, 2).map_err(at_geometry)?),
+                _ => {}
+            }
+        }
+        let positions =
+            positions.ok_or_else(|| at_geometry("has no position VertexArray".to_string()))?;
+        for (attrib, array) in &[("normal", &normals), ("texcoord", &uvs)] {
+            if let Some(array) = array {
+                if array.len() != positions.len() {
+                    return Err(at_geometry(format!(
+                        "{} {} vertices, and {} positions",
+                        array.len(),
+                        attrib,
+                        positions.len()
+                    )));
+                }
+            }
+        }
+        let vertices = (0..positions.len())
+            .map(|i| {
+                let p = &positions[i];
+                let n = normals.as_ref().map(|n| &n[i][..]).unwrap_or(&[0.0; 3]);
+                let uv = uvs.as_ref().map(|uv| &uv[i][..]).unwrap_or(&[0.0; 2]);
+                Vertex::new((p[0], p[1], p[2]), (uv[0], uv[1], 0.0), (n[0], n[1], n[2]))
+            })
+            .collect::<Vec<_>>();
+
+        // each index array is drawn with its own material
+        let mut index_arrays = Vec::new();
+        for array in mesh.children_with("IndexArray") {
+            let material = match array.property("material") {
+                Some(Value::Number(index)) => *index as usize,
+                _ => 0,
+            };
+            let mut indices = indices(array).map_err(at_geometry)?;
+            if array.string_property("front", "ccw") == "cw" {
+                for triangle in indices.chunks_mut(3) {
+                    triangle.reverse();
+                }
+            }
+            index_arrays.push((material, indices));
+        }
+        if index_arrays.is_empty() {
+            if vertices.len() % 3 != 0 {
+                return Err(at_geometry(format!(
+                    "has no IndexArray, and {} vertices - not whole triangles",
+                    vertices.len()
+                )));
+            }
+            index_arrays.push((0, (0..vertices.len()).collect()));
+        }
+
+        let mut models = Vec::new();
+        for (material, indices) in index_arrays {
+            let material = match materials.get(material).cloned().flatten() {
+                Some(name) => self.material(&name, textures, loaded)?,
+                None => Material::default(),
+            };
+            models.push(Model {
+                filename: self.path.to_string_lossy().into_owned(),
+                object: 0,
+                id: create_next_identity(),
+                model_mat: Matrix4::identity(),
+                world_mat: Matrix4::identity(),
+                material,
+                mesh: Mesh::create(vertices.clone(), &indices)
+                    .map_err(|err| at_geometry(err.to_string()))?,
+            });
+        }
+        Ok(models)
+    }
+
+    fn material(
+        &self,
+        name: &str,
+        textures: &mut TextureCache,
+        loaded: &mut Loaded,
+    ) -> Result<Material, String> {
+        if let Some((_, material)) = loaded.materials.iter().find(|(n, _)| n == name) {
+            return Ok(material.clone());
+        }
+        let structure = self.named(name)?;
+        let at_material = |err: String| format!("{}: {}", structure.describe(), err);
+        if structure.identifier != "Material" {
+            return Err(format!("{} isn't a Material", structure.describe()));
+        }
+
+        let mut material = Material {
+            name: match structure.child("Name").map(|n| n.strings()) {
+                Some(names) if !names.is_empty() => names[0].to_string(),
+                _ => name.trim_start_matches('$').to_string(),
+            },
+            ..Default::default()
+        };
+        for color in structure.children_with("Color") {
+            let values = floats(color).map_err(at_material)?;
+            if values.len() < 3 {
+                return Err(at_material(format!("Color has {} values", values.len())));
+            }
+            let rgb = [values[0], values[1], values[2]];
+            match color.string_property("attrib", "") {
+                "diffuse" => {
+                    material.diffuse = rgb;
+                    if let Some(alpha) = values.get(3) {
+                        material.opacity = *alpha;
+                    }
+                }
+                "specular" => material.specular = rgb,
+                "emission" => material.emissive = rgb,
+                "opacity" => material.opacity = values[0],
+                "transparency" => material.opacity = 1.0 - values[0],
+                _ => {}
+            }
+        }
+        for param in structure.children_with("Param") {
+            if param.string_property("attrib", "") == "specular_power" {
+                material.shininess = first_float(param).map_err(at_material)?;
+            }
+        }
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        for texture in structure.children_with("Texture") {
+            let file = match texture.strings().first() {
+                Some(file) => dir.join(file),
+                None => return Err(at_material("Texture has no file name".to_string())),
+            };
+            let slot = match texture.string_property("attrib", "") {
+                "diffuse" => &mut material.diffuse_map,
+                "normal" => &mut material.normal_map,
+                "specular" => &mut material.specular_map,
+                "emission" => &mut material.emissive_map,
+                _ => continue,
+            };
+            *slot = Some(
+                textures
+                    .load(&file)
+                    .map_err(|err| at_material(err.to_string()))?,
+            );
+        }
+
+        loaded.materials.push((name.to_string(), material.clone()));
+        Ok(material)
+    }
+}
+
+fn floats(structure: &Structure) -> Result<Vec<f32>, String> {
+    Ok(structure.numbers()?.into_iter().map(|n| n as f32).collect())
+}
+
+// An IndexArray's indices, as whole triangles. Read as f64, which holds every u32 index exactly.
+fn indices(array: &Structure) -> Result<Vec<usize>, String> {
+    let indices = array
+        .numbers()?
+        .into_iter()
+        .map(|index| {
+            if index < 0.0 || index.fract() != 0.0 || index > u32::MAX as f64 {
+                Err(format!("IndexArray holds {}, which isn't an index", index))
+            } else {
+                Ok(index as usize)
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if indices.len() % 3 != 0 {
+        return Err(format!(
+            "IndexArray has {} indices - not whole triangles",
+            indices.len()
+        ));
+    }
+    Ok(indices)
+}
+
+fn first_float(structure: &Structure) -> Result<f32, String> {
+    floats(structure)?
+        .first()
+        .cloned()
+        .ok_or_else(|| format!("{} has no value", structure.describe()))
+}
+
+// A Translation or Scale, by its kind: the value of one axis, or all three
+fn vector(structure: &Structure, kind: &str, others: f32) -> Result<[f32; 3], String> {
+    let values = floats(structure)?;
+    let axis = match kind {
+        "x" => 0,
+        "y" => 1,
+        "z" => 2,
+        "xyz" if values.len() == 3 => return Ok([values[0], values[1], values[2]]),
+        "xyz" => {
+            return Err(format!(
+                "{} has {} values, where 3 were expected",
+                structure.identifier,
+                values.len()
+            ))
+        }
+        _ => return Err(format!("unknown {} kind {:?}", structure.identifier, kind)),
+    };
+    if values.len() != 1 {
+        return Err(format!(
+            "{} (kind = {:?}) has {} values, where 1 was expected",
+            structure.identifier,
+            kind,
+            values.len()
+        ));
+    }
+    let mut vector = [others; 3];
+    vector[axis] = values[0];
+    Ok(vector)
+}
+
+// The vectors of a vertex array, cut to `size` components
+fn vectors(array: &Structure, size: usize) -> Result<Vec<Vec<f32>>, String> {
+    let values = floats(array)?;
+    let stride = array
+        .primitive()
+        .and_then(|primitive| primitive.array_size)
+        .unwrap_or(1);
+    if stride < size {
+        return Err(format!(
+            "{} VertexArray has {} components, where {} were expected",
+            array.string_property("attrib", ""),
+            stride,
+            size
+        ));
+    }
+    Ok(values
+        .chunks(stride)
+        .map(|vector| vector[..size].to_vec())
+        .collect())
+}
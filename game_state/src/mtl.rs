@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///
+/// MtlMaterial - a material as written in an MTL file, before its texture maps are loaded
+///
+/// Understands the colors (`Ka`, `Kd`, `Ks`, `Ke`), shininess (`Ns`), opacity (`d`, or `Tr`
+/// for transparency) and the diffuse, normal, specular and emissive maps (`map_Kd`,
+/// `map_Bump`/`bump`/`norm`, `map_Ks`, `map_Ke`). Other statements are ignored. Map options
+/// (`-bm 1.0`, ...) are skipped - the file name is the last word of the statement.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    pub opacity: f32,
+    pub diffuse_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    ///
+    /// A material with the defaults of the MTL format, for statements that are left out
+    ///
+    pub fn new(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_string(),
+            ambient: [0.2, 0.2, 0.2],
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [1.0, 1.0, 1.0],
+            emissive: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+            normal_map: None,
+            specular_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+///
+/// Read a material library. Texture map paths are made relative to the working directory,
+/// from being relative to the file.
+///
+pub fn load(path: &Path) -> Result<Vec<MtlMaterial>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("unable to read material library {:?}: {}", path, err))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = parse(&contents).map_err(|err| format!("{:?}: {}", path, err))?;
+    for material in materials.iter_mut() {
+        let maps = vec![
+            &mut material.diffuse_map,
+            &mut material.normal_map,
+            &mut material.specular_map,
+            &mut material.emissive_map,
+        ];
+        for map in maps.into_iter().flatten() {
+            let relative_to_dir = dir.join(&*map);
+            *map = relative_to_dir;
+        }
+    }
+    Ok(materials)
+}
+
+///
+/// Parse the materials of an MTL file. Errors give the line they are on.
+///
+pub fn parse(contents: &str) -> Result<Vec<MtlMaterial>, Box<dyn Error>> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let statement = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+
+        if statement == "newmtl" {
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(format!("line {}: newmtl without a name", line_number).into());
+            }
+            materials.push(MtlMaterial::new(&name));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => {
+                return Err(format!(
+                    "line {}: {} before the first newmtl",
+                    line_number, statement
+                )
+                .into())
+            }
+        };
+        let at_line = |err: String| format!("line {}: {}", line_number, err);
+        match statement {
+            "Ka" => material.ambient = color(&args).map_err(at_line)?,
+            "Kd" => material.diffuse = color(&args).map_err(at_line)?,
+            "Ks" => material.specular = color(&args).map_err(at_line)?,
+            "Ke" => material.emissive = color(&args).map_err(at_line)?,
+            "Ns" => material.shininess = number(&args).map_err(at_line)?,
+            "d" => material.opacity = number(&args).map_err(at_line)?,
+            "Tr" => material.opacity = 1.0 - number(&args).map_err(at_line)?,
+            "map_Kd" => material.diffuse_map = Some(map(&args).map_err(at_line)?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_map = Some(map(&args).map_err(at_line)?)
+            }
+            "map_Ks" => material.specular_map = Some(map(&args).map_err(at_line)?),
+            "map_Ke" => material.emissive_map = Some(map(&args).map_err(at_line)?),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+fn number(args: &[&str]) -> Result<f32, String> {
+    match args.first() {
+        Some(arg) => arg
+            .parse()
+            .map_err(|_| format!("expected a number, found {:?}", arg)),
+        None => Err("expected a number".to_string()),
+    }
+}
+
+fn color(args: &[&str]) -> Result<[f32; 3], String> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(format!("{} colors aren't supported", args[0]));
+    }
+    let r = number(args)?;
+    // a single value is grey
+    let g = match args.get(1) {
+        Some(_) => number(&args[1..])?,
+        None => r,
+    };
+    let b = match args.get(2) {
+        Some(_) => number(&args[2..])?,
+        None => g,
+    };
+    Ok([r, g, b])
+}
+
+fn map(args: &[&str]) -> Result<PathBuf, String> {
+    args.last()
+        .map(PathBuf::from)
+        .ok_or_else(|| "expected a texture file name".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_materials() {
+        let materials = parse(
+            "# two materials
+            newmtl plain
+            Kd 0.5 0.25 1.0
+            Ns 96.0
+            Tr 0.25
+
+            newmtl mapped
+            Ka 0.1
+            map_Kd -s 1 1 1 textures/wall.png
+            map_Bump -bm 0.5 wall_normal.png
+            map_Ks wall_spec.png
+            map_Ke wall_glow.png
+            illum 2",
+        )
+        .unwrap();
+        assert_eq!(materials.len(), 2);
+
+        let plain = &materials[0];
+        assert_eq!(plain.name, "plain");
+        assert_eq!(plain.diffuse, [0.5, 0.25, 1.0]);
+        assert_eq!(plain.shininess, 96.0);
+        assert_eq!(plain.opacity, 0.75);
+        assert_eq!(plain.diffuse_map, None);
+
+        let mapped = &materials[1];
+        assert_eq!(mapped.ambient, [0.1, 0.1, 0.1]);
+        assert_eq!(mapped.diffuse, MtlMaterial::new("").diffuse);
+        assert_eq!(mapped.diffuse_map, Some(PathBuf::from("textures/wall.png")));
+        assert_eq!(mapped.normal_map, Some(PathBuf::from("wall_normal.png")));
+        assert_eq!(mapped.specular_map, Some(PathBuf::from("wall_spec.png")));
+        assert_eq!(mapped.emissive_map, Some(PathBuf::from("wall_glow.png")));
+    }
+
+    #[test]
+    fn parse_errors_give_the_line() {
+        let err = parse("newmtl a\nKd 1.0 red 0.0").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected a number, found \"red\"");
+        let err = parse("Kd 1.0 1.0 1.0").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: Kd before the first newmtl");
+    }
+}
@@ -0,0 +1,571 @@
+use std::error::Error;
+
+///
+/// Structure - a structure of an OpenDDL file, the data language OpenGEX is written in
+///
+/// Structures with a custom identifier (`GeometryNode $node1 { ... }`) have properties and
+/// child structures. Primitive structures (`float[3] { {1.0, 2.0, 3.0} }`) have the data type
+/// as identifier and hold data instead: `data` is flattened, with `array_size` values to each
+/// subarray.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    pub identifier: String,
+    /// with its `$` (global) or `%` (local) prefix
+    pub name: Option<String>,
+    pub properties: Vec<(String, Value)>,
+    pub children: Vec<Structure>,
+    pub array_size: Option<usize>,
+    pub data: Vec<Value>,
+}
+
+///
+/// A property or data value. Numbers of every type are read as f64.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// a reference to a named structure, or `null`
+    Ref(Option<String>),
+    Type(String),
+}
+
+impl Structure {
+    pub fn property(&self, key: &str) -> Option<&Value> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    ///
+    /// A string property, or `default` when it is left out
+    ///
+    pub fn string_property<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        match self.property(key) {
+            Some(Value::String(value)) => value,
+            _ => default,
+        }
+    }
+
+    pub fn children_with<'a>(
+        &'a self,
+        identifier: &'a str,
+    ) -> impl Iterator<Item = &'a Structure> + 'a {
+        self.children
+            .iter()
+            .filter(move |child| child.identifier == identifier)
+    }
+
+    pub fn child(&self, identifier: &str) -> Option<&Structure> {
+        self.children
+            .iter()
+            .find(|child| child.identifier == identifier)
+    }
+
+    ///
+    /// The data of the first primitive structure inside this one - eg. the `float[3]` of a
+    /// `VertexArray`
+    ///
+    pub fn primitive(&self) -> Option<&Structure> {
+        self.children.iter().find(|child| child.is_primitive())
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        is_data_type(&self.identifier)
+    }
+
+    ///
+    /// The numbers of the first primitive structure inside this one
+    ///
+    pub fn numbers(&self) -> Result<Vec<f64>, String> {
+        let primitive = self
+            .primitive()
+            .ok_or_else(|| format!("{} has no data", self.describe()))?;
+        primitive
+            .data
+            .iter()
+            .map(|value| match value {
+                Value::Number(n) => Ok(*n),
+                other => Err(format!(
+                    "{} holds {:?}, where numbers were expected",
+                    self.describe(),
+                    other
+                )),
+            })
+            .collect()
+    }
+
+    ///
+    /// The strings of the first primitive structure inside this one
+    ///
+    pub fn strings(&self) -> Vec<&str> {
+        self.primitive()
+            .map(|primitive| {
+                primitive
+                    .data
+                    .iter()
+                    .filter_map(|value| match value {
+                        Value::String(s) => Some(s.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    ///
+    /// The reference held by the first primitive structure inside this one
+    ///
+    pub fn reference(&self) -> Option<&str> {
+        self.primitive()?.data.iter().find_map(|value| match value {
+            Value::Ref(Some(name)) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    ///
+    /// The identifier and name, to point out the structure in errors
+    ///
+    pub fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} {}", self.identifier, name),
+            None => self.identifier.clone(),
+        }
+    }
+}
+
+///
+/// Parse the top-level structures of an OpenDDL file. Errors give the line they are on.
+///
+pub fn parse(contents: &str) -> Result<Vec<Structure>, Box<dyn Error>> {
+    let mut parser = Parser {
+        chars: contents.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser
+        .structures(false)
+        .map_err(|err| format!("line {}: {}", parser.line, err).into())
+}
+
+// the long and short names of each primitive data type
+#[rustfmt::skip]
+const DATA_TYPES: &[&str] = &[
+    "bool", "b",
+    "int8", "i8", "int16", "i16", "int32", "i32", "int64", "i64",
+    "unsigned_int8", "u8", "unsigned_int16", "u16", "unsigned_int32", "u32", "unsigned_int64", "u64",
+    "half", "float16", "h", "f16", "float", "float32", "f", "f32", "double", "float64", "d", "f64",
+    "string", "s", "ref", "r", "type", "t",
+];
+
+fn is_data_type(identifier: &str) -> bool {
+    DATA_TYPES.contains(&identifier)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.next();
+                }
+                Some('/') if self.starts_with("//") => {
+                    while !matches!(self.next(), Some('\n') | None) {}
+                }
+                Some('/') if self.starts_with("/*") => {
+                    let start = self.line;
+                    self.pos += 2;
+                    while !self.starts_with("*/") {
+                        if self.next().is_none() {
+                            return Err(format!("comment from line {} isn't closed", start));
+                        }
+                    }
+                    self.pos += 2;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace()?;
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+            None => Err(format!(
+                "expected '{}', found the end of the file",
+                expected
+            )),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        self.skip_whitespace()?;
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let valid =
+                c == '_' || c.is_ascii_alphabetic() || (self.pos > start && c.is_ascii_digit());
+            if !valid {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return match self.peek() {
+                Some(c) => Err(format!("expected an identifier, found '{}'", c)),
+                None => Err("expected an identifier, found the end of the file".to_string()),
+            };
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn structures(&mut self, in_braces: bool) -> Result<Vec<Structure>, String> {
+        let mut structures = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            match self.peek() {
+                None if in_braces => return Err("a structure isn't closed".to_string()),
+                None => return Ok(structures),
+                Some('}') if in_braces => {
+                    self.next();
+                    return Ok(structures);
+                }
+                Some(_) => structures.push(self.structure()?),
+            }
+        }
+    }
+
+    fn structure(&mut self) -> Result<Structure, String> {
+        let identifier = self.identifier()?;
+        let mut structure = Structure {
+            identifier,
+            name: None,
+            properties: Vec::new(),
+            children: Vec::new(),
+            array_size: None,
+            data: Vec::new(),
+        };
+
+        if structure.is_primitive() {
+            self.skip_whitespace()?;
+            if self.peek() == Some('[') {
+                self.next();
+                self.skip_whitespace()?;
+                let size = self.number()?;
+                if size < 1.0 || size.fract() != 0.0 {
+                    return Err(format!("bad array size {}", size));
+                }
+                structure.array_size = Some(size as usize);
+                self.expect(']')?;
+            }
+            structure.name = self.name()?;
+            self.expect('{')?;
+            structure.data = self.data(&structure.identifier, structure.array_size)?;
+            return Ok(structure);
+        }
+
+        structure.name = self.name()?;
+        self.skip_whitespace()?;
+        if self.peek() == Some('(') {
+            self.next();
+            structure.properties = self.properties()?;
+        }
+        self.expect('{')?;
+        structure.children = self.structures(true)?;
+        Ok(structure)
+    }
+
+    fn name(&mut self) -> Result<Option<String>, String> {
+        self.skip_whitespace()?;
+        match self.peek() {
+            Some(prefix) if prefix == '$' || prefix == '%' => {
+                self.next();
+                Ok(Some(format!("{}{}", prefix, self.identifier()?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // after the opening parenthesis
+    fn properties(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let mut properties = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some(')') {
+            self.next();
+            return Ok(properties);
+        }
+        loop {
+            let key = self.identifier()?;
+            self.skip_whitespace()?;
+            // a property without a value is a true bool
+            let value = if self.peek() == Some('=') {
+                self.next();
+                self.skip_whitespace()?;
+                self.any_value()?
+            } else {
+                Value::Bool(true)
+            };
+            properties.push((key, value));
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(',') => continue,
+                Some(')') => return Ok(properties),
+                Some(c) => return Err(format!("expected ',' or ')', found '{}'", c)),
+                None => return Err("properties aren't closed".to_string()),
+            }
+        }
+    }
+
+    // after the opening brace
+    fn data(&mut self, data_type: &str, array_size: Option<usize>) -> Result<Vec<Value>, String> {
+        let mut data = Vec::new();
+        self.skip_whitespace()?;
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(data);
+        }
+        loop {
+            match array_size {
+                Some(size) => {
+                    self.expect('{')?;
+                    let mut count = 0;
+                    loop {
+                        data.push(self.value(data_type)?);
+                        count += 1;
+                        self.skip_whitespace()?;
+                        match self.next() {
+                            Some(',') => continue,
+                            Some('}') => break,
+                            Some(c) => return Err(format!("expected ',' or '}}', found '{}'", c)),
+                            None => return Err("a subarray isn't closed".to_string()),
+                        }
+                    }
+                    if count != size {
+                        return Err(format!(
+                            "subarray of {} values, where {} were expected",
+                            count, size
+                        ));
+                    }
+                }
+                None => data.push(self.value(data_type)?),
+            }
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(data),
+                Some(c) => return Err(format!("expected ',' or '}}', found '{}'", c)),
+                None => return Err("data isn't closed".to_string()),
+            }
+        }
+    }
+
+    fn value(&mut self, data_type: &str) -> Result<Value, String> {
+        self.skip_whitespace()?;
+        match data_type {
+            "bool" | "b" => match self.identifier()?.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                other => Err(format!("expected true or false, found {}", other)),
+            },
+            "string" | "s" => self.string().map(Value::String),
+            "ref" | "r" => self.reference().map(Value::Ref),
+            "type" | "t" => self.identifier().map(Value::Type),
+            _ => self.number().map(Value::Number),
+        }
+    }
+
+    // a property value, of any type
+    fn any_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('$') | Some('%') => self.reference().map(Value::Ref),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let identifier = self.identifier()?;
+                Ok(match identifier.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "null" => Value::Ref(None),
+                    _ => Value::Type(identifier),
+                })
+            }
+            _ => self.number().map(Value::Number),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let negative = match self.peek() {
+            Some('-') => {
+                self.next();
+                true
+            }
+            Some('+') => {
+                self.next();
+                false
+            }
+            _ => false,
+        };
+        let start = self.pos;
+        let value = if self.peek() == Some('\'') {
+            self.next();
+            let c = self.next().ok_or("a character literal isn't closed")?;
+            if self.next() != Some('\'') {
+                return Err("a character literal isn't closed".to_string());
+            }
+            c as u32 as f64
+        } else {
+            while let Some(c) = self.peek() {
+                let exponent_sign = (c == '+' || c == '-')
+                    && matches!(self.chars.get(self.pos - 1), Some('e') | Some('E'));
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            let literal = self.chars[start..self.pos]
+                .iter()
+                .filter(|&&c| c != '_')
+                .collect::<String>();
+            let radix = match literal.get(..2) {
+                Some("0x") | Some("0X") => Some(16),
+                Some("0o") | Some("0O") => Some(8),
+                Some("0b") | Some("0B") => Some(2),
+                _ => None,
+            };
+            let parsed = match radix {
+                Some(radix) => u64::from_str_radix(&literal[2..], radix)
+                    .map(|n| n as f64)
+                    .ok(),
+                None => literal.parse::<f64>().ok(),
+            };
+            match parsed {
+                Some(value) => value,
+                None if literal.is_empty() => match self.peek() {
+                    Some(c) => return Err(format!("expected a number, found '{}'", c)),
+                    None => return Err("expected a number, found the end of the file".into()),
+                },
+                None => return Err(format!("bad number {}", literal)),
+            }
+        };
+        Ok(if negative { -value } else { value })
+    }
+
+    // one or more string literals, which are joined
+    fn string(&mut self) -> Result<String, String> {
+        let mut string = String::new();
+        while self.peek() == Some('"') {
+            self.next();
+            loop {
+                match self.next() {
+                    Some('"') => break,
+                    Some('\\') => match self.next() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some('r') => string.push('\r'),
+                        Some('0') => string.push('\0'),
+                        Some(c) => string.push(c),
+                        None => return Err("a string isn't closed".to_string()),
+                    },
+                    Some(c) => string.push(c),
+                    None => return Err("a string isn't closed".to_string()),
+                }
+            }
+            self.skip_whitespace()?;
+        }
+        Ok(string)
+    }
+
+    fn reference(&mut self) -> Result<Option<String>, String> {
+        match self.peek() {
+            Some('$') | Some('%') => {
+                let mut reference = String::new();
+                while let Some(name) = self.name()? {
+                    reference.push_str(&name);
+                }
+                Ok(Some(reference))
+            }
+            _ => match self.identifier()?.as_str() {
+                "null" => Ok(None),
+                other => Err(format!("expected a reference, found {}", other)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_structures() {
+        let structures = parse(
+            r#"
+            // a comment
+            Metric (key = "up") {string {"z"}}
+            GeometryNode $node1 /* another */ {
+                ObjectRef {ref {$geometry1}}
+                IndexArray (material = 1, front) {
+                    unsigned_int32[3] { {0, 1, 2}, {0x2, 3, 0b0} }
+                }
+                float {-1.5e2, 2_000}
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(structures.len(), 2);
+        assert_eq!(structures[0].string_property("key", ""), "up");
+        assert_eq!(structures[0].strings(), ["z"]);
+
+        let node = &structures[1];
+        assert_eq!(node.name.as_deref(), Some("$node1"));
+        assert_eq!(
+            node.child("ObjectRef").unwrap().reference(),
+            Some("$geometry1")
+        );
+        let indices = node.child("IndexArray").unwrap();
+        assert_eq!(indices.property("material"), Some(&Value::Number(1.0)));
+        assert_eq!(indices.property("front"), Some(&Value::Bool(true)));
+        assert_eq!(indices.primitive().unwrap().array_size, Some(3));
+        assert_eq!(indices.numbers().unwrap(), [0.0, 1.0, 2.0, 2.0, 3.0, 0.0]);
+        assert_eq!(node.numbers().unwrap(), [-150.0, 2000.0]);
+    }
+
+    #[test]
+    fn parse_errors_give_the_line() {
+        let err = parse("Node {\n  float[3] {{1.0, 2.0}}\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: subarray of 2 values, where 3 were expected"
+        );
+        let err = parse("Node {\n  Name {string {\"x\"}}\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: a structure isn't closed");
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+///
+/// A timed scope within a frame. `depth` is 0 for scopes opened outside of any other on the
+/// same thread - the host's per-mod scopes - and increases with nesting. `thread` numbers the
+/// threads scopes were recorded on, in the order they were first seen.
+///
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub name: String,
+    pub depth: u32,
+    pub thread: usize,
+    /// Start time, relative to the creation of the profiler
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+///
+/// Every span recorded during one frame
+///
+#[derive(Clone, Debug, Default)]
+pub struct FrameProfile {
+    pub frame: u64,
+    pub spans: Vec<Span>,
+}
+
+///
+/// Timing of a named scope over the recorded history
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScopeStats {
+    pub count: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+pub const DEFAULT_HISTORY: usize = 300;
+
+///
+/// Profiler - a cheaply cloneable handle to the shared frame profiler.
+///
+/// The host opens a scope around each mod's update, and mods may open nested scopes of their
+/// own; scopes are closed when their guard is dropped:
+///
+/// let _scope = state.get_profiler().scope("scene traversal");
+///
+/// The spans of the last `history` frames are kept, for statistics and for writing a Chrome
+/// trace (chrome://tracing, or https://ui.perfetto.dev).
+///
+#[derive(Clone)]
+pub struct Profiler {
+    inner: Arc<Mutex<Profile>>,
+}
+
+struct Profile {
+    epoch: Instant,
+    history: usize,
+    frames: VecDeque<FrameProfile>,
+    current: FrameProfile,
+    depths: HashMap<ThreadId, u32>,
+    threads: Vec<ThreadId>,
+    dump_requested: bool,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new(DEFAULT_HISTORY)
+    }
+}
+
+impl Profiler {
+    pub fn new(history: usize) -> Self {
+        Profiler {
+            inner: Arc::new(Mutex::new(Profile {
+                epoch: Instant::now(),
+                history,
+                frames: VecDeque::with_capacity(history),
+                current: Default::default(),
+                depths: HashMap::new(),
+                threads: Vec::new(),
+                dump_requested: false,
+            })),
+        }
+    }
+
+    // A panic caught at the mod boundary may poison the lock - the profile is still usable
+    fn lock(&self) -> MutexGuard<'_, Profile> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    ///
+    /// Close the current frame, moving it into the history, and start recording `frame`
+    ///
+    pub fn begin_frame(&self, frame: u64) {
+        let mut profile = self.lock();
+        let finished = std::mem::replace(
+            &mut profile.current,
+            FrameProfile {
+                frame,
+                spans: Vec::new(),
+            },
+        );
+        // scopes left open by a panicking mod don't carry over
+        profile.depths.clear();
+        if profile.history == 0 {
+            return;
+        }
+        if profile.frames.len() == profile.history {
+            profile.frames.pop_front();
+        }
+        profile.frames.push_back(finished);
+    }
+
+    ///
+    /// Open a scope, recorded when the returned guard is dropped
+    ///
+    pub fn scope(&self, name: &str) -> Scope {
+        let depth = {
+            let mut profile = self.lock();
+            let depth = profile.depths.entry(thread::current().id()).or_insert(0);
+            *depth += 1;
+            *depth - 1
+        };
+        Scope {
+            profiler: self.clone(),
+            name: name.to_string(),
+            depth,
+            start: Instant::now(),
+        }
+    }
+
+    fn record(&self, name: String, depth: u32, start: Instant, duration: Duration) {
+        let mut profile = self.lock();
+        let id = thread::current().id();
+        profile.depths.insert(id, depth);
+        let thread = match profile.threads.iter().position(|t| *t == id) {
+            Some(thread) => thread,
+            None => {
+                profile.threads.push(id);
+                profile.threads.len() - 1
+            }
+        };
+        let start = start.duration_since(profile.epoch);
+        profile.current.spans.push(Span {
+            name,
+            depth,
+            thread,
+            start,
+            duration,
+        });
+    }
+
+    ///
+    /// Min, average, 99th percentile and max duration of a scope over the recorded frames
+    ///
+    pub fn stats(&self, name: &str) -> Option<ScopeStats> {
+        let profile = self.lock();
+        let mut durations = profile
+            .frames
+            .iter()
+            .flat_map(|f| f.spans.iter())
+            .filter(|s| s.name == name)
+            .map(|s| s.duration)
+            .collect::<Vec<_>>();
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        let count = durations.len();
+        let total: Duration = durations.iter().sum();
+        // nearest-rank percentile
+        let p99 = (count * 99).div_ceil(100) - 1;
+        Some(ScopeStats {
+            count,
+            min: durations[0],
+            avg: total / count as u32,
+            p99: durations[p99],
+            max: durations[count - 1],
+        })
+    }
+
+    ///
+    /// Names of the outermost scopes in the recorded frames - usually one per mod
+    ///
+    pub fn top_level_scopes(&self) -> Vec<String> {
+        let profile = self.lock();
+        let mut names: Vec<String> = Vec::new();
+        for span in profile.frames.iter().flat_map(|f| f.spans.iter()) {
+            if span.depth == 0 && !names.contains(&span.name) {
+                names.push(span.name.clone());
+            }
+        }
+        names
+    }
+
+    ///
+    /// Ask the host to write a trace at the end of the frame
+    ///
+    pub fn request_dump(&self) {
+        self.lock().dump_requested = true;
+    }
+
+    ///
+    /// Returns true, once, after a dump was requested
+    ///
+    pub fn take_dump_request(&self) -> bool {
+        std::mem::replace(&mut self.lock().dump_requested, false)
+    }
+
+    ///
+    /// Write the recorded frames in the Chrome trace-event format
+    ///
+    pub fn write_chrome_trace<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let profile = self.lock();
+        writeln!(out, "{{\"traceEvents\":[")?;
+        let mut first = true;
+        for frame in profile.frames.iter() {
+            for span in frame.spans.iter() {
+                if !first {
+                    writeln!(out, ",")?;
+                }
+                first = false;
+                write!(
+                    out,
+                    "{{\"name\":{},\"cat\":\"frame\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\
+                     \"pid\":1,\"tid\":{},\"args\":{{\"frame\":{}}}}}",
+                    json_string(&span.name),
+                    span.start.as_micros(),
+                    span.duration.as_micros(),
+                    span.thread + 1,
+                    frame.frame
+                )?;
+            }
+        }
+        writeln!(out, "\n],\"displayTimeUnit\":\"ms\"}}")
+    }
+
+    pub fn dump_chrome_trace<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_chrome_trace(&mut out)?;
+        out.flush()
+    }
+}
+
+///
+/// Scope - records the time from its creation to its drop
+///
+pub struct Scope {
+    profiler: Profiler,
+    name: String,
+    depth: u32,
+    start: Instant,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        let name = std::mem::take(&mut self.name);
+        self.profiler.record(name, self.depth, self.start, duration);
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_nested_scopes() {
+        let profiler = Profiler::new(10);
+        profiler.begin_frame(1);
+        {
+            let _outer = profiler.scope("mod");
+            let _inner = profiler.scope("traversal");
+        }
+        profiler.begin_frame(2);
+
+        let profile = profiler.lock();
+        let spans = &profile.frames.back().unwrap().spans;
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].name.as_str(), spans[0].depth), ("traversal", 1));
+        assert_eq!((spans[1].name.as_str(), spans[1].depth), ("mod", 0));
+        assert!(spans[1].duration >= spans[0].duration);
+    }
+
+    #[test]
+    fn nests_per_thread() {
+        let profiler = Profiler::new(10);
+        profiler.begin_frame(1);
+        {
+            let _main = profiler.scope("main");
+            let worker = profiler.clone();
+            thread::spawn(move || drop(worker.scope("worker")))
+                .join()
+                .unwrap();
+        }
+        profiler.begin_frame(2);
+
+        let profile = profiler.lock();
+        let spans = &profile.frames.back().unwrap().spans;
+        assert_eq!((spans[0].name.as_str(), spans[0].depth), ("worker", 0));
+        assert_eq!((spans[1].name.as_str(), spans[1].depth), ("main", 0));
+        assert_ne!(spans[0].thread, spans[1].thread);
+    }
+
+    #[test]
+    fn keeps_history_and_stats() {
+        let profiler = Profiler::new(3);
+        for frame in 0..5 {
+            profiler.begin_frame(frame);
+            profiler.record(
+                "mod".to_string(),
+                0,
+                Instant::now(),
+                Duration::from_micros(frame * 100),
+            );
+        }
+        profiler.begin_frame(5);
+
+        let stats = profiler.stats("mod").unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, Duration::from_micros(200));
+        assert_eq!(stats.avg, Duration::from_micros(300));
+        assert_eq!(stats.p99, Duration::from_micros(400));
+        assert_eq!(profiler.top_level_scopes(), vec!["mod".to_string()]);
+        assert!(profiler.stats("missing").is_none());
+    }
+
+    #[test]
+    fn writes_chrome_trace() {
+        let profiler = Profiler::new(3);
+        profiler.begin_frame(0);
+        drop(profiler.scope("say \"hi\""));
+        profiler.begin_frame(1);
+
+        let mut out = Vec::new();
+        profiler.write_chrome_trace(&mut out).unwrap();
+        let trace = String::from_utf8(out).unwrap();
+        assert!(trace.starts_with("{\"traceEvents\":["));
+        assert!(trace.contains("\"name\":\"say \\\"hi\\\"\",\"cat\":\"frame\",\"ph\":\"X\""));
+    }
+}
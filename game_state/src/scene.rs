@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix4, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Model, TextureCache};
+use crate::state::{SceneGraph, SceneNode};
+use crate::thing::{CameraFacet, HealthFacet, Thing, World};
+use crate::tree::{Node, RcNode};
+
+///
+/// SceneDescription - a scene as written in a `.ron` file: the models it uses, a hierarchy of
+/// nodes placing them, and the Things to create in the World
+///
+/// Example:
+///
+/// (
+///     models: [
+///         (name: "plane", path: "assets/models/plane.obj"),
+///     ],
+///     nodes: [
+///         (
+///             name: Some("helpers"),
+///             children: [
+///                 (model: Some("plane"), transform: (translation: (-1.5, 0.0, 0.0))),
+///                 (model: Some("plane"), transform: (translation: (1.5, 0.0, 0.0))),
+///             ],
+///         ),
+///     ],
+///     things: [
+///         (camera: Some((position: (0.0, 0.0, -2.0), pitch: -1.5))),
+///         (model: Some((model: "plane")), health: Some(100)),
+///     ],
+/// )
+///
+/// Model paths are relative to the working directory, like those given to `Model::load`.
+/// Everything but a model's name and path, and a camera's position, may be left out.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub models: Vec<ModelDescription>,
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>,
+    #[serde(default)]
+    pub things: Vec<ThingDescription>,
+}
+
+///
+/// A model file, and the name nodes and things refer to it by
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelDescription {
+    pub name: String,
+    pub path: String,
+}
+
+///
+/// A scene graph node. Nodes at the top level are children of the graph's root.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeDescription {
+    /// only used to point out the node in errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Transform::is_identity")]
+    pub transform: Transform,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeDescription>,
+}
+
+///
+/// A transform, applied as scale, then rotation, then translation. Rotation is in euler angles
+/// (roll, pitch, yaw), in radians.
+///
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    #[serde(default)]
+    pub translation: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "unit_scale")]
+    pub scale: [f32; 3],
+}
+
+fn unit_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: unit_scale(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::default()
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let [tx, ty, tz] = self.translation;
+        let [roll, pitch, yaw] = self.rotation;
+        let [sx, sy, sz] = self.scale;
+        Matrix4::new_translation(&Vector3::new(tx, ty, tz))
+            * Matrix4::from_euler_angles(roll, pitch, yaw)
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(sx, sy, sz))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let values = self
+            .translation
+            .iter()
+            .chain(self.rotation.iter())
+            .chain(self.scale.iter());
+        for value in values {
+            if !value.is_finite() {
+                return Err(format!("transform has a non-finite value {}", value));
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// A Thing in the World, with a facet for each field given
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThingDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<ModelInstanceDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub pitch: f32,
+    #[serde(default)]
+    pub yaw: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInstanceDescription {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Transform::is_identity")]
+    pub transform: Transform,
+}
+
+///
+/// Scene - what building a SceneDescription produces: the render layer, and the models loaded
+/// for it, which are shared by the layer and the Things
+///
+pub struct Scene {
+    pub graph: SceneGraph,
+    pub models: Vec<Arc<Model>>,
+    pub things: Vec<Arc<Mutex<Thing>>>,
+}
+
+impl SceneDescription {
+    ///
+    /// Read, parse and validate a scene file
+    ///
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("unable to read scene {}: {}", path.display(), err))?;
+        Self::parse(&contents).map_err(|err| format!("scene {}: {}", path.display(), err).into())
+    }
+
+    ///
+    /// Parse and validate a scene from a string. Syntax errors give the line and column, other
+    /// errors the entry they are about - eg. `nodes[0].children[1]`.
+    ///
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let scene: SceneDescription = ron::de::from_str(contents)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    ///
+    /// Write the scene in the same format `parse` reads
+    ///
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    ///
+    /// Check that names are unique, references resolve, and transforms are finite
+    ///
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let mut names = Vec::new();
+        for (i, model) in self.models.iter().enumerate() {
+            let entry = format!("models[{}]", i);
+            if model.name.is_empty() {
+                return Err(format!("{}: model has an empty name", entry).into());
+            }
+            if model.path.is_empty() {
+                return Err(
+                    format!("{} ({:?}): model has an empty path", entry, model.name).into(),
+                );
+            }
+            if names.contains(&model.name.as_str()) {
+                return Err(format!("{}: duplicate model name {:?}", entry, model.name).into());
+            }
+            names.push(model.name.as_str());
+        }
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            validate_node(node, &format!("nodes[{}]", i), &names)?;
+        }
+
+        for (i, thing) in self.things.iter().enumerate() {
+            let entry = format!("things[{}]", i);
+            if thing.camera.is_none() && thing.model.is_none() && thing.health.is_none() {
+                return Err(format!("{}: thing has no facets", entry).into());
+            }
+            if let Some(camera) = &thing.camera {
+                let [x, y, z] = camera.position;
+                for value in &[x, y, z, camera.pitch, camera.yaw] {
+                    if !value.is_finite() {
+                        return Err(format!(
+                            "{}.camera: camera has a non-finite value {}",
+                            entry, value
+                        )
+                        .into());
+                    }
+                }
+            }
+            if let Some(instance) = &thing.model {
+                let entry = format!("{}.model", entry);
+                if !names.contains(&instance.model.as_str()) {
+                    return Err(format!("{}: unknown model {:?}", entry, instance.model).into());
+                }
+                instance
+                    .transform
+                    .validate()
+                    .map_err(|err| format!("{}: {}", entry, err))?;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Load the models, build the scene graph, and add the Things to the world
+    ///
+    /// A model file may hold several objects: a node showing it gets a child node for each
+    /// object after the first, and a Thing gets a model facet for each. Textures shared by
+    /// several models are decoded once.
+    ///
+    pub fn build(&self, world: &mut World) -> Result<Scene, Box<dyn Error>> {
+        self.validate()?;
+
+        let mut loaded = HashMap::new();
+        let mut models = Vec::new();
+        let mut textures = TextureCache::new();
+        for (i, description) in self.models.iter().enumerate() {
+            let objects =
+                Model::load_with_cache(&description.path, Matrix4::identity(), &mut textures)
+                    .map_err(|err| {
+                        format!(
+                            "models[{}] ({:?}): unable to load {}: {}",
+                            i, description.name, description.path, err
+                        )
+                    })?;
+            if objects.is_empty() {
+                return Err(format!(
+                    "models[{}] ({:?}): no models found in {}",
+                    i, description.name, description.path
+                )
+                .into());
+            }
+            let objects = objects.into_iter().map(Arc::new).collect::<Vec<_>>();
+            models.extend(objects.iter().cloned());
+            loaded.insert(description.name.as_str(), objects);
+        }
+
+        let root = Node::create(SceneNode::default(), None);
+        for node in &self.nodes {
+            build_node(node, &root, &loaded);
+        }
+
+        let mut things = Vec::new();
+        for thing in &self.things {
+            let mut builder = world.start_thing();
+            if let Some(camera) = &thing.camera {
+                let [x, y, z] = camera.position;
+                builder = builder.with_camera(CameraFacet::new(
+                    Vector3::new(x, y, z),
+                    camera.pitch,
+                    camera.yaw,
+                ));
+            }
+            if let Some(instance) = &thing.model {
+                for object in &loaded[instance.model.as_str()] {
+                    builder = builder.with_model(instance.transform.matrix(), object.clone());
+                }
+            }
+            if let Some(hp) = thing.health {
+                builder = builder.with_health(HealthFacet::new(hp));
+            }
+            things.push(builder.build());
+        }
+
+        Ok(Scene {
+            graph: SceneGraph { root },
+            models,
+            things,
+        })
+    }
+}
+
+fn validate_node(node: &NodeDescription, entry: &str, models: &[&str]) -> Result<(), String> {
+    let entry = match &node.name {
+        Some(name) => format!("{} ({:?})", entry, name),
+        None => entry.to_string(),
+    };
+    if let Some(model) = &node.model {
+        if !models.contains(&model.as_str()) {
+            return Err(format!("{}: unknown model {:?}", entry, model));
+        }
+    }
+    node.transform
+        .validate()
+        .map_err(|err| format!("{}: {}", entry, err))?;
+    for (i, child) in node.children.iter().enumerate() {
+        validate_node(child, &format!("{}.children[{}]", entry, i), models)?;
+    }
+    Ok(())
+}
+
+fn build_node(
+    description: &NodeDescription,
+    parent: &RcNode<SceneNode>,
+    models: &HashMap<&str, Vec<Arc<Model>>>,
+) {
+    let objects = description
+        .model
+        .as_ref()
+        .map(|name| models[name.as_str()].as_slice())
+        .unwrap_or(&[]);
+    let node = Node::create(
+        SceneNode::new(objects.first().cloned(), description.transform.matrix()),
+        Some(parent),
+    );
+    for object in objects.iter().skip(1) {
+        Node::create(
+            SceneNode::new(Some(object.clone()), Matrix4::identity()),
+            Some(&node),
+        );
+    }
+    for child in &description.children {
+        build_node(child, &node, models);
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use image::RgbaImage;
+use nalgebra::{Matrix3, Matrix4, Point3, Vector3, Vector4, U3};
+
+use crate::model::Model;
+use crate::state::{DrawMode, SceneGraph};
+use crate::thing::CameraFacet;
+use crate::tree::BreadthFirstIterator;
+use crate::{create_next_identity, Identifyable, Identity, Renderer};
+
+pub mod raster;
+use self::raster::{ClipVertex, Framebuffer, Relief, Texture};
+
+const CLEAR_COLOR: u32 = raster::BLACK;
+
+// A model's maps, converted for drawing - the normal and specular maps only if it has either
+struct ModelTextures {
+    color: Texture,
+    relief: Option<(Texture, Texture)>,
+}
+
+fn texture(image: RgbaImage) -> Texture {
+    let (width, height) = image.dimensions();
+    Texture::from_rgba(width, height, &image.into_raw())
+}
+
+///
+/// Rasterizer - draws queued render layers into a framebuffer, on the CPU
+///
+/// Draws the same image as the vulkano renderer: the same projection, texture coordinates and
+/// draw modes, with the relief of normal and specular maps. Models are drawn straight from the
+/// scene graph, only their maps are converted - once, on first use.
+///
+/// This is the core of `mod_rendering_software`, which presents the frames to a window, and of
+/// `OffscreenRenderer`, which keeps them in memory.
+///
+pub struct Rasterizer {
+    framebuffer: Framebuffer,
+    draw_mode: DrawMode,
+    textures: HashMap<Identity, ModelTextures>,
+    render_layer_queue: VecDeque<Arc<SceneGraph>>,
+}
+
+impl Rasterizer {
+    pub fn new(width: u32, height: u32, draw_mode: DrawMode) -> Self {
+        Rasterizer {
+            framebuffer: Framebuffer::new(width, height),
+            draw_mode,
+            textures: HashMap::new(),
+            render_layer_queue: VecDeque::new(),
+        }
+    }
+
+    ///
+    /// The last frame drawn
+    ///
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    ///
+    /// Resize the framebuffer, if the size changed
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) != (self.framebuffer.width(), self.framebuffer.height()) {
+            self.framebuffer.resize(width, height);
+        }
+    }
+
+    pub fn queue_render_layer(&mut self, layer: Arc<SceneGraph>) {
+        self.render_layer_queue.push_back(layer);
+    }
+
+    ///
+    /// Forget the queued layers and the converted textures
+    ///
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.render_layer_queue.clear();
+    }
+
+    ///
+    /// Convert the model's maps again, the next time it is drawn
+    ///
+    pub fn model_updated(&mut self, model: &Model) {
+        self.textures.remove(&model.id);
+    }
+
+    ///
+    /// The last frame drawn, as an image
+    ///
+    pub fn capture(&self) -> RgbaImage {
+        let fb = &self.framebuffer;
+        RgbaImage::from_raw(fb.width(), fb.height(), fb.to_rgba())
+            .expect("framebuffer size matches its pixels")
+    }
+
+    ///
+    /// Draw the queued render layers, in the order they were queued, into a cleared framebuffer
+    ///
+    pub fn draw(&mut self, camera: &CameraFacet) {
+        if self.framebuffer.width() == 0 || self.framebuffer.height() == 0 {
+            // minimized - nothing to draw into
+            self.render_layer_queue.clear();
+            return;
+        }
+        self.framebuffer.clear(CLEAR_COLOR);
+
+        let proj_mat = Matrix4::new_perspective(
+            self.framebuffer.width() as f32 / self.framebuffer.height() as f32,
+            ::std::f32::consts::FRAC_PI_2,
+            0.01,
+            100.0,
+        );
+
+        while let Some(next_layer) = self.render_layer_queue.pop_front() {
+            next_layer.update_world_matrices();
+            let iterator = BreadthFirstIterator::new(next_layer.root.clone());
+            for (_node_id, rc) in iterator {
+                let node = &rc.borrow();
+                let model = match node.data.model.clone() {
+                    Some(model) => model,
+                    None => continue,
+                };
+                let transform_mat = node.data.world() * model.model_mat;
+
+                self.draw_model(&model, &proj_mat, &(camera.view * transform_mat));
+            }
+        }
+    }
+
+    fn draw_model(&mut self, model: &Model, proj: &Matrix4<f32>, model_view: &Matrix4<f32>) {
+        let textures = self.textures.entry(model.id).or_insert_with(|| {
+            let material = &model.material;
+            let relief = if material.normal_map.is_some() || material.specular_map.is_some() {
+                Some((
+                    texture(material.normal_texture()),
+                    texture(material.specular_texture()),
+                ))
+            } else {
+                None
+            };
+            ModelTextures {
+                color: texture(material.unlit_texture()),
+                relief,
+            }
+        });
+        let mvp = proj * model_view;
+
+        let vertices = model
+            .mesh
+            .vertices
+            .iter()
+            .map(|v| {
+                let h = mvp * Vector4::new(v.position.0, v.position.1, v.position.2, 1.0);
+                ClipVertex {
+                    position: [h.x, h.y, h.z, h.w],
+                    uv: [v.uvw.0, v.uvw.1],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // view space positions and normals, for the relief - normals as the shaders transform
+        // them
+        let normal_mat = model_view
+            .fixed_slice::<U3, U3>(0, 0)
+            .into_owned()
+            .try_inverse()
+            .unwrap_or_else(Matrix3::identity)
+            .transpose();
+        let view_space = |i: usize| {
+            let v = &model.mesh.vertices[i];
+            let p =
+                model_view.transform_point(&Point3::new(v.position.0, v.position.1, v.position.2));
+            let n = normal_mat * Vector3::new(v.normal.0, v.normal.1, v.normal.2);
+            (p.coords, n, [v.uvw.0, v.uvw.1])
+        };
+        let relief = |t: &[usize; 3]| {
+            textures.relief.as_ref().map(|(normal_map, specular_map)| {
+                let (p0, n0, uv0) = view_space(t[0]);
+                let (p1, n1, uv1) = view_space(t[1]);
+                let (p2, n2, uv2) = view_space(t[2]);
+                Relief::new(
+                    normal_map,
+                    specular_map,
+                    [p0, p1, p2],
+                    [n0, n1, n2],
+                    [uv0, uv1, uv2],
+                )
+            })
+        };
+
+        let fb = &mut self.framebuffer;
+        let texture = Some(&textures.color);
+        let triangles = model.mesh.indices.triangles();
+        match self.draw_mode {
+            DrawMode::Wireframe(line_width) => {
+                for t in triangles {
+                    let relief = relief(&t);
+                    for i in 0..3 {
+                        let a = &vertices[t[i]];
+                        let b = &vertices[t[(i + 1) % 3]];
+                        raster::draw_line(fb, a, b, line_width, texture, relief.as_ref());
+                    }
+                }
+            }
+            DrawMode::Points => {
+                for v in vertices.iter() {
+                    raster::draw_point(fb, v, texture);
+                }
+            }
+            DrawMode::Textured => {
+                for t in triangles {
+                    let triangle = [vertices[t[0]], vertices[t[1]], vertices[t[2]]];
+                    raster::fill_triangle(fb, &triangle, texture, relief(&t).as_ref());
+                }
+            }
+        }
+    }
+}
+
+///
+/// OffscreenRenderer - a Renderer without a window, keeping each frame in memory until it is
+/// captured. Used to test what a scene looks like, see `tests/golden.rs`.
+///
+pub struct OffscreenRenderer {
+    id: Identity,
+    rasterizer: Rasterizer,
+}
+
+impl OffscreenRenderer {
+    pub fn new(width: u32, height: u32, draw_mode: DrawMode) -> Self {
+        OffscreenRenderer {
+            id: create_next_identity(),
+            rasterizer: Rasterizer::new(width, height, draw_mode),
+        }
+    }
+
+    pub fn rasterizer(&self) -> &Rasterizer {
+        &self.rasterizer
+    }
+}
+
+impl Identifyable for OffscreenRenderer {
+    fn identify(&self) -> Identity {
+        self.id
+    }
+}
+
+impl Renderer for OffscreenRenderer {
+    fn load(&mut self) {}
+
+    fn unload(&mut self) {
+        self.rasterizer.clear();
+    }
+
+    fn queue_render_layer(&mut self, layer: Arc<SceneGraph>) {
+        self.rasterizer.queue_render_layer(layer);
+    }
+
+    fn present(&mut self, camera: &CameraFacet) {
+        self.rasterizer.draw(camera);
+    }
+
+    fn model_updated(&mut self, model: &Arc<Model>) {
+        self.rasterizer.model_updated(model);
+    }
+
+    fn capture_frame(&mut self) -> Option<RgbaImage> {
+        Some(self.rasterizer.capture())
+    }
+}
@@ -0,0 +1,730 @@
+//!
+//! Triangle, line and point rasterization into a CPU framebuffer.
+//!
+//! Vertices come in clip space - the output of a projection matrix - and are clipped against
+//! the near plane, divided by w and mapped to the framebuffer with y growing downwards from
+//! NDC -1, the Vulkan convention, so the software renderer shows the same image as the vulkano
+//! one. The other planes are handled by clamping to the framebuffer, and by the depth range
+//! test - lines are clipped to the framebuffer before they are stepped.
+//!
+//! Normal and specular maps are applied as a `Relief`, lit by the light of the vulkano
+//! renderer's fragment shader.
+//!
+
+use nalgebra::Vector3;
+
+use crate::model::MAX_SHININESS;
+
+///
+/// Colors are packed as 0xAARRGGBB
+///
+pub const BLACK: u32 = 0xff00_0000;
+pub const WHITE: u32 = 0xffff_ffff;
+
+// Vertices this close to w = 0 are behind the camera, for our purposes
+const W_EPSILON: f32 = 1e-5;
+
+///
+/// Framebuffer - color and depth for every pixel, row by row from the top left
+///
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    color: Vec<u32>,
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Framebuffer {
+            width,
+            height,
+            color: vec![BLACK; len],
+            depth: vec![1.0; len],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    ///
+    /// Resize, discarding the contents
+    ///
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = Framebuffer::new(width, height);
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        for c in self.color.iter_mut() {
+            *c = color;
+        }
+        for d in self.depth.iter_mut() {
+            *d = 1.0;
+        }
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.color
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.color
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> u32 {
+        self.color[(y * self.width + x) as usize]
+    }
+
+    pub fn depth(&self, x: u32, y: u32) -> f32 {
+        self.depth[(y * self.width + x) as usize]
+    }
+
+    ///
+    /// The pixels as RGBA bytes, row by row from the top left
+    ///
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.color.len() * 4);
+        for c in self.color.iter() {
+            bytes.extend_from_slice(&[(c >> 16) as u8, (c >> 8) as u8, *c as u8, (c >> 24) as u8]);
+        }
+        bytes
+    }
+
+    // Depth test, then blend and write the fragment
+    fn plot(&mut self, x: i32, y: i32, depth: f32, color: u32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        if !(0.0..=1.0).contains(&depth) {
+            return;
+        }
+        let i = (y as u32 * self.width + x as u32) as usize;
+        if depth > self.depth[i] {
+            return;
+        }
+        self.depth[i] = depth;
+        self.color[i] = blend(color, self.color[i]);
+    }
+}
+
+///
+/// Texture - RGBA texels, sampled with the nearest texel and coordinates clamped to the edges
+///
+pub struct Texture {
+    width: u32,
+    height: u32,
+    texels: Vec<u32>,
+}
+
+impl Texture {
+    ///
+    /// A texture from RGBA bytes, row by row from the top left
+    ///
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Self {
+        let texels = rgba
+            .chunks(4)
+            .map(|c| pack(c[0], c[1], c[2], c[3]))
+            .collect::<Vec<_>>();
+        assert_eq!(texels.len(), (width * height) as usize);
+        Texture {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    pub fn sample(&self, u: f32, v: f32) -> u32 {
+        if self.texels.is_empty() {
+            return WHITE;
+        }
+        let x = (u * self.width as f32) as i64;
+        let y = (v * self.height as f32) as i64;
+        let x = x.max(0).min(self.width as i64 - 1) as u32;
+        let y = y.max(0).min(self.height as i64 - 1) as u32;
+        self.texels[(y * self.width + x) as usize]
+    }
+}
+
+pub fn pack(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    (a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+fn unpack(c: u32) -> [u8; 4] {
+    [(c >> 16) as u8, (c >> 8) as u8, c as u8, (c >> 24) as u8]
+}
+
+///
+/// Relief - a normal and a specular map, with the tangent frame of the triangle they're drawn
+/// on: the directions texture coordinates grow in along its surface, and its normal. The frame
+/// and `position` (the triangle's center, for the direction it is seen from) are in view space.
+///
+/// Like the shaders, the normal map shades the surface relative to its own normal, so a flat
+/// map leaves its color unchanged, and the specular map adds highlights.
+///
+pub struct Relief<'a> {
+    pub normal_map: &'a Texture,
+    pub specular_map: &'a Texture,
+    pub tangent: Vector3<f32>,
+    pub bitangent: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub position: Vector3<f32>,
+}
+
+impl<'a> Relief<'a> {
+    ///
+    /// The frame of a triangle from its view space positions and normals, and texture
+    /// coordinates. Without distinct texture coordinates the normal map has no directions on
+    /// the surface, and only the triangle's normal is used.
+    ///
+    pub fn new(
+        normal_map: &'a Texture,
+        specular_map: &'a Texture,
+        positions: [Vector3<f32>; 3],
+        normals: [Vector3<f32>; 3],
+        uvs: [[f32; 2]; 3],
+    ) -> Self {
+        let (e1, e2) = (positions[1] - positions[0], positions[2] - positions[0]);
+        let normal = (normals[0] + normals[1] + normals[2])
+            .try_normalize(0.0)
+            .or_else(|| e1.cross(&e2).try_normalize(0.0))
+            .unwrap_or_else(Vector3::z);
+        let (du1, dv1) = (uvs[1][0] - uvs[0][0], uvs[1][1] - uvs[0][1]);
+        let (du2, dv2) = (uvs[2][0] - uvs[0][0], uvs[2][1] - uvs[0][1]);
+        let r = du1 * dv2 - du2 * dv1;
+        let (tangent, bitangent) = if r == 0.0 {
+            (Vector3::zeros(), Vector3::zeros())
+        } else {
+            let tangent = (e1 * dv2 - e2 * dv1) / r;
+            let bitangent = (e2 * du1 - e1 * du2) / r;
+            // scaled like the shaders' frame - the longer one to unit length
+            let scale = tangent.norm().max(bitangent.norm());
+            (tangent / scale, bitangent / scale)
+        };
+        Relief {
+            normal_map,
+            specular_map,
+            tangent,
+            bitangent,
+            normal,
+            position: (positions[0] + positions[1] + positions[2]) / 3.0,
+        }
+    }
+
+    fn light(&self, color: u32, u: f32, v: f32) -> u32 {
+        let light = Vector3::new(0.5, 0.5, 0.5).normalize();
+        let brightness = |n: &Vector3<f32>| 0.7 + 0.3 * n.dot(&light).max(0.0);
+
+        let [x, y, z, _] = unpack(self.normal_map.sample(u, v));
+        let mapped = |c: u8| (c as f32 - 128.0) / 127.0;
+        let bumped =
+            (self.tangent * mapped(x) + self.bitangent * mapped(y) + self.normal * mapped(z))
+                .try_normalize(0.0)
+                .unwrap_or(self.normal);
+        let relief = brightness(&bumped) / brightness(&self.normal);
+
+        let [sr, sg, sb, shininess] = unpack(self.specular_map.sample(u, v));
+        let view = (-self.position)
+            .try_normalize(0.0)
+            .unwrap_or_else(Vector3::z);
+        let half = (light + view).normalize();
+        let exponent = (shininess as f32 / 255.0 * MAX_SHININESS).max(1.0);
+        let highlight = bumped.dot(&half).max(0.0).powf(exponent);
+
+        let [r, g, b, a] = unpack(color);
+        let lit = |c: u8, s: u8| {
+            (c as f32 * relief + s as f32 * highlight)
+                .round()
+                .min(255.0) as u8
+        };
+        pack(lit(r, sr), lit(g, sg), lit(b, sb), a)
+    }
+}
+
+// "source over" alpha blending, the result is opaque
+fn blend(src: u32, dst: u32) -> u32 {
+    let alpha = src >> 24;
+    if alpha == 0xff {
+        return src;
+    }
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xff;
+        let d = (dst >> shift) & 0xff;
+        (s * alpha + d * (0xff - alpha)) / 0xff
+    };
+    0xff00_0000 | channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+///
+/// A vertex in clip space, with its texture coordinates
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipVertex {
+    pub position: [f32; 4],
+    pub uv: [f32; 2],
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let mut position = [0.0; 4];
+        for (i, p) in position.iter_mut().enumerate() {
+            *p = self.position[i] + (other.position[i] - self.position[i]) * t;
+        }
+        ClipVertex {
+            position,
+            uv: [
+                self.uv[0] + (other.uv[0] - self.uv[0]) * t,
+                self.uv[1] + (other.uv[1] - self.uv[1]) * t,
+            ],
+        }
+    }
+
+    // Distance inside the near plane, z = -w - negative when clipped
+    fn near_distance(&self) -> f32 {
+        self.position[2] + self.position[3]
+    }
+}
+
+// A vertex after the perspective divide. Attributes are divided by w, and interpolate
+// linearly in screen space - see `uv`.
+#[derive(Copy, Clone, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    depth: f32,
+    inv_w: f32,
+    u_w: f32,
+    v_w: f32,
+}
+
+impl ScreenVertex {
+    fn project(v: &ClipVertex, width: u32, height: u32) -> Self {
+        let w = v.position[3].max(W_EPSILON);
+        let inv_w = 1.0 / w;
+        ScreenVertex {
+            x: (v.position[0] * inv_w + 1.0) * 0.5 * width as f32,
+            y: (v.position[1] * inv_w + 1.0) * 0.5 * height as f32,
+            depth: v.position[2] * inv_w * 0.5 + 0.5,
+            inv_w,
+            u_w: v.uv[0] * inv_w,
+            v_w: v.uv[1] * inv_w,
+        }
+    }
+
+    fn lerp(&self, other: &ScreenVertex, t: f32) -> ScreenVertex {
+        let l = |a: f32, b: f32| a + (b - a) * t;
+        ScreenVertex {
+            x: l(self.x, other.x),
+            y: l(self.y, other.y),
+            depth: l(self.depth, other.depth),
+            inv_w: l(self.inv_w, other.inv_w),
+            u_w: l(self.u_w, other.u_w),
+            v_w: l(self.v_w, other.v_w),
+        }
+    }
+
+    // perspective-correct texture coordinates
+    fn uv(&self) -> (f32, f32) {
+        (self.u_w / self.inv_w, self.v_w / self.inv_w)
+    }
+}
+
+fn shade(texture: Option<&Texture>, relief: Option<&Relief>, v: &ScreenVertex) -> u32 {
+    let (u, v) = v.uv();
+    let color = match texture {
+        Some(texture) => texture.sample(u, v),
+        None => WHITE,
+    };
+    match relief {
+        Some(relief) => relief.light(color, u, v),
+        None => color,
+    }
+}
+
+///
+/// Clip a triangle against the near plane, returning a convex polygon of 0, 3 or 4 vertices
+///
+fn clip_near(triangle: &[ClipVertex; 3]) -> Vec<ClipVertex> {
+    let mut clipped = Vec::with_capacity(4);
+    for i in 0..3 {
+        let a = &triangle[i];
+        let b = &triangle[(i + 1) % 3];
+        let (da, db) = (a.near_distance(), b.near_distance());
+        if da >= 0.0 {
+            clipped.push(*a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            clipped.push(a.lerp(b, da / (da - db)));
+        }
+    }
+    clipped
+}
+
+///
+/// Fill a triangle, textured if a texture is given, with relief if one is given. Both windings
+/// are drawn.
+///
+pub fn fill_triangle(
+    fb: &mut Framebuffer,
+    triangle: &[ClipVertex; 3],
+    texture: Option<&Texture>,
+    relief: Option<&Relief>,
+) {
+    let polygon = clip_near(triangle);
+    if polygon.len() < 3 {
+        return;
+    }
+    let projected = polygon
+        .iter()
+        .map(|v| ScreenVertex::project(v, fb.width, fb.height))
+        .collect::<Vec<_>>();
+    for i in 1..projected.len() - 1 {
+        fill_screen_triangle(
+            fb,
+            [projected[0], projected[i], projected[i + 1]],
+            texture,
+            relief,
+        );
+    }
+}
+
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+fn fill_screen_triangle(
+    fb: &mut Framebuffer,
+    v: [ScreenVertex; 3],
+    texture: Option<&Texture>,
+    relief: Option<&Relief>,
+) {
+    let area = edge(&v[0], &v[1], v[2].x, v[2].y);
+    if area == 0.0 {
+        return;
+    }
+    let min_x = v
+        .iter()
+        .map(|v| v.x)
+        .fold(f32::MAX, f32::min)
+        .floor()
+        .max(0.0) as i32;
+    let min_y = v
+        .iter()
+        .map(|v| v.y)
+        .fold(f32::MAX, f32::min)
+        .floor()
+        .max(0.0) as i32;
+    let max_x = v
+        .iter()
+        .map(|v| v.x)
+        .fold(f32::MIN, f32::max)
+        .ceil()
+        .min(fb.width as f32) as i32;
+    let max_y = v
+        .iter()
+        .map(|v| v.y)
+        .fold(f32::MIN, f32::max)
+        .ceil()
+        .min(fb.height as f32) as i32;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            // sample at the pixel center
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let w0 = edge(&v[1], &v[2], px, py) / area;
+            let w1 = edge(&v[2], &v[0], px, py) / area;
+            let w2 = edge(&v[0], &v[1], px, py) / area;
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+            let l = |f: fn(&ScreenVertex) -> f32| w0 * f(&v[0]) + w1 * f(&v[1]) + w2 * f(&v[2]);
+            let fragment = ScreenVertex {
+                x: px,
+                y: py,
+                depth: l(|v| v.depth),
+                inv_w: l(|v| v.inv_w),
+                u_w: l(|v| v.u_w),
+                v_w: l(|v| v.v_w),
+            };
+            fb.plot(x, y, fragment.depth, shade(texture, relief, &fragment));
+        }
+    }
+}
+
+///
+/// Draw a line `width` pixels wide, with the relief of the triangle it is an edge of, if given
+///
+pub fn draw_line(
+    fb: &mut Framebuffer,
+    a: &ClipVertex,
+    b: &ClipVertex,
+    width: f32,
+    texture: Option<&Texture>,
+    relief: Option<&Relief>,
+) {
+    let (da, db) = (a.near_distance(), b.near_distance());
+    let (a, b) = match (da >= 0.0, db >= 0.0) {
+        (true, true) => (*a, *b),
+        (true, false) => (*a, a.lerp(b, da / (da - db))),
+        (false, true) => (b.lerp(a, db / (db - da)), *b),
+        (false, false) => return,
+    };
+    let a = ScreenVertex::project(&a, fb.width, fb.height);
+    let b = ScreenVertex::project(&b, fb.width, fb.height);
+
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    // thickness is spread across the minor axis
+    let x_major = dx.abs() >= dy.abs();
+    let thickness = (width.round() as i32).max(1);
+    let offset = (thickness - 1) / 2;
+
+    // an endpoint close to the near plane projects far off screen - only step the part that
+    // can touch the framebuffer, with room for the thickness
+    let margin = thickness as f32;
+    let max = (fb.width as f32 + margin, fb.height as f32 + margin);
+    let (a, b) = match clip_to_rect(&a, &b, (-margin, -margin), max) {
+        Some((t0, t1)) => (a.lerp(&b, t0), a.lerp(&b, t1)),
+        None => return,
+    };
+
+    let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil().max(1.0) as i32;
+    for step in 0..=steps {
+        let p = a.lerp(&b, step as f32 / steps as f32);
+        let color = shade(texture, relief, &p);
+        let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+        for t in 0..thickness {
+            let t = t - offset;
+            if x_major {
+                fb.plot(x, y + t, p.depth, color);
+            } else {
+                fb.plot(x + t, y, p.depth, color);
+            }
+        }
+    }
+}
+
+// Liang-Barsky - the parameters along a..b of the part of the segment inside the rectangle
+fn clip_to_rect(
+    a: &ScreenVertex,
+    b: &ScreenVertex,
+    min: (f32, f32),
+    max: (f32, f32),
+) -> Option<(f32, f32)> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    let edges = [
+        (-dx, a.x - min.0),
+        (dx, max.0 - a.x),
+        (-dy, a.y - min.1),
+        (dy, max.1 - a.y),
+    ];
+    for &(p, q) in edges.iter() {
+        if p == 0.0 {
+            // parallel to this edge - inside it or not at all
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        None
+    } else {
+        Some((t0, t1))
+    }
+}
+
+///
+/// Draw a single pixel point. Points aren't part of a triangle, so have no relief.
+///
+pub fn draw_point(fb: &mut Framebuffer, v: &ClipVertex, texture: Option<&Texture>) {
+    if v.near_distance() < 0.0 || v.position[3] < W_EPSILON {
+        return;
+    }
+    let p = ScreenVertex::project(v, fb.width, fb.height);
+    fb.plot(
+        p.x.floor() as i32,
+        p.y.floor() as i32,
+        p.depth,
+        shade(texture, None, &p),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32, w: f32, uv: [f32; 2]) -> ClipVertex {
+        ClipVertex {
+            position: [x * w, y * w, z * w, w],
+            uv,
+        }
+    }
+
+    #[test]
+    fn depth_test_keeps_nearest() {
+        let mut fb = Framebuffer::new(8, 8);
+        let red = Texture::from_rgba(1, 1, &[255, 0, 0, 255]);
+        let green = Texture::from_rgba(1, 1, &[0, 255, 0, 255]);
+        // two screen covering triangles, the green one nearer, drawn last then first
+        let cover = |z: f32| {
+            [
+                vertex(-1.0, -1.0, z, 1.0, [0.0, 0.0]),
+                vertex(3.0, -1.0, z, 1.0, [0.0, 0.0]),
+                vertex(-1.0, 3.0, z, 1.0, [0.0, 0.0]),
+            ]
+        };
+        fill_triangle(&mut fb, &cover(0.5), Some(&red), None);
+        fill_triangle(&mut fb, &cover(0.0), Some(&green), None);
+        fill_triangle(&mut fb, &cover(0.5), Some(&red), None);
+        assert!(fb.pixels().iter().all(|&c| c == pack(0, 255, 0, 255)));
+        assert_eq!(fb.depth(3, 3), 0.5);
+
+        fb.clear(BLACK);
+        assert_eq!(fb.pixel(3, 3), BLACK);
+        assert_eq!(fb.depth(3, 3), 1.0);
+    }
+
+    #[test]
+    fn interpolates_uvs_with_perspective() {
+        // left half black, right half white
+        let texture = Texture::from_rgba(2, 1, &[0, 0, 0, 255, 255, 255, 255, 255]);
+        let mut fb = Framebuffer::new(64, 1);
+        // a quad receding to the right - its texture midpoint is left of the screen center
+        let near = 1.0;
+        let far = 4.0;
+        let quad = [
+            vertex(-1.0, -1.0, 0.0, near, [0.0, 0.0]),
+            vertex(1.0, -1.0, 0.0, far, [1.0, 0.0]),
+            vertex(1.0, 1.0, 0.0, far, [1.0, 1.0]),
+            vertex(-1.0, 1.0, 0.0, near, [0.0, 1.0]),
+        ];
+        fill_triangle(&mut fb, &[quad[0], quad[1], quad[2]], Some(&texture), None);
+        fill_triangle(&mut fb, &[quad[0], quad[2], quad[3]], Some(&texture), None);
+
+        let first_white = (0..64).find(|&x| fb.pixel(x, 0) == WHITE).unwrap();
+        // affine interpolation would switch at 32, perspective-correct at 64 * 0.8
+        assert!(first_white > 48 && first_white < 54, "{}", first_white);
+    }
+
+    #[test]
+    fn clips_against_near_plane() {
+        let mut fb = Framebuffer::new(16, 16);
+        let behind = [
+            vertex(-1.0, -1.0, -2.0, 1.0, [0.0, 0.0]),
+            vertex(1.0, -1.0, -2.0, 1.0, [0.0, 0.0]),
+            vertex(0.0, 1.0, -2.0, 1.0, [0.0, 0.0]),
+        ];
+        fill_triangle(&mut fb, &behind, None, None);
+        assert!(fb.pixels().iter().all(|&c| c == BLACK));
+
+        // one vertex behind the camera, w < 0
+        let crossing = [
+            vertex(-1.0, -1.0, 0.0, 1.0, [0.0, 0.0]),
+            vertex(1.0, -1.0, 0.0, 1.0, [0.0, 0.0]),
+            ClipVertex {
+                position: [0.0, 2.0, -2.0, -1.0],
+                uv: [0.0, 0.0],
+            },
+        ];
+        assert_eq!(clip_near(&crossing).len(), 4);
+        fill_triangle(&mut fb, &crossing, None, None);
+        assert_eq!(fb.pixel(8, 0), WHITE);
+    }
+
+    #[test]
+    fn draws_wide_lines_and_points() {
+        let mut fb = Framebuffer::new(16, 16);
+        let a = vertex(-1.0, 0.0, 0.0, 1.0, [0.0, 0.0]);
+        let b = vertex(1.0, 0.0, 0.0, 1.0, [0.0, 0.0]);
+        draw_line(&mut fb, &a, &b, 3.0, None, None);
+        let lit = fb.pixels().iter().filter(|&&c| c == WHITE).count();
+        assert_eq!(lit, 16 * 3);
+        assert_eq!(fb.pixel(5, 7), WHITE);
+        assert_eq!(fb.pixel(5, 9), WHITE);
+        assert_eq!(fb.pixel(5, 10), BLACK);
+
+        fb.clear(BLACK);
+        draw_point(&mut fb, &vertex(0.0, 0.0, 0.0, 2.0, [0.0, 0.0]), None);
+        assert_eq!(fb.pixel(8, 8), WHITE);
+        assert_eq!(fb.pixels().iter().filter(|&&c| c == WHITE).count(), 1);
+    }
+
+    #[test]
+    fn clips_lines_to_the_framebuffer() {
+        let mut fb = Framebuffer::new(16, 16);
+        // the far end is close to the camera, and projects nearly a million pixels off screen
+        let a = vertex(0.0, 0.0, 0.0, 1.0, [0.0, 0.0]);
+        let b = vertex(1e5, 0.0, 0.0, 1e-3, [0.0, 0.0]);
+        draw_line(&mut fb, &a, &b, 1.0, None, None);
+        let lit = fb.pixels().iter().filter(|&&c| c == WHITE).count();
+        assert_eq!(lit, 8);
+        assert_eq!(fb.pixel(8, 8), WHITE);
+        assert_eq!(fb.pixel(15, 8), WHITE);
+
+        fb.clear(BLACK);
+        let a = vertex(-2.0, -3.0, 0.0, 1.0, [0.0, 0.0]);
+        let b = vertex(2.0, -3.0, 0.0, 1.0, [0.0, 0.0]);
+        draw_line(&mut fb, &a, &b, 1.0, None, None);
+        assert!(fb.pixels().iter().all(|&c| c == BLACK));
+    }
+
+    #[test]
+    fn flat_relief_keeps_the_color() {
+        let grey = Texture::from_rgba(1, 1, &[100, 100, 100, 255]);
+        let flat = Texture::from_rgba(1, 1, &[128, 128, 255, 255]);
+        let tilted = Texture::from_rgba(1, 1, &[201, 201, 201, 255]);
+        let no_highlights = Texture::from_rgba(1, 1, &[0, 0, 0, 0]);
+        // a grey highlight, as broad as the lowest shininess makes it
+        let shiny = Texture::from_rgba(1, 1, &[64, 64, 64, 0]);
+        // facing the viewer, texture coordinates growing along x and y
+        let relief = |normal_map, specular_map| {
+            Relief::new(
+                normal_map,
+                specular_map,
+                [
+                    Vector3::new(0.0, 0.0, -2.0),
+                    Vector3::new(1.0, 0.0, -2.0),
+                    Vector3::new(0.0, 1.0, -2.0),
+                ],
+                [Vector3::z(); 3],
+                [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            )
+        };
+        let cover = [
+            vertex(-1.0, -1.0, 0.0, 1.0, [0.0, 0.0]),
+            vertex(3.0, -1.0, 0.0, 1.0, [0.0, 0.0]),
+            vertex(-1.0, 3.0, 0.0, 1.0, [0.0, 0.0]),
+        ];
+        let mut fb = Framebuffer::new(4, 4);
+        let color = |fb: &mut Framebuffer, relief: &Relief| {
+            fb.clear(BLACK);
+            fill_triangle(fb, &cover, Some(&grey), Some(relief));
+            fb.pixel(1, 1)
+        };
+
+        let flat_relief = relief(&flat, &no_highlights);
+        assert_eq!(flat_relief.tangent, Vector3::x());
+        assert_eq!(flat_relief.bitangent, Vector3::y());
+        assert_eq!(color(&mut fb, &flat_relief), pack(100, 100, 100, 255));
+
+        // tilted towards the light
+        let [r, ..] = unpack(color(&mut fb, &relief(&tilted, &no_highlights)));
+        assert!(r > 100, "{}", r);
+
+        // the light is off to the side - a highlight brightens, without saturating
+        let [r, ..] = unpack(color(&mut fb, &relief(&flat, &shiny)));
+        assert!(r > 100 && r < 255, "{}", r);
+    }
+}
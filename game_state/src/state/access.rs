@@ -6,13 +6,18 @@ use std::sync::Arc;
 use sdl2::video::WindowContext;
 
 use super::DrawMode;
+use super::GraphicsApi;
 use super::Model;
 use super::Renderer;
 
 use crate::input::events::InputEvent;
 use crate::input::screen::ScreenPoint;
+use crate::jobs::Jobs;
+use crate::logging::Logger;
+use crate::profiler::Profiler;
 use crate::state::render_state::WindowWithAttrs;
-use crate::state::{SceneGraph, State, World};
+use crate::state::{SceneGraph, State, TimeState, World};
+use crate::tree::BreadthFirstIterator;
 use crate::ui::events::UIEvent;
 use crate::Identity;
 
@@ -25,6 +30,16 @@ pub trait WorldAccess {
 pub trait ModelAccess {
     fn get_models(&self) -> Vec<Arc<Model>>;
     fn add_model(&mut self, model: Arc<Model>);
+
+    ///
+    /// Swap in a new version of a model, with the same identity as the one it replaces, in
+    /// the render layers and the world, and tell the renderers. Returns false, changing
+    /// nothing, if no model has that identity.
+    ///
+    /// The renderers upload the new model straight away - a mod replacing models uses
+    /// `RenderAccess` too.
+    ///
+    fn replace_model(&mut self, model: Arc<Model>) -> bool;
 }
 
 pub trait VariableAccess {
@@ -36,8 +51,17 @@ pub trait VariableAccess {
 }
 
 pub trait WindowAccess {
-    fn add_window(&mut self, w: u32, h: u32, title: &str, x: i32, y: i32, draw_mode: DrawMode);
+    fn add_window(
+        &mut self,
+        w: u32,
+        h: u32,
+        title: &str,
+        x: i32,
+        y: i32,
+        draw_mode: DrawMode,
+    ) -> Result<(), Box<dyn Error>>;
     fn get_windows(&mut self) -> Vec<(Rc<WindowContext>, DrawMode)>;
+    fn get_graphics_api(&self) -> GraphicsApi;
 }
 
 // Accessor trait for State by topic
@@ -70,6 +94,29 @@ pub trait InputAccess {
     fn set_mouse_pos(&mut self, sp: ScreenPoint);
 }
 
+pub trait LogAccess {
+    fn get_logger(&self) -> &Logger;
+}
+
+pub trait ProfilerAccess {
+    fn get_profiler(&self) -> &Profiler;
+}
+
+pub trait JobAccess {
+    fn get_jobs(&self) -> &Jobs;
+}
+
+pub trait TimeAccess {
+    fn get_time(&self) -> &TimeState;
+    fn get_time_mut(&mut self) -> &mut TimeState;
+    fn get_tick(&self) -> u64 {
+        self.get_time().tick
+    }
+    fn get_alpha(&self) -> f32 {
+        self.get_time().alpha
+    }
+}
+
 pub trait UIAccess {
     fn pending_ui_events(&mut self) -> &VecDeque<UIEvent>;
     fn queue_ui_event(&mut self, event: UIEvent);
@@ -77,6 +124,34 @@ pub trait UIAccess {
     fn on_ui_unload(&mut self);
 }
 
+impl LogAccess for State {
+    fn get_logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+impl ProfilerAccess for State {
+    fn get_profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+}
+
+impl JobAccess for State {
+    fn get_jobs(&self) -> &Jobs {
+        &self.jobs
+    }
+}
+
+impl TimeAccess for State {
+    fn get_time(&self) -> &TimeState {
+        &self.time_state
+    }
+
+    fn get_time_mut(&mut self) -> &mut TimeState {
+        &mut self.time_state
+    }
+}
+
 impl VariableAccess for State {
     fn get_bool(&self, key: &'static str) -> Option<bool> {
         self.variables.get(key).map(|Variable::Bool(v)| *v)
@@ -95,6 +170,40 @@ impl ModelAccess for State {
     fn add_model(&mut self, model: Arc<Model>) {
         self.render_state.models.push(model);
     }
+
+    fn replace_model(&mut self, model: Arc<Model>) -> bool {
+        let existing = self
+            .render_state
+            .models
+            .iter_mut()
+            .find(|existing| existing.id == model.id);
+        match existing {
+            Some(existing) => *existing = model.clone(),
+            None => return false,
+        }
+
+        for layer in &self.render_state.render_layers {
+            for (_id, rc) in BreadthFirstIterator::new(layer.root.clone()) {
+                let mut node = rc.borrow_mut();
+                let same_model = match &node.data.model {
+                    Some(existing) => existing.id == model.id,
+                    None => false,
+                };
+                if same_model {
+                    node.data.model = Some(model.clone());
+                }
+            }
+        }
+        for instance in self.world.get_facets().models.iter_mut() {
+            if instance.model.id == model.id {
+                instance.model = model.clone();
+            }
+        }
+        for renderer in self.render_state.renderers.iter_mut() {
+            renderer.model_updated(&model);
+        }
+        true
+    }
 }
 
 impl WorldAccess for State {
@@ -104,23 +213,36 @@ impl WorldAccess for State {
 }
 
 impl WindowAccess for State {
-    // TODO: make fallible
-    fn add_window(&mut self, w: u32, h: u32, title: &str, x: i32, y: i32, draw_mode: DrawMode) {
-        let window = {
-            self.sdl_subsystems
-                .video
-                .window(title, w, h)
-                .position(x, y)
-                .resizable()
-                .allow_highdpi()
-                .vulkan()
-                .build()
-                .unwrap()
-        };
+    fn add_window(
+        &mut self,
+        w: u32,
+        h: u32,
+        title: &str,
+        x: i32,
+        y: i32,
+        draw_mode: DrawMode,
+    ) -> Result<(), Box<dyn Error>> {
+        let subsystems = self
+            .sdl_subsystems
+            .as_ref()
+            .ok_or_else(|| format!("unable to add window '{}' - running headless", title))?;
+        let mut builder = subsystems.video.window(title, w, h);
+        builder.position(x, y).resizable().allow_highdpi();
+        match self.render_state.graphics_api {
+            GraphicsApi::Vulkan => {
+                builder.vulkan();
+            }
+            GraphicsApi::OpenGL => {
+                builder.opengl();
+            }
+            GraphicsApi::Software => {}
+        }
+        let window = builder.build()?;
 
         self.render_state
             .windows
             .push(WindowWithAttrs { window, draw_mode });
+        Ok(())
     }
 
     fn get_windows(&mut self) -> Vec<(Rc<WindowContext>, DrawMode)> {
@@ -130,6 +252,10 @@ impl WindowAccess for State {
             .map(|w| (w.window.context(), w.draw_mode))
             .collect::<Vec<_>>()
     }
+
+    fn get_graphics_api(&self) -> GraphicsApi {
+        self.render_state.graphics_api
+    }
 }
 
 impl RenderLayerAccess for State {
@@ -160,9 +286,11 @@ impl RenderAccess for State {
     }
 
     fn present_all(&mut self) {
-        let camera = &self.world.get_facets().cameras[0];
-        for r in self.render_state.renderers.iter_mut() {
-            r.present(camera);
+        // nothing to present until there is a camera
+        if let Some(camera) = self.world.get_facets().cameras.first() {
+            for r in self.render_state.renderers.iter_mut() {
+                r.present(camera);
+            }
         }
     }
 
@@ -179,6 +307,10 @@ impl RenderAccess for State {
     }
 
     fn push_render_layers(&mut self) {
+        // present_all won't draw - and drain - the queued layers until there is a camera
+        if self.world.get_facets().cameras.is_empty() {
+            return;
+        }
         // queue each existing render layers for rendering
         for i in 0..self.render_state.renderers.len() {
             for r in &self.render_state.render_layers {
@@ -197,7 +329,8 @@ impl RenderAccess for State {
         for i in 0..self.render_state.renderers.len() {
             self.render_state.renderers[i].unload();
         }
-        println!("RenderAccess::on_render_unload");
+        self.logger
+            .debug("render", "RenderAccess::on_render_unload");
         self.render_state.renderers.clear();
     }
 }
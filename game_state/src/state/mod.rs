@@ -1,30 +1,38 @@
 use std::collections::HashMap;
+use std::error::Error;
 
+use super::jobs::{self, Jobs};
+use super::logging::Logger;
 use super::model::Model;
+use super::profiler::Profiler;
 use super::Renderer;
 use crate::thing::World;
 
 pub use self::access::{
-    InputAccess, ModelAccess, RenderAccess, RenderLayerAccess, VariableAccess, WindowAccess,
-    WorldAccess,
+    InputAccess, JobAccess, LogAccess, ModelAccess, ProfilerAccess, RenderAccess,
+    RenderLayerAccess, TimeAccess, VariableAccess, WindowAccess, WorldAccess,
 };
 pub use self::input_state::InputState;
-pub use self::render_state::{DrawMode, RenderState, SceneGraph};
+pub use self::render_state::{DrawMode, GraphicsApi, RenderState, SceneGraph, SceneNode};
 pub use self::simulation_state::SimulationState;
+pub use self::time_state::TimeState;
 use self::ui_state::UIState;
 
 mod access;
 mod input_state;
 mod render_state;
 mod simulation_state;
+mod time_state;
 mod ui_state;
 
 ///
 /// This is the central, and global, state passed to each mod during the main loop
 ///
+/// The SDL context and subsystems are None when running headless (see `StateBuilder`).
+///
 pub struct State {
-    pub sdl_context: sdl2::Sdl,
-    pub sdl_subsystems: SdlSubsystems,
+    pub sdl_context: Option<sdl2::Sdl>,
+    pub sdl_subsystems: Option<SdlSubsystems>,
 
     /// Root container of the Thing/Facet system (game world state)
     world: World,
@@ -42,6 +50,18 @@ pub struct State {
     pub variables: HashMap<&'static str, Variable>,
 
     pub simulation_state: SimulationState,
+
+    /// Frame and fixed tick timing, maintained by the host
+    time_state: TimeState,
+
+    /// Shared log, filtered per target and written to the sinks installed by the host
+    logger: Logger,
+
+    /// Per-frame timing of mods, and of scopes within them
+    profiler: Profiler,
+
+    /// Background jobs spawned by mods, run on the host's worker threads
+    jobs: Jobs,
 }
 
 #[derive(Copy, Clone)]
@@ -56,18 +76,100 @@ pub struct SdlSubsystems {
 
 impl Default for State {
     fn default() -> Self {
-        let ctx = sdl2::init().expect("unable to create sdl2 context");
-        let video = ctx.video().expect("unable to create video subsystem");
-        let event_pump = ctx.event_pump().expect("unable to create event pump");
-        Self {
-            sdl_context: ctx,
-            sdl_subsystems: SdlSubsystems { video, event_pump },
+        StateBuilder::new()
+            .build()
+            .expect("unable to initialize SDL video")
+    }
+}
+
+impl State {
+    pub fn builder() -> StateBuilder {
+        StateBuilder::new()
+    }
+
+    ///
+    /// Returns true if there is no SDL context - no windows, no input events
+    ///
+    pub fn is_headless(&self) -> bool {
+        self.sdl_context.is_none()
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        self.jobs.shutdown();
+    }
+}
+
+///
+/// StateBuilder - configures and creates a State
+///
+/// let state = State::builder().headless().build()?;
+///
+/// A headless State never initializes SDL, so it can be used without a display - on a
+/// dedicated server, or in tests.
+///
+#[derive(Default)]
+pub struct StateBuilder {
+    headless: bool,
+    job_threads: Option<usize>,
+    graphics_api: GraphicsApi,
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self
+    }
+
+    ///
+    /// Number of threads running background jobs - 0 leaves jobs to `Jobs::run_pending`
+    ///
+    pub fn job_threads(mut self, count: usize) -> Self {
+        self.job_threads = Some(count);
+        self
+    }
+
+    ///
+    /// The graphics API windows are created for - Vulkan by default
+    ///
+    pub fn graphics_api(mut self, api: GraphicsApi) -> Self {
+        self.graphics_api = api;
+        self
+    }
+
+    pub fn build(self) -> Result<State, Box<dyn Error>> {
+        let (sdl_context, sdl_subsystems) = if self.headless {
+            (None, None)
+        } else {
+            let ctx = sdl2::init()?;
+            let video = ctx.video()?;
+            let event_pump = ctx.event_pump()?;
+            (Some(ctx), Some(SdlSubsystems { video, event_pump }))
+        };
+        let logger = Logger::default();
+        let jobs = Jobs::new(logger.clone());
+        jobs.start_workers(self.job_threads.unwrap_or(jobs::DEFAULT_THREADS))?;
+        Ok(State {
+            sdl_context,
+            sdl_subsystems,
             world: Default::default(),
-            render_state: Default::default(),
+            render_state: RenderState {
+                graphics_api: self.graphics_api,
+                ..Default::default()
+            },
             input_state: Default::default(),
             simulation_state: Default::default(),
+            time_state: Default::default(),
             ui_state: Default::default(),
             variables: HashMap::new(),
-        }
+            logger,
+            profiler: Default::default(),
+            jobs,
+        })
     }
 }
@@ -1,22 +1,134 @@
 use std::sync::Arc;
 
+use nalgebra::Matrix4;
 use sdl2::video::Window;
 
 use super::{Model, Renderer};
 use crate::tree::RcNode;
 
 #[derive(Default)]
-pub struct SceneGraph<T = Option<Arc<Model>>> {
+pub struct SceneGraph<T = SceneNode> {
     pub root: RcNode<T>,
 }
 
-#[derive(Copy, Clone)]
+impl SceneGraph<SceneNode> {
+    ///
+    /// Recompute the world matrix of every node whose local transform changed since the last
+    /// call, and of everything below it. Nodes that didn't move are left alone.
+    ///
+    /// Renderers call this on each layer before drawing it, so it is cheap when nothing changed.
+    ///
+    pub fn update_world_matrices(&self) {
+        // (node, parent's world matrix, whether the parent's world matrix changed)
+        let mut stack = vec![(self.root.clone(), Matrix4::identity(), false)];
+        while let Some((rc, parent_world, parent_changed)) = stack.pop() {
+            let mut node = rc.borrow_mut();
+            let changed = parent_changed || node.data.dirty;
+            if changed {
+                node.data.world = parent_world * node.data.local;
+                node.data.dirty = false;
+            }
+            let world = node.data.world;
+            for child in node.children() {
+                stack.push((child.clone(), world, changed));
+            }
+        }
+    }
+}
+
+///
+/// SceneNode - what each node of a SceneGraph holds: an optional model, placed by a transform
+/// relative to the parent node
+///
+/// The same model may be put under any number of nodes, to draw it at each of them. A model is
+/// drawn with `world() * model.model_mat`, so `Model::model_mat` stays the model's own base
+/// transform, shared by every instance.
+///
+/// The world matrix is cached, and only valid after `SceneGraph::update_world_matrices`.
+///
+pub struct SceneNode {
+    pub model: Option<Arc<Model>>,
+    local: Matrix4<f32>,
+    world: Matrix4<f32>,
+    dirty: bool,
+}
+
+impl SceneNode {
+    pub fn new(model: Option<Arc<Model>>, local: Matrix4<f32>) -> Self {
+        SceneNode {
+            model,
+            local,
+            world: Matrix4::identity(),
+            dirty: true,
+        }
+    }
+
+    ///
+    /// The transform relative to the parent node
+    ///
+    pub fn local(&self) -> &Matrix4<f32> {
+        &self.local
+    }
+
+    ///
+    /// Move the node, and so everything below it, on the next world matrix update
+    ///
+    pub fn set_local(&mut self, local: Matrix4<f32>) {
+        self.local = local;
+        self.dirty = true;
+    }
+
+    ///
+    /// The transform relative to the root, as of the last world matrix update
+    ///
+    pub fn world(&self) -> &Matrix4<f32> {
+        &self.world
+    }
+
+    ///
+    /// Whether the world matrix is out of date
+    ///
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    ///
+    /// Recompute the world matrix on the next update, without changing the local transform -
+    /// needed after `Node::reparent`, which knows nothing about transforms
+    ///
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+}
+
+impl Default for SceneNode {
+    fn default() -> Self {
+        SceneNode::new(None, Matrix4::identity())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum DrawMode {
     Wireframe(f32),
     Points,
     Textured,
 }
 
+///
+/// The graphics API windows are created for. A window is presented to by one API only, so this
+/// must match the rendering mod that is loaded.
+///
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum GraphicsApi {
+    /// for `mod_rendering_vulkano`
+    #[default]
+    Vulkan,
+    /// for `mod_rendering_opengl`, through an SDL GL context
+    OpenGL,
+    /// for `mod_rendering_software`, through the SDL window surface
+    Software,
+}
+
 pub struct WindowWithAttrs {
     pub window: Window,
     pub draw_mode: DrawMode,
@@ -27,6 +139,7 @@ pub struct RenderState {
     pub windows: Vec<WindowWithAttrs>,
     pub renderers: Vec<Box<dyn Renderer>>,
     pub render_layers: Vec<Arc<SceneGraph>>,
+    pub graphics_api: GraphicsApi,
 }
 
 impl Default for RenderState {
@@ -36,6 +149,7 @@ impl Default for RenderState {
             windows: Vec::new(),
             renderers: Vec::new(),
             render_layers: Vec::new(),
+            graphics_api: Default::default(),
         }
     }
 }
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+///
+/// Timing of the current frame, kept up to date by the host's main loop
+///
+/// Mods ticked at a fixed rate are passed `fixed_dt` on every tick, and mods ticked every frame
+/// are passed `frame_dt`. Renderers can use `alpha` to interpolate between the last two fixed
+/// ticks.
+///
+#[derive(Clone, Debug)]
+pub struct TimeState {
+    /// Frames run so far
+    pub frame: u64,
+    /// Fixed ticks run so far
+    pub tick: u64,
+    /// Length of a fixed tick
+    pub fixed_dt: Duration,
+    /// Wall time between the start of the previous frame and this one
+    pub frame_dt: Duration,
+    /// How far the current frame is between the last fixed tick and the next, from 0 to 1
+    pub alpha: f32,
+}
+
+impl Default for TimeState {
+    fn default() -> Self {
+        TimeState {
+            frame: 0,
+            tick: 0,
+            fixed_dt: Duration::from_nanos(1_000_000_000 / 60),
+            frame_dt: Duration::from_millis(0),
+            alpha: 0.0,
+        }
+    }
+}
@@ -237,6 +237,13 @@ impl<'a> ThingBuilder<'a> {
         self
     }
 
+    pub fn with_health(mut self, health: HealthFacet) -> Self {
+        let idx = self.world.facets.health.len();
+        self.world.facets.health.push(health);
+        self.facets.push(FacetIndex::Health(idx));
+        self
+    }
+
     pub fn build(self) -> Arc<Mutex<Thing>> {
         let thing = Thing::new(self.facets);
         let a = Arc::new(Mutex::new(thing));
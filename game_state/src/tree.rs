@@ -78,12 +78,6 @@ impl<T> NodeVisitor<T> for BreadthFirstVisitor<T> {
     }
 }
 
-impl<T> Drop for Node<T> {
-    fn drop(&mut self) {
-        println!("Dropping node {}", self.id);
-    }
-}
-
 impl<T> fmt::Display for Node<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let p = match self.parent {
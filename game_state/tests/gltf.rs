@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use game_state::model::gltf;
+    use game_state::model::{Indices, Model, TextureCache};
+    use game_state::nalgebra::{Matrix4, Vector3};
+    use game_state::tree::BreadthFirstIterator;
+
+    fn asset(name: &str) -> String {
+        format!("{}/../assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn translation(x: f32, y: f32, z: f32) -> Matrix4<f32> {
+        Matrix4::new_translation(&Vector3::new(x, y, z))
+    }
+
+    #[test]
+    fn gltf_models_are_placed_by_their_nodes() {
+        let models = Model::load(&asset("quads.gltf"), Matrix4::identity()).unwrap();
+        // the quad under "base", the two primitives of its child "arm", and the quad again
+        // under "other"
+        assert_eq!(models.len(), 4);
+        assert_eq!(models[0].model_mat, translation(0.0, 1.0, 0.0));
+        assert_eq!(models[1].model_mat, translation(2.0, 1.0, 0.0));
+        assert_eq!(models[2].model_mat, translation(2.0, 1.0, 0.0));
+        assert_eq!(models[3].model_mat, Matrix4::new_scaling(2.0));
+        // numbered in load order, though the last shows the same mesh as the first
+        let objects = models.iter().map(|model| model.object).collect::<Vec<_>>();
+        assert_eq!(objects, [0, 1, 2, 3]);
+
+        let quad = &models[0];
+        assert_eq!(quad.mesh.vertices.len(), 4);
+        assert_eq!(quad.mesh.indices, Indices::U16(vec![0, 1, 2, 0, 2, 3]));
+        // v flipped to point up, as in OBJ files
+        assert_eq!(quad.mesh.vertices[0].uvw.1, 0.0);
+
+        let brick = &quad.material;
+        assert_eq!(brick.name, "brick");
+        assert_eq!(brick.diffuse, [1.0, 0.5, 0.5]);
+        assert_eq!(brick.metallic, 0.25);
+        assert_eq!(brick.roughness, 0.75);
+        let diffuse_map = brick.diffuse_map.as_ref().unwrap();
+        assert_eq!(diffuse_map.path, Path::new(&asset("plane.png")));
+
+        let glow = &models[1].material;
+        assert_eq!(glow.emissive, [1.0, 1.0, 0.0]);
+        assert_eq!(glow.opacity, 0.5);
+        // glTF's default material is white
+        assert_eq!(models[2].material.name, "default");
+        assert_eq!(models[2].material.diffuse, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn glb_files_embed_buffers_and_textures() {
+        let models = Model::load(&asset("quads.glb"), Matrix4::identity()).unwrap();
+        assert_eq!(models.len(), 4);
+
+        let diffuse_map = models[0].material.diffuse_map.clone().unwrap();
+        assert_eq!(diffuse_map.path, PathBuf::from(asset("quads.glb")));
+        assert_eq!(diffuse_map.image.to_rgba().dimensions(), (2, 2));
+        // decoded once, for both nodes showing the quad
+        let other = models[3].material.diffuse_map.clone().unwrap();
+        assert!(Arc::ptr_eq(&diffuse_map.image, &other.image));
+
+        assert_eq!(
+            models[0].source_files(),
+            [PathBuf::from(asset("quads.glb"))]
+        );
+    }
+
+    #[test]
+    fn gltf_scene_keeps_the_node_tree() {
+        let scene = gltf::load_scene(&asset("quads.gltf"), &mut TextureCache::new()).unwrap();
+        // each mesh primitive is loaded once
+        assert_eq!(scene.models.len(), 3);
+
+        scene.graph.update_world_matrices();
+        let placed = BreadthFirstIterator::new(scene.graph.root.clone())
+            .filter_map(|(_id, rc)| {
+                let node = rc.borrow();
+                node.data
+                    .model
+                    .as_ref()
+                    .map(|model| (model.id, *node.data.world()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(placed.len(), 4);
+        assert_eq!(placed[0].1, translation(0.0, 1.0, 0.0));
+        assert_eq!(placed[1].1, Matrix4::new_scaling(2.0));
+        // "base" and "other" show the same model
+        assert_eq!(placed[0].0, placed[1].0);
+        assert_eq!(placed[2].1, translation(2.0, 1.0, 0.0));
+        // the second primitive of "arm" hangs off it
+        assert_eq!(placed[3].1, translation(2.0, 1.0, 0.0));
+    }
+}
@@ -0,0 +1,135 @@
+//!
+//! Golden image tests - render a scene offscreen, from a fixed camera, and compare it against a
+//! stored PNG in `tests/golden`.
+//!
+//! A missing golden image fails the test. To add one, or after an intended change to how things
+//! look, record them with `SG_UPDATE_GOLDEN=1 cargo test --test golden` and review the new
+//! images before committing them. On a mismatch, the rendered image is written
+//! to the cargo target directory, next to the failure message.
+//!
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use game_state::image::{self, RgbaImage};
+    use game_state::model::Model;
+    use game_state::nalgebra::{Matrix4, Vector3};
+    use game_state::software::OffscreenRenderer;
+    use game_state::state::{DrawMode, SceneGraph, SceneNode};
+    use game_state::thing::CameraFacet;
+    use game_state::tree::Node;
+    use game_state::Renderer;
+
+    const WIDTH: u32 = 96;
+    const HEIGHT: u32 = 72;
+
+    ///
+    /// How far a pixel may be off, per channel, and how many pixels may be off by more - to
+    /// absorb rounding differences at triangle edges between platforms
+    ///
+    const CHANNEL_TOLERANCE: u8 = 8;
+    const MAX_DIFFERING_PIXELS: usize = 8;
+
+    fn asset(name: &str) -> String {
+        format!("{}/../assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn camera() -> CameraFacet {
+        CameraFacet::new(Vector3::new(0.0, 0.0, -4.0), 0.5, 0.7)
+    }
+
+    fn render(model: &str, draw_mode: DrawMode) -> RgbaImage {
+        let models = Model::load(&asset(model), Matrix4::identity()).unwrap();
+        let root = Node::create(SceneNode::default(), None);
+        for model in models {
+            let node = SceneNode::new(Some(Arc::new(model)), Matrix4::identity());
+            Node::create(node, Some(&root));
+        }
+
+        let mut renderer = OffscreenRenderer::new(WIDTH, HEIGHT, draw_mode);
+        renderer.queue_render_layer(Arc::new(SceneGraph { root }));
+        renderer.present(&camera());
+        renderer.capture_frame().unwrap()
+    }
+
+    fn assert_matches_golden(name: &str, actual: &RgbaImage) {
+        let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.png", name));
+        if std::env::var_os("SG_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+            actual.save(&golden_path).unwrap();
+            eprintln!("recorded golden image {:?}", golden_path);
+            return;
+        }
+        assert!(
+            golden_path.exists(),
+            "{} - no golden image at {:?}, record it with SG_UPDATE_GOLDEN=1",
+            name,
+            golden_path
+        );
+
+        let expected = image::open(&golden_path).unwrap().to_rgba();
+        assert_eq!(
+            expected.dimensions(),
+            actual.dimensions(),
+            "{} - size differs from {:?}",
+            name,
+            golden_path
+        );
+        let mut differing = 0;
+        let mut worst = 0;
+        for (e, a) in expected.chunks(4).zip(actual.chunks(4)) {
+            let diff = e
+                .iter()
+                .zip(a.iter())
+                .map(|(e, a)| (*e as i16 - *a as i16).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0);
+            worst = worst.max(diff);
+            if diff > CHANNEL_TOLERANCE {
+                differing += 1;
+            }
+        }
+        if differing > MAX_DIFFERING_PIXELS {
+            let actual_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+                .join("golden")
+                .join(format!("{}.png", name));
+            std::fs::create_dir_all(actual_path.parent().unwrap()).unwrap();
+            actual.save(&actual_path).unwrap();
+            panic!(
+                "{} - {} pixels differ from {:?} (by up to {}), rendered image written to {:?}",
+                name, differing, golden_path, worst, actual_path
+            );
+        }
+    }
+
+    #[test]
+    fn textured_cube() {
+        let image = render("helper-cube.obj", DrawMode::Textured);
+        // the cube is in the middle of the frame, over the black background
+        let center = image.get_pixel(WIDTH / 2, HEIGHT / 2);
+        assert_ne!(center, image.get_pixel(0, 0));
+        assert_matches_golden("helper_cube_textured", &image);
+    }
+
+    #[test]
+    fn wireframe_cube() {
+        let image = render("helper-cube.obj", DrawMode::Wireframe(2.0));
+        assert_matches_golden("helper_cube_wireframe", &image);
+    }
+
+    #[test]
+    fn points_cube() {
+        let image = render("helper-cube.obj", DrawMode::Points);
+        assert_matches_golden("helper_cube_points", &image);
+    }
+
+    #[test]
+    fn textured_plane() {
+        let image = render("plane.obj", DrawMode::Textured);
+        assert_matches_golden("plane_textured", &image);
+    }
+}
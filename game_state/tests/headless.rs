@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use game_state::model::Model;
+    use game_state::nalgebra::Matrix4;
+    use game_state::software::OffscreenRenderer;
+    use game_state::state::{
+        DrawMode, GraphicsApi, LogAccess, ModelAccess, RenderAccess, RenderLayerAccess, SceneGraph,
+        SceneNode, State, VariableAccess, WindowAccess, WorldAccess,
+    };
+    use game_state::tree::Node;
+
+    #[test]
+    fn headless_state_has_no_sdl() {
+        let state = State::builder().headless().build().unwrap();
+        assert!(state.is_headless());
+        assert!(state.sdl_context.is_none());
+        assert!(state.sdl_subsystems.is_none());
+    }
+
+    #[test]
+    fn add_window_fails_when_headless() {
+        let mut state = State::builder().headless().build().unwrap();
+        let result = state.add_window(800, 600, "headless", 0, 0, DrawMode::Textured);
+        assert!(result.is_err());
+        assert!(state.get_windows().is_empty());
+    }
+
+    #[test]
+    fn builder_sets_graphics_api() {
+        let state = State::builder().headless().build().unwrap();
+        assert_eq!(state.get_graphics_api(), GraphicsApi::Vulkan);
+        let state = State::builder()
+            .headless()
+            .graphics_api(GraphicsApi::OpenGL)
+            .build()
+            .unwrap();
+        assert_eq!(state.get_graphics_api(), GraphicsApi::OpenGL);
+    }
+
+    #[test]
+    fn headless_state_can_be_updated() {
+        let mut state = State::builder().headless().build().unwrap();
+        for frame in 0..10 {
+            state.set_bool("even", frame % 2 == 0);
+            state.get_world().get_facets();
+            // no camera and no renderers - presenting is a no-op
+            state.push_render_layers();
+            state.present_all();
+        }
+        assert_eq!(state.get_bool("even"), Some(false));
+        state.get_logger().info("test", "still running");
+    }
+
+    #[test]
+    fn replace_model_swaps_every_use() {
+        let path = format!("{}/../assets/models/plane.obj", env!("CARGO_MANIFEST_DIR"));
+        let load = || Model::load(&path, Matrix4::identity()).unwrap().remove(0);
+
+        let mut state = State::builder().headless().build().unwrap();
+        let model = Arc::new(load());
+        state.add_model(model.clone());
+        let root = Node::create(SceneNode::default(), None);
+        let node = Node::create(
+            SceneNode::new(Some(model.clone()), Matrix4::identity()),
+            Some(&root),
+        );
+        state.add_render_layer(Arc::new(SceneGraph { root }));
+        state
+            .get_world()
+            .start_thing()
+            .with_model(Matrix4::identity(), model.clone())
+            .build();
+        state.add_renderer(Box::new(OffscreenRenderer::new(8, 8, DrawMode::Textured)));
+
+        let mut reloaded = load();
+        reloaded.id = model.id;
+        let reloaded = Arc::new(reloaded);
+        assert!(state.replace_model(reloaded.clone()));
+
+        assert!(Arc::ptr_eq(&state.get_models()[0], &reloaded));
+        let node_model = node.borrow().data.model.clone().unwrap();
+        assert!(Arc::ptr_eq(&node_model, &reloaded));
+        assert!(Arc::ptr_eq(
+            &state.get_world().get_facets().models[0].model,
+            &reloaded
+        ));
+
+        // a model that was never added isn't replaced
+        assert!(!state.replace_model(Arc::new(load())));
+        assert!(Arc::ptr_eq(&state.get_models()[0], &reloaded));
+    }
+}
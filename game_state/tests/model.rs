@@ -0,0 +1,212 @@
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use game_state::model::{Indices, Material, Mesh, Model, TextureCache, Vertex, FLAT_NORMAL};
+    use game_state::nalgebra::Matrix4;
+
+    fn asset(name: &str) -> String {
+        format!("{}/../assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn material_is_read_from_mtl() {
+        let models = Model::load(&asset("helper-cube.obj"), Matrix4::identity()).unwrap();
+        let material = &models[0].material;
+        assert_eq!(material.name, "Material.001");
+        assert_eq!(material.diffuse, [0.8, 0.8, 0.8]);
+        assert_eq!(material.specular, [0.5, 0.5, 0.5]);
+        assert!((material.shininess - 324.0).abs() < 0.001);
+        assert_eq!(material.opacity, 1.0);
+
+        let diffuse_map = material.diffuse_map.as_ref().unwrap();
+        assert_eq!(diffuse_map.path, Path::new(&asset("directions_cube.png")));
+        assert!(material.normal_map.is_none());
+        assert_eq!(material.maps().count(), 1);
+    }
+
+    #[test]
+    fn models_without_a_material_get_the_default() {
+        let models = Model::load(&asset("lego.obj"), Matrix4::identity()).unwrap();
+        assert!(!models.is_empty());
+        for model in models {
+            assert!(model.material.diffuse_map.is_none());
+            assert_eq!(model.material.diffuse, Material::default().diffuse);
+            // drawn with a single texel of the diffuse color
+            assert_eq!(model.material.unlit_texture().dimensions(), (1, 1));
+        }
+    }
+
+    #[test]
+    fn missing_maps_are_neutral() {
+        let mut material = Material::default();
+        material.shininess = 500.0;
+        // a flat normal, no highlights
+        assert_eq!(material.normal_texture().into_raw(), FLAT_NORMAL.to_vec());
+        assert_eq!(material.specular_texture().into_raw(), vec![0, 0, 0, 128]);
+
+        let mut textures = TextureCache::new();
+        material.specular_map = Some(textures.load(Path::new(&asset("plane.png"))).unwrap());
+        let specular = material.specular_texture();
+        assert_eq!(
+            specular.dimensions(),
+            material
+                .specular_map
+                .as_ref()
+                .unwrap()
+                .image
+                .to_rgba()
+                .dimensions()
+        );
+        assert!(specular.pixels().all(|texel| texel.data[3] == 128));
+    }
+
+    const TRIANGLE: &str = "v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vn 0.0 0.0 1.0
+";
+
+    #[test]
+    fn each_object_gets_its_own_material() {
+        let dir = std::env::temp_dir().join(format!("objects-{}", std::process::id()));
+        fs::create_dir_all(dir.join("textures")).unwrap();
+        fs::copy(asset("plane.png"), dir.join("textures/wall.png")).unwrap();
+        fs::write(
+            dir.join("walls.mtl"),
+            "newmtl red\nKd 1.0 0.0 0.0\n\
+             newmtl brick\nmap_Kd textures/wall.png\n\
+             newmtl mossy_brick\nKd 0.5 1.0 0.5\nmap_Kd textures/wall.png\n",
+        )
+        .unwrap();
+        let face = |n: usize| {
+            format!(
+                "f {0}/1/1 {1}/1/1 {2}/1/1\n",
+                n * 3 + 1,
+                n * 3 + 2,
+                n * 3 + 3
+            )
+        };
+        let mut obj = "mtllib walls.mtl\n".to_string();
+        for (n, material) in ["red", "brick", "mossy_brick"].iter().enumerate() {
+            obj += &format!("o wall{}\nusemtl {}\n{}{}", n, material, TRIANGLE, face(n));
+        }
+        let filename = dir.join("walls.obj");
+        fs::write(&filename, obj).unwrap();
+
+        let models = Model::load(filename.to_str().unwrap(), Matrix4::identity()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names = models
+            .iter()
+            .map(|model| model.material.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["red", "brick", "mossy_brick"]);
+        assert_eq!(models[0].material.diffuse, [1.0, 0.0, 0.0]);
+        assert!(models[0].material.diffuse_map.is_none());
+
+        // found next to the MTL, and decoded once for both materials
+        let brick = models[1].material.diffuse_map.as_ref().unwrap();
+        let mossy_brick = models[2].material.diffuse_map.as_ref().unwrap();
+        assert_eq!(brick.path, dir.join("textures/wall.png"));
+        assert!(Arc::ptr_eq(&brick.image, &mossy_brick.image));
+    }
+
+    // The materials of the objects of an OBJ using red and brick
+    fn object_materials(name: &str, obj: &str) -> Vec<String> {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("walls.mtl"),
+            "newmtl red\nKd 1.0 0.0 0.0\nnewmtl brick\nKd 0.5 0.2 0.2\n",
+        )
+        .unwrap();
+        let filename = dir.join("walls.obj");
+        fs::write(&filename, format!("mtllib walls.mtl\n{}", obj)).unwrap();
+
+        let models = Model::load(filename.to_str().unwrap(), Matrix4::identity()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        models
+            .iter()
+            .map(|model| model.material.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn each_group_gets_its_own_material() {
+        let obj = format!(
+            "g wall0\nusemtl brick\n{0}f 1/1/1 2/1/1 3/1/1\n\
+             g wall1\nusemtl red\n{0}f 4/1/1 5/1/1 6/1/1\n",
+            TRIANGLE
+        );
+        assert_eq!(object_materials("groups", &obj), ["brick", "red"]);
+    }
+
+    #[test]
+    fn material_before_the_first_object_is_its_own() {
+        // the first object uses brick, and the second carries it over
+        let obj = format!(
+            "usemtl brick\no wall0\n{0}f 1/1/1 2/1/1 3/1/1\n\
+             o wall1\n{0}f 4/1/1 5/1/1 6/1/1\n\
+             o wall2\nusemtl red\n{0}f 7/1/1 8/1/1 9/1/1\n",
+            TRIANGLE
+        );
+        assert_eq!(object_materials("leading", &obj), ["brick", "brick", "red"]);
+    }
+
+    #[test]
+    fn textures_are_decoded_once() {
+        let mut textures = TextureCache::new();
+        let first = Model::load_with_cache(
+            &asset("helper-cube.obj"),
+            Matrix4::identity(),
+            &mut textures,
+        )
+        .unwrap();
+        let second = Model::load_with_cache(
+            &asset("helper-cube.obj"),
+            Matrix4::identity(),
+            &mut textures,
+        )
+        .unwrap();
+        assert_eq!(textures.len(), 1);
+
+        let image = |models: &[Model]| {
+            models[0]
+                .material
+                .diffuse_map
+                .as_ref()
+                .unwrap()
+                .image
+                .clone()
+        };
+        assert!(Arc::ptr_eq(&image(&first), &image(&second)));
+    }
+
+    fn vertices(count: usize) -> Vec<Vertex> {
+        vec![Vertex::new((0.0, 0.0, 0.0), (0.0, 0.0, 0.0), (0.0, 0.0, 1.0)); count]
+    }
+
+    #[test]
+    fn indices_widen_with_the_vertex_count() {
+        let mesh = Mesh::create(vertices(65_536), &[0, 1, 65_535]).unwrap();
+        assert_eq!(mesh.indices, Indices::U16(vec![0, 1, 65_535]));
+
+        let mesh = Mesh::create(vertices(65_537), &[0, 65_535, 65_536]).unwrap();
+        assert_eq!(mesh.indices, Indices::U32(vec![0, 65_535, 65_536]));
+        assert_eq!(
+            mesh.indices.triangles().collect::<Vec<_>>(),
+            [[0, 65_535, 65_536]]
+        );
+    }
+
+    #[test]
+    fn out_of_range_indices_are_an_error() {
+        let err = Mesh::create(vertices(3), &[0, 1, 3]).unwrap_err();
+        assert_eq!(err.to_string(), "index 3 is out of range of the 3 vertices");
+    }
+}
@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+
+    use game_state::model::ogex;
+    use game_state::model::{Indices, Model, TextureCache};
+    use game_state::nalgebra::{Matrix4, Point3, Vector3};
+    use game_state::tree::BreadthFirstIterator;
+
+    fn asset(name: &str) -> String {
+        format!("{}/../assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).norm() < 0.0001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn gurp_geometry_is_imported() {
+        let models = Model::load(&asset("gurp.ogex"), Matrix4::identity()).unwrap();
+        assert_eq!(models.len(), 1);
+
+        let cube = &models[0];
+        assert_eq!(cube.mesh.vertices.len(), 556);
+        match &cube.mesh.indices {
+            Indices::U16(indices) => assert_eq!(indices.len(), 308 * 3),
+            Indices::U32(_) => panic!("556 vertices fit 16-bit indices"),
+        }
+        assert_eq!(cube.material.name, "Material");
+        assert_eq!(cube.material.specular, [0.5, 0.5, 0.5]);
+        assert_eq!(cube.material.shininess, 50.0);
+
+        // exported z-up, turned y-up
+        let up = cube.model_mat.transform_point(&Point3::new(0.0, 0.0, 1.0));
+        assert_near(up, Point3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn gurp_scene_keeps_every_node() {
+        let scene = ogex::load_scene(&asset("gurp.ogex"), &mut TextureCache::new()).unwrap();
+        assert_eq!(scene.models.len(), 1);
+        // the cube, the lamp and the camera
+        assert_eq!(scene.graph.root.borrow().children().len(), 3);
+
+        scene.graph.update_world_matrices();
+        let lamp = scene.graph.root.borrow().children()[1].clone();
+        assert!(lamp.borrow().data.model.is_none());
+        let position = lamp
+            .borrow()
+            .data
+            .world()
+            .transform_point(&Point3::origin());
+        assert_near(position, Point3::new(4.076245, 5.903862, -1.005454));
+    }
+
+    #[test]
+    fn hierarchy_and_index_arrays_are_imported() {
+        let dir = std::env::temp_dir().join(format!("ogex-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("arm.ogex");
+        fs::write(
+            &filename,
+            r#"
+            Metric (key = "up") {string {"y"}}
+            Node $node1 {
+                Translation {float[3] {{0.0, 1.0, 0.0}}}
+                GeometryNode $node2 {
+                    ObjectRef {ref {$geometry1}}
+                    MaterialRef (index = 1) {ref {$material2}}
+                    MaterialRef {ref {$material1}}
+                    Translation (kind = "x") {float {2.0}}
+                }
+            }
+            GeometryObject $geometry1 {
+                Mesh {
+                    VertexArray (attrib = "position") {
+                        float[3] {{0.0, 0.0, 0.0}, {1.0, 0.0, 0.0}, {0.0, 1.0, 0.0}}
+                    }
+                    IndexArray {unsigned_int16[3] {{0, 1, 2}}}
+                    IndexArray (material = 1, front = "cw") {unsigned_int16[3] {{0, 1, 2}}}
+                }
+            }
+            Material $material1 {Name {string {"red"}} Color (attrib = "diffuse") {float[3] {{1.0, 0.0, 0.0}}}}
+            Material $material2 {Color (attrib = "diffuse") {float[4] {{0.0, 0.0, 1.0, 0.5}}}}
+            "#,
+        )
+        .unwrap();
+        let filename = filename.to_str().unwrap();
+        let models = Model::load(filename, Matrix4::identity()).unwrap();
+        let scene = ogex::load_scene(filename, &mut TextureCache::new()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(models.len(), 2);
+        let placed = Matrix4::new_translation(&Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(models[0].model_mat, placed);
+        assert_eq!(models[1].model_mat, placed);
+        assert_eq!(models[0].material.name, "red");
+        assert_eq!(models[1].material.name, "material2");
+        assert_eq!(models[1].material.opacity, 0.5);
+        // clockwise triangles are turned around
+        assert_eq!(models[1].mesh.indices, Indices::U16(vec![2, 1, 0]));
+
+        scene.graph.update_world_matrices();
+        let worlds = BreadthFirstIterator::new(scene.graph.root.clone())
+            .filter(|(_id, rc)| rc.borrow().data.model.is_some())
+            .map(|(_id, rc)| *rc.borrow().data.world())
+            .collect::<Vec<_>>();
+        assert_eq!(worlds, [placed, placed]);
+    }
+
+    #[test]
+    fn index_arrays_must_be_whole_triangles() {
+        let dir = std::env::temp_dir().join(format!("ogex-indices-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let load = |name: &str, index_array: &str| {
+            let filename = dir.join(name);
+            fs::write(
+                &filename,
+                format!(
+                    r#"
+                    GeometryNode {{ObjectRef {{ref {{$geometry1}}}}}}
+                    GeometryObject $geometry1 {{
+                        Mesh {{
+                            VertexArray (attrib = "position") {{
+                                float[3] {{{{0, 0, 0}}, {{1, 0, 0}}, {{0, 1, 0}}, {{1, 1, 0}}}}
+                            }}
+                            {}
+                        }}
+                    }}
+                    "#,
+                    index_array
+                ),
+            )
+            .unwrap();
+            Model::load(filename.to_str().unwrap(), Matrix4::identity())
+        };
+
+        let models = load("u32.ogex", "IndexArray {unsigned_int32 {0, 1, 2, 1, 3, 2}}").unwrap();
+        assert_eq!(models[0].mesh.indices.len(), 6);
+
+        assert!(load("partial.ogex", "IndexArray {unsigned_int32 {0, 1, 2, 3}}").is_err());
+        assert!(load("negative.ogex", "IndexArray {float {0, -1, 2}}").is_err());
+        assert!(load("fraction.ogex", "IndexArray {float {0, 1.5, 2}}").is_err());
+        // without an IndexArray, every 3 vertices are a triangle
+        assert!(load("unindexed.ogex", "").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
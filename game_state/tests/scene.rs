@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use game_state::nalgebra::{Matrix4, Vector3};
+    use game_state::scene::{
+        ModelDescription, NodeDescription, SceneDescription, ThingDescription, Transform,
+    };
+    use game_state::state::{SceneGraph, SceneNode};
+    use game_state::thing::World;
+    use game_state::tree::{BreadthFirstIterator, Node};
+
+    fn translation(x: f32, y: f32, z: f32) -> Matrix4<f32> {
+        Matrix4::new_translation(&Vector3::new(x, y, z))
+    }
+
+    #[test]
+    fn world_matrices_through_any_depth() {
+        let root = Node::create(SceneNode::new(None, translation(1.0, 0.0, 0.0)), None);
+        let mut parent = root.clone();
+        for _ in 0..10 {
+            parent = Node::create(
+                SceneNode::new(None, translation(1.0, 0.0, 0.0)),
+                Some(&parent),
+            );
+        }
+        let leaf = Node::create(
+            SceneNode::new(None, translation(0.0, 2.0, 0.0)),
+            Some(&parent),
+        );
+        let graph = SceneGraph { root };
+
+        assert!(leaf.borrow().data.is_dirty());
+        graph.update_world_matrices();
+
+        assert!(!leaf.borrow().data.is_dirty());
+        assert_eq!(*leaf.borrow().data.world(), translation(11.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn moving_a_parent_moves_its_children() {
+        let root = Node::create(SceneNode::default(), None);
+        let parent = Node::create(
+            SceneNode::new(None, translation(1.0, 0.0, 0.0)),
+            Some(&root),
+        );
+        let child = Node::create(
+            SceneNode::new(None, translation(0.0, 1.0, 0.0)),
+            Some(&parent),
+        );
+        let sibling = Node::create(
+            SceneNode::new(None, translation(0.0, 0.0, 1.0)),
+            Some(&root),
+        );
+        let graph = SceneGraph { root };
+        graph.update_world_matrices();
+
+        parent
+            .borrow_mut()
+            .data
+            .set_local(translation(5.0, 0.0, 0.0));
+        assert!(parent.borrow().data.is_dirty());
+        // the cached matrix is stale until the update
+        assert_eq!(*child.borrow().data.world(), translation(1.0, 1.0, 0.0));
+
+        graph.update_world_matrices();
+        assert_eq!(*child.borrow().data.world(), translation(5.0, 1.0, 0.0));
+        assert_eq!(*sibling.borrow().data.world(), translation(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn reparented_nodes_follow_their_new_parent() {
+        let root = Node::create(SceneNode::default(), None);
+        let left = Node::create(
+            SceneNode::new(None, translation(-1.0, 0.0, 0.0)),
+            Some(&root),
+        );
+        let right = Node::create(
+            SceneNode::new(None, translation(1.0, 0.0, 0.0)),
+            Some(&root),
+        );
+        let child = Node::create(SceneNode::default(), Some(&left));
+        let graph = SceneGraph { root };
+        graph.update_world_matrices();
+        assert_eq!(*child.borrow().data.world(), translation(-1.0, 0.0, 0.0));
+
+        Node::reparent(child.clone(), right).unwrap();
+        child.borrow_mut().data.mark_dirty();
+        graph.update_world_matrices();
+        assert_eq!(*child.borrow().data.world(), translation(1.0, 0.0, 0.0));
+    }
+
+    fn default_scene() -> SceneDescription {
+        let path = format!(
+            "{}/../assets/scenes/default.ron",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        SceneDescription::load(Path::new(&path)).unwrap()
+    }
+
+    #[test]
+    fn scene_round_trips() {
+        let scene = default_scene();
+        assert_eq!(scene.models.len(), 1);
+        assert_eq!(scene.nodes[0].children.len(), 2);
+
+        let written = scene.to_ron().unwrap();
+        assert_eq!(SceneDescription::parse(&written).unwrap(), scene);
+    }
+
+    #[test]
+    fn scene_errors_point_to_the_entry() {
+        let err = SceneDescription::parse(
+            r#"(
+                models: [(name: "plane", path: "plane.obj")],
+                nodes: [(children: [(), (name: Some("left"), model: Some("cube"))])],
+            )"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"nodes[0].children[1] ("left"): unknown model "cube""#
+        );
+
+        let err = SceneDescription::parse(
+            r#"(
+                models: [(name: "plane", path: "a.obj"), (name: "plane", path: "b.obj")],
+            )"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"models[1]: duplicate model name "plane""#
+        );
+
+        let err = SceneDescription::parse("(things: [(health: Some(10)), ()])").unwrap_err();
+        assert_eq!(err.to_string(), "things[1]: thing has no facets");
+
+        // syntax errors give the position instead
+        let err = SceneDescription::parse("(models: [(name: \"plane\",\n path: )])").unwrap_err();
+        assert!(err.to_string().starts_with("2:"), "{}", err);
+    }
+
+    #[test]
+    fn scene_builds_graph_and_things() {
+        let path = format!("{}/../assets/models/plane.obj", env!("CARGO_MANIFEST_DIR"));
+        let node = |x: f32| NodeDescription {
+            model: Some("plane".to_string()),
+            transform: Transform {
+                translation: [x, 0.0, 0.0],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let description = SceneDescription {
+            models: vec![ModelDescription {
+                name: "plane".to_string(),
+                path,
+            }],
+            nodes: vec![NodeDescription {
+                children: vec![node(-1.0), node(1.0)],
+                ..Default::default()
+            }],
+            things: vec![ThingDescription {
+                health: Some(10),
+                ..Default::default()
+            }],
+        };
+
+        let mut world = World::new();
+        let scene = description.build(&mut world).unwrap();
+        assert_eq!(scene.things.len(), 1);
+        assert_eq!(world.get_facets().health[0].hp, 10);
+
+        scene.graph.update_world_matrices();
+        let placed = BreadthFirstIterator::new(scene.graph.root.clone())
+            .filter_map(|(_id, rc)| {
+                let node = rc.borrow();
+                node.data
+                    .model
+                    .as_ref()
+                    .map(|model| (model.id, *node.data.world()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(placed.len(), 2);
+        // one model, instanced twice
+        assert_eq!(placed[0].0, placed[1].0);
+        assert_eq!(placed[0].1, translation(-1.0, 0.0, 0.0));
+        assert_eq!(placed[1].1, translation(1.0, 0.0, 0.0));
+    }
+}
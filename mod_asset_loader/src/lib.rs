@@ -1,69 +1,142 @@
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-// TODO: switch to nalgebra
-use game_state::nalgebra::{Matrix4, Vector3};
-
+use game_state::abi::{ModAbi, ModResult};
 use game_state::model::Model;
+use game_state::nalgebra::Matrix4;
+use game_state::scene::SceneDescription;
+use game_state::state::LogAccess;
 use game_state::state::ModelAccess;
 use game_state::state::RenderLayerAccess;
-use game_state::state::SceneGraph;
 use game_state::state::State;
 use game_state::state::WorldAccess;
-use game_state::thing::CameraFacet;
-use game_state::tree::Node;
-
-#[no_mangle]
-pub extern "C" fn mod_asset_loader_load(state: &mut State) {
-    assert!(state.get_render_layers().is_empty());
 
-    let origin = Vector3::new(0.0, 0.0, 0.0);
-    let mx = Matrix4::new_translation(&origin) * Matrix4::new_scaling(1.0);
+mod watcher;
+use watcher::AssetWatcher;
 
-    let model_path = "assets/models/plane.obj";
-    println!(" loading model: {}", model_path);
-    let helper = Model::load(model_path, mx).unwrap().pop().unwrap();
+const SCENE_PATH: &str = "assets/scenes/default.ron";
+const LOG_TARGET: &str = "asset_loader";
 
-    let am = Arc::new(helper);
-    state.add_model(am.clone());
+// Watches the files the loaded models came from. It lives in this library, so it is lost - and
+// rebuilt on load - when the library is reloaded.
+static WATCHER: Mutex<Option<AssetWatcher>> = Mutex::new(None);
 
-    let world = state.get_world();
-    // build the actual entity within the world
-    let _camera = world
-        .start_thing()
-        .with_camera(CameraFacet::new(
-            Vector3::new(0.0, 0.0, -2.0), // pos
-            -1.5,                         // pitch
-            0.0,                          // yaw
-        ))
-        .build();
+#[no_mangle]
+pub extern "C" fn mod_asset_loader_abi() -> ModAbi {
+    ModAbi::current()
+}
 
-    let _helper_cube = world.start_thing().with_model(mx, am.clone()).build();
+#[no_mangle]
+pub extern "C-unwind" fn mod_asset_loader_load_v2(state: &mut State) -> ModResult {
+    assert!(state.get_render_layers().is_empty());
 
-    let root = Node::create(None, None);
+    state
+        .get_logger()
+        .info(LOG_TARGET, format!("loading scene: {}", SCENE_PATH));
+    let description = match SceneDescription::load(Path::new(SCENE_PATH)) {
+        Ok(description) => description,
+        Err(err) => return ModResult::error(err.to_string()),
+    };
+    let scene = match description.build(state.get_world()) {
+        Ok(scene) => scene,
+        Err(err) => return ModResult::error(format!("scene {}: {}", SCENE_PATH, err)),
+    };
 
-    // TODO make this tree api better... currently returns the node, instead make a builder?
-    // or maybe load from a file format (yaml?)
-    let _ = Node::create(None, Some(&root));
-    let helpers = Node::create(None, Some(&root));
-    let _ = Node::create(Some(am.clone()), Some(&helpers));
-    let _ = Node::create(Some(am), Some(&helpers));
+    *lock_watcher() = Some(AssetWatcher::new(&scene.models));
+    for model in scene.models {
+        state.add_model(model);
+    }
 
     // NOTE: there's some index-mirroring happening here, we probably want to associate somehow
     // other than this - it's going to be easy to get wrong
-    state.add_render_layer(Arc::new(SceneGraph { root }));
+    state.add_render_layer(Arc::new(scene.graph));
+    ModResult::ok()
 }
 
 #[no_mangle]
-pub extern "C" fn mod_asset_loader_update(_state: &mut State, _dt: &Duration) {
+pub extern "C-unwind" fn mod_asset_loader_update(state: &mut State, _dt: &Duration) {
     //
     // this module might look for unused assets, or requests for loading new ones?
     // for instance, instead of blindly loading an asset and pushing it into state, we COULD be loading files
-    // in a multithreaded context, pushing them in on this thread when we are ticked
+    // as background jobs (JobAccess), pushing them in on this thread when we are ticked
     //
+    if let Some(watcher) = lock_watcher().as_mut() {
+        for filename in watcher.poll() {
+            reload_models(state, watcher, &filename);
+        }
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn mod_asset_loader_unload(state: &mut State) {
+pub extern "C-unwind" fn mod_asset_loader_unload(state: &mut State) {
+    *lock_watcher() = None;
     state.clear_render_layers();
 }
+
+fn lock_watcher() -> std::sync::MutexGuard<'static, Option<AssetWatcher>> {
+    WATCHER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Read the models from a changed file again, and swap them in under their old identities, so
+// the render layers, the world and the renderers pick them up
+fn reload_models(state: &mut State, watcher: &mut AssetWatcher, filename: &str) {
+    let previous = watcher.models(filename).to_vec();
+    let objects = previous
+        .iter()
+        .map(|(object, _id)| object + 1)
+        .max()
+        .unwrap_or(0);
+    let models = match Model::load(filename, Matrix4::identity()) {
+        Ok(models) => models,
+        Err(err) => {
+            // likely saved halfway - keep the old models until the next change
+            state.get_logger().error(
+                LOG_TARGET,
+                format!("unable to reload {}: {}", filename, err),
+            );
+            return;
+        }
+    };
+    if models.len() != objects {
+        state.get_logger().warn(
+            LOG_TARGET,
+            format!(
+                "{} now has {} objects instead of {} - reload the mod to pick them up",
+                filename,
+                models.len(),
+                objects
+            ),
+        );
+        return;
+    }
+
+    // each object replaces its old version everywhere the file is used
+    let mut files = Vec::new();
+    for path in models.iter().flat_map(|model| model.source_files()) {
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    watcher.set_source_files(filename, files);
+
+    let current = state.get_models();
+    for model in models {
+        for (_object, id) in previous
+            .iter()
+            .filter(|(object, _id)| *object == model.object)
+        {
+            if let Some(old) = current.iter().find(|old| old.id == *id) {
+                let mut replacement = model.clone();
+                replacement.id = old.id;
+                replacement.model_mat = old.model_mat;
+                state.replace_model(Arc::new(replacement));
+            }
+        }
+    }
+    state
+        .get_logger()
+        .info(LOG_TARGET, format!("reloaded {}", filename));
+}
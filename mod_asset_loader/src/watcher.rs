@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use game_state::model::Model;
+use game_state::Identity;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+///
+/// AssetWatcher - notices changes to the files models were loaded from, checking at most every
+/// POLL_INTERVAL
+///
+pub struct AssetWatcher {
+    sources: Vec<ModelSource>,
+    last_check: Instant,
+}
+
+// The models loaded from one model file, with the object of the file each is, and every file
+// they were read from
+struct ModelSource {
+    filename: String,
+    models: Vec<(usize, Identity)>,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl AssetWatcher {
+    pub fn new(models: &[Arc<Model>]) -> Self {
+        let mut sources: Vec<ModelSource> = Vec::new();
+        for model in models {
+            match sources.iter_mut().find(|s| s.filename == model.filename) {
+                Some(source) => {
+                    source.models.push((model.object, model.id));
+                    // each object may use textures of its own
+                    for path in model.source_files() {
+                        if !source.files.iter().any(|(watched, _)| *watched == path) {
+                            let modified = modified(&path);
+                            source.files.push((path, modified));
+                        }
+                    }
+                }
+                None => sources.push(ModelSource {
+                    filename: model.filename.clone(),
+                    models: vec![(model.object, model.id)],
+                    files: watch(model.source_files()),
+                }),
+            }
+        }
+        AssetWatcher {
+            sources,
+            last_check: Instant::now(),
+        }
+    }
+
+    ///
+    /// Returns the OBJ files with a change to them, or to their materials or textures, since
+    /// the last change was reported
+    ///
+    pub fn poll(&mut self) -> Vec<String> {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_check = Instant::now();
+
+        let mut changed = Vec::new();
+        for source in self.sources.iter_mut() {
+            let mut source_changed = false;
+            for (path, last_modified) in source.files.iter_mut() {
+                let current = modified(path);
+                if current != *last_modified {
+                    *last_modified = current;
+                    source_changed = true;
+                }
+            }
+            if source_changed {
+                changed.push(source.filename.clone());
+            }
+        }
+        changed
+    }
+
+    ///
+    /// The models loaded from a model file, each with the object of the file it is - a file
+    /// used more than once in the scene has a model for each object every time
+    ///
+    pub fn models(&self, filename: &str) -> &[(usize, Identity)] {
+        self.sources
+            .iter()
+            .find(|s| s.filename == filename)
+            .map(|s| s.models.as_slice())
+            .unwrap_or(&[])
+    }
+
+    ///
+    /// Watch the files the reloaded models of a file were read from, which may not be the same
+    /// as before - eg. after a material was pointed at another texture
+    ///
+    pub fn set_source_files(&mut self, filename: &str, files: Vec<PathBuf>) {
+        if let Some(source) = self.sources.iter_mut().find(|s| s.filename == filename) {
+            source.files = watch(files);
+        }
+    }
+}
+
+fn watch(files: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files
+        .into_iter()
+        .map(|path| {
+            let modified = modified(&path);
+            (path, modified)
+        })
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_state::nalgebra::Matrix4;
+    use std::fs;
+
+    #[test]
+    fn watches_the_textures_of_every_object() {
+        let dir = std::env::temp_dir().join(format!("watcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let plane = format!("{}/../assets/models/plane.png", env!("CARGO_MANIFEST_DIR"));
+        fs::copy(&plane, dir.join("brick.png")).unwrap();
+        fs::copy(&plane, dir.join("moss.png")).unwrap();
+        fs::write(
+            dir.join("walls.mtl"),
+            "newmtl brick\nmap_Kd brick.png\nnewmtl moss\nmap_Kd moss.png\n",
+        )
+        .unwrap();
+        let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n";
+        fs::write(
+            dir.join("walls.obj"),
+            format!(
+                "mtllib walls.mtl\no a\nusemtl brick\n{0}f 1/1/1 2/1/1 3/1/1\n\
+                 o b\nusemtl moss\n{0}f 4/1/1 5/1/1 6/1/1\n",
+                triangle
+            ),
+        )
+        .unwrap();
+
+        let filename = dir.join("walls.obj");
+        let models = Model::load(filename.to_str().unwrap(), Matrix4::identity()).unwrap();
+        let models = models.into_iter().map(Arc::new).collect::<Vec<_>>();
+        let watcher = AssetWatcher::new(&models);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let watched = watcher.sources[0]
+            .files
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(watcher.sources.len(), 1);
+        assert!(watched.contains(&dir.join("brick.png")));
+        assert!(watched.contains(&dir.join("moss.png")));
+        assert!(watched.contains(&dir.join("walls.mtl")));
+    }
+}
@@ -1,12 +1,32 @@
 use std::time::Duration;
 
+use game_state::abi::{ModAbi, ModResult};
 use game_state::state::State;
 
 #[no_mangle]
-pub extern "C" fn mod_dummy_load(state: &mut State) {}
+pub extern "C" fn mod_dummy_abi() -> ModAbi {
+    ModAbi::current()
+}
 
 #[no_mangle]
-pub extern "C" fn mod_dummy_update(state: &mut State, dt: &Duration) {}
+pub extern "C-unwind" fn mod_dummy_load_v2(state: &mut State) -> ModResult {
+    ModResult::ok()
+}
 
 #[no_mangle]
-pub extern "C" fn mod_dummy_unload(state: &mut State) {}
+pub extern "C-unwind" fn mod_dummy_update_v2(state: &mut State, dt: &Duration) -> ModResult {
+    ModResult::ok()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_dummy_unload_v2(state: &mut State) -> ModResult {
+    ModResult::ok()
+}
+
+// Optional: carry private state across a reload of this mod
+#[no_mangle]
+pub extern "C-unwind" fn mod_dummy_save_state(state: &mut State, saved: &mut Vec<u8>) {}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C-unwind" fn mod_dummy_restore_state(state: &mut State, saved: &[u8]) {}
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use gilrs::{Axis, Button, Event, EventType, Gilrs};
 
+use game_state::abi::ModAbi;
 use game_state::input::events::{DeviceId, InputEvent, JoyAxis, JoyButton};
 //use game_state::input::InputSource;
 use game_state::state::{InputAccess, State};
@@ -128,7 +129,12 @@ impl InputSource for GamepadInput {
 */
 
 #[no_mangle]
-pub extern "C" fn mod_gamepad_load(state: &mut State) {
+pub extern "C" fn mod_gamepad_abi() -> ModAbi {
+    ModAbi::current()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_gamepad_load(state: &mut State) {
   /*  let gamepad_input = GamepadInput::new();
     let gamepad_input = Box::new(gamepad_input) as Box<InputSource>;
     state.add_input_source(gamepad_input);
@@ -136,9 +142,9 @@ pub extern "C" fn mod_gamepad_load(state: &mut State) {
 }
 
 #[no_mangle]
-pub extern "C" fn mod_gamepad_update(state: &mut State, dt: &Duration) {}
+pub extern "C-unwind" fn mod_gamepad_update(state: &mut State, dt: &Duration) {}
 
 #[no_mangle]
-pub extern "C" fn mod_gamepad_unload(state: &mut State) {
+pub extern "C-unwind" fn mod_gamepad_unload(state: &mut State) {
     unimplemented!()
 }
@@ -1,13 +1,19 @@
 use std::time::Duration;
 
+use game_state::abi::{ModAbi, ModResult};
 use game_state::sdl2::video::Window;
-use game_state::state::{InputAccess, State, VariableAccess, WindowAccess, WorldAccess};
+use game_state::state::{
+    InputAccess, LogAccess, ProfilerAccess, State, VariableAccess, WindowAccess, WorldAccess,
+};
 use game_state::thing::{CameraFacet, Direction};
+use game_state::{debug, info};
 
 use game_state::sdl2::{
     event::Event as SdlEvent, keyboard::Keycode, mouse::MouseUtil, video::FullscreenType,
 };
 
+const LOG_TARGET: &str = "input";
+
 // this module's purpose is to turn input events into meaningful application input
 // this might include closing windows, keyboard presses, mouse drags
 // mapping user settings to keyboard and mouse bindings
@@ -23,21 +29,37 @@ fn grab_cursor(grab: bool, mouse: &MouseUtil) {
 }
 
 #[no_mangle]
-pub extern "C" fn mod_input_load(state: &mut State) {
+pub extern "C" fn mod_input_abi() -> ModAbi {
+    ModAbi::current()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_input_load_v2(state: &mut State) -> ModResult {
+    let mouse = match state.sdl_context {
+        Some(ref ctx) => ctx.mouse(),
+        None => return ModResult::error("no input to gather - running headless"),
+    };
+    if state.get_windows().is_empty() {
+        return ModResult::retry("waiting for a window");
+    }
     state.on_input_load();
 
-    let mouse = state.sdl_context.mouse();
     let mouse_grabbed = state.get_bool("mouse_grabbed").unwrap_or(true);
     grab_cursor(mouse_grabbed, &mouse);
+    ModResult::ok()
 }
 
 #[no_mangle]
-pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
-    let frame_events = state
-        .sdl_subsystems
-        .event_pump
-        .poll_iter()
-        .collect::<Vec<_>>();
+pub extern "C-unwind" fn mod_input_update(state: &mut State, dt: &Duration) {
+    // load() made sure we have SDL and a window
+    let frame_events = match state.sdl_subsystems {
+        Some(ref mut subsystems) => subsystems.event_pump.poll_iter().collect::<Vec<_>>(),
+        None => return,
+    };
+    let mouse = match state.sdl_context {
+        Some(ref ctx) => ctx.mouse(),
+        None => return,
+    };
 
     // TODO: wrap unsafe call in State, particularly WindowAccess
     let (sdlwin, _) = state.get_windows()[0].clone();
@@ -45,14 +67,15 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
     //
 
     let mut paused = state.get_bool("paused").unwrap_or(false);
-    let mouse = state.sdl_context.mouse();
     let mut mouse_grabbed = state.get_bool("mouse_grabbed").unwrap_or(true);
+    let logger = state.get_logger().clone();
+    let profiler = state.get_profiler().clone();
     let mut camera = &mut state.get_world().get_facets().cameras[0];
 
     for event in frame_events {
         match event {
             SdlEvent::Quit { .. } => {
-                println!("quitting...");
+                logger.info(LOG_TARGET, "quitting...");
                 std::process::exit(0);
             }
             SdlEvent::KeyDown {
@@ -61,7 +84,7 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
             } => match code {
                 Keycode::Escape => {
                     if paused {
-                        println!("user pressed 'Esc' : unpaused.");
+                        logger.info(LOG_TARGET, "user pressed 'Esc' : unpaused.");
                         paused = false;
 
                         // re-grab the cursor if we are unpausing and it was grabbed
@@ -69,7 +92,7 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
                             grab_cursor(true, &mouse);
                         }
                     } else {
-                        println!("user pressed 'Esc' : paused.");
+                        logger.info(LOG_TARGET, "user pressed 'Esc' : paused.");
                         camera.movement_dir = None;
                         paused = true;
 
@@ -82,7 +105,7 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
 
                 Keycode::Q => {
                     if paused {
-                        println!("user pressed 'q' while paused : hard exit.");
+                        logger.info(LOG_TARGET, "user pressed 'q' while paused : hard exit.");
                         std::process::exit(0);
                     }
                 }
@@ -110,10 +133,13 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
                 },
 
                 Keycode::Num9 => {
-                    println!("{}", camera.perspective.fovy());
+                    info!(logger, LOG_TARGET, "fovy {}", camera.perspective.fovy());
                 }
                 Keycode::Num0 => camera.perspective.set_fovy(camera.perspective.fovy() - 1.0),
 
+                // the host writes a trace of the profiler's history at the end of the frame
+                Keycode::F12 => profiler.request_dump(),
+
                 _ => {}
             },
             SdlEvent::KeyUp {
@@ -130,9 +156,13 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
             }
             SdlEvent::MouseMotion { xrel, yrel, .. } => {
                 if !paused {
-                    println!(
+                    debug!(
+                        logger,
+                        LOG_TARGET,
                         "pos {:?} pitch {}, yaw {}",
-                        camera.pos, camera.pitch, camera.yaw
+                        camera.pos,
+                        camera.pitch,
+                        camera.yaw
                     );
                     let sensitivity = 100.0;
                     let (dx, dy) = (xrel as f32, yrel as f32);
@@ -179,6 +209,6 @@ pub extern "C" fn mod_input_update(state: &mut State, dt: &Duration) {
 }
 
 #[no_mangle]
-pub extern "C" fn mod_input_unload(state: &mut State) {
+pub extern "C-unwind" fn mod_input_unload(state: &mut State) {
     state.on_input_unload();
 }
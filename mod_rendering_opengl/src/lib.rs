@@ -1,39 +1,65 @@
 use std::time::Duration;
 
-use game_state::winit::EventsLoop;
-
-use std::sync::{Arc, Mutex};
+use game_state::abi::{ModAbi, ModResult};
+use game_state::sdl2::video::Window;
+use game_state::state::{
+    GraphicsApi, LogAccess, ModelAccess, ProfilerAccess, RenderAccess, State, WindowAccess,
+};
 
 mod renderer;
-
-use game_state::state::{DrawMode, RenderAccess, State, WindowAccess};
-
 use renderer::opengl::OpenGLRenderer;
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_opengl_load(state: &mut State) {
-    let events_loop = Arc::new(Mutex::new(Some(EventsLoop::new())));
-    let windows = state
-        .get_window_builders()
-        .iter()
-        .map(|x| x.clone())
-        .collect::<Vec<_>>();
-
-    for w in windows {
-        state.add_renderer(Box::new(OpenGLRenderer::new(w, events_loop.clone())));
+pub extern "C" fn mod_rendering_opengl_abi() -> ModAbi {
+    ModAbi::current()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_rendering_opengl_load_v2(state: &mut State) -> ModResult {
+    if state.get_graphics_api() != GraphicsApi::OpenGL {
+        return ModResult::error(
+            "windows weren't created for OpenGL - run with --graphics opengl, \
+             or graphics = \"opengl\" in sg_engine.toml",
+        );
+    }
+    let windows = state.get_windows();
+
+    for (w, draw_mode) in windows {
+        let window = unsafe { Window::from_ref(w) };
+        let maybe_renderer = OpenGLRenderer::new(
+            window,
+            draw_mode,
+            state.get_logger().clone(),
+            state.get_profiler().clone(),
+        );
+
+        match maybe_renderer {
+            Ok(mut renderer) => {
+                for model in state.get_models().iter() {
+                    renderer.upload_model(model.clone());
+                }
+                state.add_renderer(Box::new(renderer));
+            }
+            Err(err) => {
+                // don't leave renderers for some of the windows behind
+                state.on_render_unload();
+                return ModResult::error(format!("Failed to load renderer. {}", err));
+            }
+        }
     }
 
     state.on_render_load();
+    ModResult::ok()
 }
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_opengl_update(state: &mut State, dt: &Duration) {
+pub extern "C-unwind" fn mod_rendering_opengl_update(state: &mut State, _dt: &Duration) {
     // queue each existing render layers for rendering
     state.push_render_layers();
     state.present_all();
 }
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_opengl_unload(state: &mut State) {
+pub extern "C-unwind" fn mod_rendering_opengl_unload(state: &mut State) {
     state.on_render_unload();
 }
@@ -1,94 +1,417 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::mem;
+use std::ptr;
+use std::sync::Arc;
+
 use gl;
 use gl::types::*;
 
-use glutin;
-use glutin::GlContext;
-
 use game_state;
+use game_state::image::RgbaImage;
+use game_state::logging::Logger;
+use game_state::model::{Indices, Model};
+use game_state::nalgebra::{Matrix4, Vector3};
+use game_state::profiler::Profiler;
+use game_state::sdl2::video::{GLContext, GLProfile, SwapInterval, Window};
+use game_state::state::DrawMode;
 use game_state::state::SceneGraph;
-use game_state::winit;
-use game_state::Renderer;
+use game_state::thing::CameraFacet;
+use game_state::tree::BreadthFirstIterator;
+use game_state::utils::fps;
+use game_state::{Identifyable, Identity, Renderer};
 
-use game_state::input;
+pub mod shaders;
+use self::shaders::Program;
 
-use std::sync::{Arc, Mutex};
+const LOG_TARGET: &str = "rendering_opengl";
 
-use game_state::thing::CameraFacet;
-use game_state::Identity;
+// position, normal, uv
+const FLOATS_PER_VERTEX: usize = 3 + 3 + 2;
 
-use std::collections::VecDeque;
+// ModelData holds the GL objects of a Model uploaded to this renderer's context - geometry,
+// indices and the color, normal and specular maps
+pub struct ModelData {
+    pub model: Arc<Model>,
+    vao: GLuint,
+    vertices: GLuint,
+    indices: GLuint,
+    index_count: GLsizei,
+    // UNSIGNED_SHORT or UNSIGNED_INT, as wide as the mesh's indices
+    index_type: GLenum,
+    diffuse_map: GLuint,
+    normal_map: GLuint,
+    specular_map: GLuint,
+}
 
+impl ModelData {
+    // the renderer's context must be current
+    unsafe fn delete(&self) {
+        gl::DeleteVertexArrays(1, &self.vao);
+        gl::DeleteBuffers(1, &self.vertices);
+        gl::DeleteBuffers(1, &self.indices);
+        let textures = [self.diffuse_map, self.normal_map, self.specular_map];
+        gl::DeleteTextures(textures.len() as GLsizei, textures.as_ptr());
+    }
+}
+
+// Upload an image as a new texture, the context must be current
+unsafe fn upload_texture(image: RgbaImage) -> GLuint {
+    let (width, height) = image.dimensions();
+    let pixels = image.into_raw();
+
+    let mut texture = 0;
+    gl::GenTextures(1, &mut texture);
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+    gl::TexParameteri(
+        gl::TEXTURE_2D,
+        gl::TEXTURE_WRAP_S,
+        gl::CLAMP_TO_EDGE as GLint,
+    );
+    gl::TexParameteri(
+        gl::TEXTURE_2D,
+        gl::TEXTURE_WRAP_T,
+        gl::CLAMP_TO_EDGE as GLint,
+    );
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as GLint,
+        width as GLsizei,
+        height as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_ptr() as *const _,
+    );
+    texture
+}
+
+///
+/// OpenGLRenderer - draws the queued render layers through an SDL GL context on its window
+///
+/// Draws the same image as the vulkano renderer: the same projection (flipped to Vulkan's
+/// y-down clip space), texture coordinates and draw modes. Every renderer has its own context,
+/// so models are uploaded to each renderer separately.
+///
 pub struct OpenGLRenderer {
     id: Identity,
-    gl_window: glutin::GlWindow,
-    el: glutin::EventsLoop,
+    // dropped in order: the GL objects, then their context, then the window
+    program: Program,
+    model_data: Vec<ModelData>,
+    context: GLContext,
+    window: Window,
     render_layer_queue: VecDeque<Arc<SceneGraph>>,
+    fps: fps::FPS,
+    logger: Logger,
+    profiler: Profiler,
 }
+
 impl OpenGLRenderer {
     pub fn new(
-        window: winit::WindowBuilder,
-        events_loop: Arc<Mutex<Option<winit::event::EventsLoop<()>>>>,
-    ) -> Self {
-        let context = glutin::ContextBuilder::new();
-        let el = glutin::EventsLoop::new();
-
-        let gl_window = glutin::GlWindow::new(window.clone(), context, &el).unwrap();
+        window: Window,
+        draw_mode: DrawMode,
+        logger: Logger,
+        profiler: Profiler,
+    ) -> Result<Self, Box<dyn Error>> {
+        let video = window.subsystem().clone();
+        let attr = video.gl_attr();
+        attr.set_context_profile(GLProfile::Core);
+        attr.set_context_version(3, 3);
 
-        unsafe {
-            gl_window.make_current().unwrap();
-        }
+        let context = window.gl_create_context()?;
+        window.gl_make_current(&context)?;
+        gl::load_with(|symbol| video.gl_get_proc_address(symbol) as *const _);
+        // don't block on vsync, once for each window
+        let _ = video.gl_set_swap_interval(SwapInterval::Immediate);
 
+        let program = Program::new()?;
         unsafe {
-            gl::load_with(|symbol| gl_window.get_proc_address(symbol) as *const _);
-            gl::ClearColor(0.0, 0.1, 0.0, 1.0);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LESS);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::PointSize(1.0);
+            match draw_mode {
+                DrawMode::Wireframe(line_width) => {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                    gl::LineWidth(line_width);
+                }
+                DrawMode::Points => gl::PolygonMode(gl::FRONT_AND_BACK, gl::POINT),
+                DrawMode::Textured => gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL),
+            }
         }
+        check_error("setting up the context")?;
 
-        OpenGLRenderer {
+        Ok(OpenGLRenderer {
             id: game_state::create_next_identity(),
-            gl_window: gl_window,
-            el,
+            program,
+            model_data: Vec::new(),
+            context,
+            window,
             render_layer_queue: VecDeque::new(),
+            fps: fps::FPS::new(),
+            logger,
+            profiler,
+        })
+    }
+
+    fn make_current(&self) -> bool {
+        match self.window.gl_make_current(&self.context) {
+            Ok(()) => true,
+            Err(err) => {
+                self.logger.error(
+                    LOG_TARGET,
+                    format!(
+                        "renderer {} unable to make its context current - {}",
+                        self.id, err
+                    ),
+                );
+                false
+            }
+        }
+    }
+
+    pub fn upload_model(&mut self, model: Arc<Model>) {
+        if !self.make_current() {
+            return;
+        }
+        self.upload_model_current(model);
+    }
+
+    // upload_model(), with the context already current
+    fn upload_model_current(&mut self, model: Arc<Model>) {
+        self.logger.debug(
+            LOG_TARGET,
+            format!("renderer {} uploading model {}", self.id, model.filename),
+        );
+        let mesh = &model.mesh;
+        let mut vertices = Vec::with_capacity(mesh.vertices.len() * FLOATS_PER_VERTEX);
+        for v in mesh.vertices.iter() {
+            vertices.extend_from_slice(&[
+                v.position.0,
+                v.position.1,
+                v.position.2,
+                v.normal.0,
+                v.normal.1,
+                v.normal.2,
+                v.uvw.0,
+                v.uvw.1,
+            ]);
+        }
+        let data = unsafe {
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            let mut buffers = [0; 2];
+            gl::GenBuffers(2, buffers.as_mut_ptr());
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffers[0]);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffers[1]);
+            let (index_bytes, index_data) = match &mesh.indices {
+                Indices::U16(indices) => (
+                    indices.len() * mem::size_of::<u16>(),
+                    indices.as_ptr() as *const _,
+                ),
+                Indices::U32(indices) => (
+                    indices.len() * mem::size_of::<u32>(),
+                    indices.as_ptr() as *const _,
+                ),
+            };
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                index_bytes as GLsizeiptr,
+                index_data,
+                gl::STATIC_DRAW,
+            );
+
+            let stride = (FLOATS_PER_VERTEX * mem::size_of::<f32>()) as GLsizei;
+            for (location, (offset, size)) in [(0, 3), (3, 3), (6, 2)].iter().enumerate() {
+                gl::EnableVertexAttribArray(location as GLuint);
+                gl::VertexAttribPointer(
+                    location as GLuint,
+                    *size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (offset * mem::size_of::<f32>()) as *const _,
+                );
+            }
+            gl::BindVertexArray(0);
+
+            // materials without a normal or specular map get a neutral texel for it
+            let material = &model.material;
+            let diffuse_map = upload_texture(material.unlit_texture());
+            let normal_map = upload_texture(material.normal_texture());
+            let specular_map = upload_texture(material.specular_texture());
+
+            ModelData {
+                model: model.clone(),
+                vao,
+                vertices: buffers[0],
+                indices: buffers[1],
+                index_count: mesh.indices.len() as GLsizei,
+                index_type: match mesh.indices {
+                    Indices::U16(_) => gl::UNSIGNED_SHORT,
+                    Indices::U32(_) => gl::UNSIGNED_INT,
+                },
+                diffuse_map,
+                normal_map,
+                specular_map,
+            }
+        };
+
+        if let Err(err) = check_error("uploading a model") {
+            self.logger.error(
+                LOG_TARGET,
+                format!("renderer {} - {} {}", self.id, model.filename, err),
+            );
         }
+        self.model_data.push(data);
     }
 
     fn render(&mut self, camera: &CameraFacet) {
+        if !self.make_current() {
+            self.render_layer_queue.clear();
+            return;
+        }
+        let (width, height) = self.window.drawable_size();
+
+        // the projection of the vulkano renderer, flipped into Vulkan's y-down clip space so
+        // both renderers show the same image
+        let proj_mat = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, -1.0, 1.0))
+            * Matrix4::new_perspective(
+                width as f32 / height.max(1) as f32,
+                ::std::f32::consts::FRAC_PI_2,
+                0.01,
+                100.0,
+            );
+        let view = camera.view;
+
         unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::ClearDepth(1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::UseProgram(self.program.id);
+            gl::UniformMatrix4fv(
+                self.program.proj,
+                1,
+                gl::FALSE,
+                proj_mat.as_slice().as_ptr(),
+            );
+            gl::Uniform1i(self.program.tex, 0);
+            gl::Uniform1i(self.program.normal_tex, 1);
+            gl::Uniform1i(self.program.specular_tex, 2);
         }
 
-        loop {
-            match self.render_layer_queue.pop_front() {
-                Some(layer) => {
+        let traversal = self.profiler.scope("scene traversal");
+        while let Some(next_layer) = self.render_layer_queue.pop_front() {
+            next_layer.update_world_matrices();
+            let iterator = BreadthFirstIterator::new(next_layer.root.clone());
+            for (_node_id, rc) in iterator {
+                let node = &rc.borrow();
+                let model = match node.data.model.clone() {
+                    Some(model) => model,
+                    None => continue,
+                };
+                if !self.model_data.iter().any(|md| md.model.id == model.id) {
+                    self.upload_model_current(model.clone());
+                }
+                let md = match self.model_data.iter().find(|md| md.model.id == model.id) {
+                    Some(md) => md,
+                    None => continue,
+                };
 
-                    //TODO : gather needed resources from the layer and render
+                let transform_mat = node.data.world() * model.model_mat;
+                let model_view = view * transform_mat;
 
+                unsafe {
+                    gl::UniformMatrix4fv(
+                        self.program.model_mat,
+                        1,
+                        gl::FALSE,
+                        model_view.as_slice().as_ptr(),
+                    );
+                    let maps = [md.diffuse_map, md.normal_map, md.specular_map];
+                    for (unit, map) in maps.iter().enumerate() {
+                        gl::ActiveTexture(gl::TEXTURE0 + unit as GLenum);
+                        gl::BindTexture(gl::TEXTURE_2D, *map);
+                    }
+                    gl::BindVertexArray(md.vao);
+                    gl::DrawElements(gl::TRIANGLES, md.index_count, md.index_type, ptr::null());
                 }
-                None => break,
             }
         }
+        unsafe { gl::BindVertexArray(0) };
+        drop(traversal);
+
+        if let Err(err) = check_error("drawing") {
+            self.logger.error(
+                LOG_TARGET,
+                format!(
+                    "OpenGLRenderer::render() frame {} - {}",
+                    self.fps.count(),
+                    err
+                ),
+            );
+        }
+        self.window.gl_swap_window();
+        self.fps.update();
+    }
+
+    fn delete_models(&mut self) {
+        if self.model_data.is_empty() || !self.make_current() {
+            return;
+        }
+        for md in self.model_data.drain(..) {
+            unsafe { md.delete() };
+        }
+    }
 
-        self.gl_window
-            .swap_buffers()
-            .expect("failed to swap buffers");
+    #[allow(dead_code)]
+    fn fps(&self) -> f32 {
+        self.fps.get()
     }
 }
 
-impl game_state::Identifyable for OpenGLRenderer {
-    fn identify(&self) -> Identity {
-        self.id
+fn check_error(doing: &str) -> Result<(), String> {
+    let mut errors = Vec::new();
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+        errors.push(format!("0x{:x}", error));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("GL errors {} while {}", errors.join(", "), doing))
     }
 }
 
-impl game_state::input::InputSource for OpenGLRenderer {
-    fn get_input_events(&mut self) -> VecDeque<input::events::InputEvent> {
-        VecDeque::new()
+impl Identifyable for OpenGLRenderer {
+    fn identify(&self) -> Identity {
+        self.id
     }
 }
 
 impl Renderer for OpenGLRenderer {
     fn load(&mut self) {}
 
-    fn unload(&mut self) {}
+    fn unload(&mut self) {
+        self.delete_models();
+        self.render_layer_queue.clear();
+    }
 
     fn queue_render_layer(&mut self, layer: Arc<SceneGraph>) {
         self.render_layer_queue.push_back(layer);
@@ -97,4 +420,30 @@ impl Renderer for OpenGLRenderer {
     fn present(&mut self, camera: &CameraFacet) {
         self.render(camera);
     }
+
+    fn model_updated(&mut self, model: &Arc<Model>) {
+        let idx = match self
+            .model_data
+            .iter()
+            .position(|md| md.model.id == model.id)
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+        if !self.make_current() {
+            return;
+        }
+        let old = self.model_data.remove(idx);
+        unsafe { old.delete() };
+        self.upload_model_current(model.clone());
+    }
+}
+
+impl Drop for OpenGLRenderer {
+    fn drop(&mut self) {
+        self.delete_models();
+        // the program is deleted next, with its context current
+        let _ = self.make_current();
+        self.logger.debug(LOG_TARGET, "OpenGLRenderer drop");
+    }
 }
@@ -0,0 +1,188 @@
+use std::error::Error;
+use std::ffi::CString;
+use std::ptr;
+
+use gl;
+use gl::types::*;
+
+// GLSL 3.30 versions of assets/shaders/vs.glsl and fs.glsl - the vulkan flavored sources use
+// push constants and descriptor sets, which OpenGL doesn't have
+pub const VERTEX_SHADER: &str = r#"
+#version 330 core
+
+uniform mat4 proj;
+uniform mat4 model_mat;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+
+out vec3 v_normal;
+out vec2 v_uv;
+out vec3 v_position;
+
+void main() {
+    vec4 view_position = model_mat * vec4(position, 1.0);
+    v_normal = transpose(inverse(mat3(model_mat))) * normal;
+    v_position = view_position.xyz;
+    gl_Position = proj * view_position;
+    v_uv = uv;
+}
+"#;
+
+pub const FRAGMENT_SHADER: &str = r#"
+#version 330 core
+
+uniform sampler2D tex;
+uniform sampler2D normal_tex;
+uniform sampler2D specular_tex;
+
+in vec3 v_normal;
+in vec2 v_uv;
+in vec3 v_position;
+
+out vec4 f_color;
+
+const vec3 LIGHT = vec3(0.5, 0.5, 0.5);
+
+float brightness(vec3 normal) {
+    return mix(0.7, 1.0, max(dot(normal, normalize(LIGHT)), 0.0));
+}
+
+vec3 mapped_normal(vec3 normal) {
+    vec3 mapped = (texture(normal_tex, v_uv).xyz * 255.0 - 128.0) / 127.0;
+    vec3 dp1 = dFdx(v_position);
+    vec3 dp2 = dFdy(v_position);
+    vec2 duv1 = dFdx(v_uv);
+    vec2 duv2 = dFdy(v_uv);
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if (scale == 0.0) {
+        return normal;
+    }
+    scale = inversesqrt(scale);
+    return normalize(mat3(tangent * scale, bitangent * scale, normal) * mapped);
+}
+
+void main() {
+    vec3 normal = normalize(v_normal);
+    vec3 bumped = mapped_normal(normal);
+    vec4 color = texture(tex, v_uv);
+    float relief = brightness(bumped) / brightness(normal);
+
+    vec4 specular = texture(specular_tex, v_uv);
+    vec3 half_vector = normalize(normalize(LIGHT) + normalize(-v_position));
+    float exponent = max(specular.a * 1000.0, 1.0);
+    vec3 highlight = specular.rgb * pow(max(dot(bumped, half_vector), 0.0), exponent);
+
+    f_color = vec4(color.rgb * relief + highlight, color.a);
+}
+"#;
+
+///
+/// Program - a linked vertex and fragment shader, with the locations of its uniforms
+///
+pub struct Program {
+    pub id: GLuint,
+    pub proj: GLint,
+    pub model_mat: GLint,
+    pub tex: GLint,
+    pub normal_tex: GLint,
+    pub specular_tex: GLint,
+}
+
+impl Program {
+    ///
+    /// Compile and link the built-in shaders. The GL context must be current.
+    ///
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let vertex = compile(gl::VERTEX_SHADER, VERTEX_SHADER)?;
+        let fragment = match compile(gl::FRAGMENT_SHADER, FRAGMENT_SHADER) {
+            Ok(fragment) => fragment,
+            Err(err) => {
+                unsafe { gl::DeleteShader(vertex) };
+                return Err(err);
+            }
+        };
+        let id = unsafe {
+            let id = gl::CreateProgram();
+            gl::AttachShader(id, vertex);
+            gl::AttachShader(id, fragment);
+            gl::LinkProgram(id);
+            gl::DeleteShader(vertex);
+            gl::DeleteShader(fragment);
+
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut status);
+            if status != gl::TRUE as GLint {
+                let mut len = 0;
+                gl::GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut len);
+                let mut log = vec![0u8; len.max(1) as usize];
+                gl::GetProgramInfoLog(id, len, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+                gl::DeleteProgram(id);
+                return Err(format!("unable to link shaders - {}", info_log(&log)).into());
+            }
+            id
+        };
+        Ok(Program {
+            id,
+            proj: uniform_location(id, "proj"),
+            model_mat: uniform_location(id, "model_mat"),
+            tex: uniform_location(id, "tex"),
+            normal_tex: uniform_location(id, "normal_tex"),
+            specular_tex: uniform_location(id, "specular_tex"),
+        })
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.id) };
+    }
+}
+
+fn compile(kind: GLenum, source: &str) -> Result<GLuint, Box<dyn Error>> {
+    let source = CString::new(source)?;
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        gl::ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+
+        let mut status = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+        if status != gl::TRUE as GLint {
+            let mut len = 0;
+            gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+            let mut log = vec![0u8; len.max(1) as usize];
+            gl::GetShaderInfoLog(
+                shader,
+                len,
+                ptr::null_mut(),
+                log.as_mut_ptr() as *mut GLchar,
+            );
+            gl::DeleteShader(shader);
+            let stage = if kind == gl::VERTEX_SHADER {
+                "vertex"
+            } else {
+                "fragment"
+            };
+            return Err(format!("unable to compile {} shader - {}", stage, info_log(&log)).into());
+        }
+        Ok(shader)
+    }
+}
+
+fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let name = CString::new(name).expect("uniform names have no nul bytes");
+    unsafe { gl::GetUniformLocation(program, name.as_ptr()) }
+}
+
+fn info_log(log: &[u8]) -> String {
+    String::from_utf8_lossy(log)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use game_state::abi::{ModAbi, ModResult};
+use game_state::sdl2::video::Window;
+use game_state::state::{
+    GraphicsApi, LogAccess, ProfilerAccess, RenderAccess, State, WindowAccess,
+};
+
+mod renderer;
+use renderer::software::SoftwareRenderer;
+
+#[no_mangle]
+pub extern "C" fn mod_rendering_software_abi() -> ModAbi {
+    ModAbi::current()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_rendering_software_load_v2(state: &mut State) -> ModResult {
+    if state.get_graphics_api() != GraphicsApi::Software {
+        return ModResult::error(
+            "windows weren't created for software rendering - run with --graphics software, \
+             or graphics = \"software\" in sg_engine.toml",
+        );
+    }
+    let windows = state.get_windows();
+
+    for (w, draw_mode) in windows {
+        let window = unsafe { Window::from_ref(w) };
+        let maybe_renderer = SoftwareRenderer::new(
+            window,
+            draw_mode,
+            state.get_logger().clone(),
+            state.get_profiler().clone(),
+        );
+
+        match maybe_renderer {
+            Ok(renderer) => state.add_renderer(Box::new(renderer)),
+            Err(err) => {
+                // don't leave renderers for some of the windows behind
+                state.on_render_unload();
+                return ModResult::error(format!("Failed to load renderer. {}", err));
+            }
+        }
+    }
+
+    state.on_render_load();
+    ModResult::ok()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_rendering_software_update(state: &mut State, _dt: &Duration) {
+    // queue each existing render layers for rendering
+    state.push_render_layers();
+    state.present_all();
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_rendering_software_unload(state: &mut State) {
+    state.on_render_unload();
+}
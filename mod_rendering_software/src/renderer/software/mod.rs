@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use game_state;
+use game_state::image::RgbaImage;
+use game_state::logging::Logger;
+use game_state::model::Model;
+use game_state::profiler::Profiler;
+use game_state::sdl2::pixels::PixelFormatEnum;
+use game_state::sdl2::surface::{Surface, SurfaceRef};
+use game_state::sdl2::video::Window;
+use game_state::software::raster::Framebuffer;
+use game_state::software::Rasterizer;
+use game_state::state::DrawMode;
+use game_state::state::SceneGraph;
+use game_state::thing::CameraFacet;
+use game_state::utils::fps;
+use game_state::{Identifyable, Identity, Renderer};
+
+const LOG_TARGET: &str = "rendering_software";
+
+///
+/// SoftwareRenderer - rasterizes the queued render layers on the CPU, and copies each frame to
+/// its window's surface. For machines without a usable GPU.
+///
+/// The drawing itself is `game_state::software::Rasterizer`, shared with the offscreen renderer
+/// used by tests.
+///
+pub struct SoftwareRenderer {
+    id: Identity,
+    window: Window,
+    rasterizer: Rasterizer,
+    fps: fps::FPS,
+    logger: Logger,
+    profiler: Profiler,
+}
+
+impl SoftwareRenderer {
+    pub fn new(
+        window: Window,
+        draw_mode: DrawMode,
+        logger: Logger,
+        profiler: Profiler,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = window.size();
+        if width == 0 || height == 0 {
+            return Err(format!("window {:?} has no area", window.title()).into());
+        }
+        Ok(SoftwareRenderer {
+            id: game_state::create_next_identity(),
+            window,
+            rasterizer: Rasterizer::new(width, height, draw_mode),
+            fps: fps::FPS::new(),
+            logger,
+            profiler,
+        })
+    }
+
+    fn render(&mut self, camera: &CameraFacet) {
+        let (width, height) = self.window.size();
+        self.rasterizer.resize(width, height);
+
+        let traversal = self.profiler.scope("scene traversal");
+        self.rasterizer.draw(camera);
+        drop(traversal);
+
+        if width > 0 && height > 0 {
+            let framebuffer = self.rasterizer.framebuffer_mut();
+            if let Err(err) = Self::blit_to_window(&self.window, framebuffer) {
+                self.logger.error(
+                    LOG_TARGET,
+                    format!("renderer {} unable to present - {}", self.id, err),
+                );
+            }
+        }
+        self.fps.update();
+    }
+
+    // Copy the framebuffer to the window's surface
+    fn blit_to_window(window: &Window, framebuffer: &mut Framebuffer) -> Result<(), String> {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let pixels = framebuffer.pixels_mut();
+        // Safety: the framebuffer's u32 pixels, viewed as bytes for SDL - ARGB8888 is a
+        // packed, native endian format, just like them
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixels.len() * 4)
+        };
+        let frame = Surface::from_data(bytes, width, height, width * 4, PixelFormatEnum::ARGB8888)?;
+
+        // Window::surface() wants the EventPump, to be sure it runs on the main thread - which
+        // the renderer already does, as mods using RenderAccess are updated there
+        unsafe {
+            let raw = game_state::sdl2::sys::SDL_GetWindowSurface(window.raw());
+            if raw.is_null() {
+                return Err(game_state::sdl2::get_error());
+            }
+            frame.blit(None, SurfaceRef::from_ll_mut(raw), None)?;
+            if game_state::sdl2::sys::SDL_UpdateWindowSurface(window.raw()) != 0 {
+                return Err(game_state::sdl2::get_error());
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn fps(&self) -> f32 {
+        self.fps.get()
+    }
+}
+
+impl Identifyable for SoftwareRenderer {
+    fn identify(&self) -> Identity {
+        self.id
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn load(&mut self) {}
+
+    fn unload(&mut self) {
+        self.rasterizer.clear();
+    }
+
+    fn queue_render_layer(&mut self, layer: Arc<SceneGraph>) {
+        self.rasterizer.queue_render_layer(layer);
+    }
+
+    fn present(&mut self, camera: &CameraFacet) {
+        self.render(camera);
+    }
+
+    fn model_updated(&mut self, model: &Arc<Model>) {
+        self.rasterizer.model_updated(model);
+    }
+
+    fn capture_frame(&mut self) -> Option<RgbaImage> {
+        Some(self.rasterizer.capture())
+    }
+}
+
+impl Drop for SoftwareRenderer {
+    fn drop(&mut self) {
+        self.logger.debug(LOG_TARGET, "SoftwareRenderer drop");
+    }
+}
@@ -1,2 +1,31 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// The built-in shaders as SPIR-V, the same sources `vs` and `fs` are built from - shaders
+// compiled at runtime are checked against their interface
 fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let mut compiler = shaderc::Compiler::new().expect("unable to create shader compiler");
+    let shaders = [
+        (
+            "../assets/shaders/vs.glsl",
+            shaderc::ShaderKind::Vertex,
+            "vs.spv",
+        ),
+        (
+            "../assets/shaders/fs.glsl",
+            shaderc::ShaderKind::Fragment,
+            "fs.spv",
+        ),
+    ];
+    for (source, kind, spirv) in shaders.iter() {
+        println!("cargo:rerun-if-changed={}", source);
+        let glsl = fs::read_to_string(source).unwrap_or_else(|err| panic!("{}: {}", source, err));
+        let artifact = compiler
+            .compile_into_spirv(&glsl, *kind, source, "main", None)
+            .unwrap_or_else(|err| panic!("{}: {}", source, err));
+        fs::write(out_dir.join(spirv), artifact.as_binary_u8())
+            .unwrap_or_else(|err| panic!("{}: {}", spirv, err));
+    }
 }
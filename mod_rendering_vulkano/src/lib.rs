@@ -1,14 +1,26 @@
 use std::time::Duration;
 
+use game_state::abi::{ModAbi, ModResult};
 use game_state::sdl2::video::Window;
-use game_state::state::ModelAccess;
-use game_state::state::{RenderAccess, State, WindowAccess};
+use game_state::state::{GraphicsApi, ModelAccess};
+use game_state::state::{LogAccess, ProfilerAccess, RenderAccess, State, WindowAccess};
 
 mod renderer;
 use renderer::vulkano::VulkanoRenderer;
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_vulkano_load(state: &mut State) {
+pub extern "C" fn mod_rendering_vulkano_abi() -> ModAbi {
+    ModAbi::current()
+}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_rendering_vulkano_load_v2(state: &mut State) -> ModResult {
+    if state.get_graphics_api() != GraphicsApi::Vulkan {
+        return ModResult::error(
+            "windows weren't created for Vulkan - run with --graphics vulkan, \
+             or graphics = \"vulkan\" in sg_engine.toml",
+        );
+    }
     let windows = state.get_windows();
 
     for (w, draw_mode) in windows {
@@ -18,7 +30,13 @@ pub extern "C" fn mod_rendering_vulkano_load(state: &mut State) {
             let c = unsafe { &*sdlwin.raw() };
             crate::renderer::vulkano::vulkano_sdl2::WinPtr { raw: c as *const _ }
         };
-        let maybe_renderer = VulkanoRenderer::new(win_ptr, draw_mode, state.get_models());
+        let maybe_renderer = VulkanoRenderer::new(
+            win_ptr,
+            draw_mode,
+            state.get_models(),
+            state.get_logger().clone(),
+            state.get_profiler().clone(),
+        );
 
         match maybe_renderer {
             Ok(mut renderer) => {
@@ -27,21 +45,26 @@ pub extern "C" fn mod_rendering_vulkano_load(state: &mut State) {
                 }
                 state.add_renderer(Box::new(renderer));
             }
-            Err(err) => println!("Failed to load renderer. {}", err),
+            Err(err) => {
+                // don't leave renderers for some of the windows behind
+                state.on_render_unload();
+                return ModResult::error(format!("Failed to load renderer. {}", err));
+            }
         }
     }
 
     state.on_render_load();
+    ModResult::ok()
 }
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_vulkano_update(state: &mut State, _dt: &Duration) {
+pub extern "C-unwind" fn mod_rendering_vulkano_update(state: &mut State, _dt: &Duration) {
     // queue each existing render layers for rendering
     state.push_render_layers();
     state.present_all();
 }
 
 #[no_mangle]
-pub extern "C" fn mod_rendering_vulkano_unload(state: &mut State) {
+pub extern "C-unwind" fn mod_rendering_vulkano_unload(state: &mut State) {
     state.on_render_unload();
 }
@@ -1,2 +1 @@
 pub mod vulkano;
-
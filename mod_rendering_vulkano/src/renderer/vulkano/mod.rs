@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::error::Error;
 use std::mem;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -9,8 +10,10 @@ use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
 use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
 use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
 use vulkano::device::{Device, Queue};
+use vulkano::format::{FormatDesc, R8G8B8A8Srgb, R8G8B8A8Unorm};
 use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
 use vulkano::image::attachment::AttachmentImage;
+use vulkano::image::immutable::ImmutableImageInitialization;
 use vulkano::image::{
     ImageAccess, ImageLayout, ImageUsage, ImageViewAccess, ImmutableImage, MipmapsCount,
     SwapchainImage,
@@ -25,7 +28,10 @@ use vulkano::sync::now;
 use vulkano::sync::GpuFuture;
 
 use game_state;
-use game_state::model::Model;
+use game_state::image::RgbaImage;
+use game_state::logging::Logger;
+use game_state::model::{Indices, Model};
+use game_state::profiler::Profiler;
 use game_state::state::DrawMode;
 use game_state::state::SceneGraph;
 use game_state::thing::CameraFacet;
@@ -35,26 +41,20 @@ use game_state::{Identifyable, Identity, Renderer};
 
 use game_state::nalgebra::Matrix4;
 
+pub mod shaders;
+pub mod spirv;
 pub mod vertex;
+use self::shaders::{ShaderPair, ShaderWatcher, FRAGMENT_SHADER_PATH, VERTEX_SHADER_PATH};
 use self::vertex::Vertex;
 
+const LOG_TARGET: &str = "rendering_vulkano";
+
 pub mod vulkano_sdl2;
 
 use vulkano_sdl2::WinPtr;
 
-//TODO: compile these elsewhere, at build time?
-// These shaders are a PITA, generated by build.rs, dependent on OUT_DIR... *barf
-// More importantly, these are actually compiled SPIR-V, ignore the glsl file extension on them
-/*
-use vulkano::pipeline::shader::{
-    GraphicsShaderType, ShaderInterfaceDef, ShaderInterfaceDefEntry, ShaderModule,
-};
-fn load_shader(device: Arc<Device>, path: PathBuf) -> Result<Arc<ShaderModule>, Box<Error>> {
-    let mut f = File::open(&path)?;
-    let mut v = vec![];
-    f.read_to_end(&mut v)?;
-    Ok(unsafe { ShaderModule::new(device, &v) }?)
-}*/
+// The shaders built into the mod. They also provide the interface (inputs, outputs, layout)
+// that shaders compiled at runtime from the same sources are bound with - see `shaders`.
 mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
@@ -73,17 +73,28 @@ mod fs {
 pub struct ModelData {
     pub model: Arc<Model>,
     pub vertices: Arc<CpuAccessibleBuffer<[Vertex]>>,
-    pub indices: Arc<CpuAccessibleBuffer<[u16]>>,
+    pub indices: IndexBuffer,
     pub diffuse_map: Arc<CpuAccessibleBuffer<[[u8; 4]]>>,
-    pub material_data: MaterialRenderData<vulkano::format::R8G8B8A8Srgb>,
+    pub normal_map: Arc<CpuAccessibleBuffer<[[u8; 4]]>>,
+    pub specular_map: Arc<CpuAccessibleBuffer<[[u8; 4]]>>,
+    pub material_data: MaterialRenderData<R8G8B8A8Srgb>,
+}
+
+// IndexBuffer holds a mesh's indices at the width the mesh has them
+pub enum IndexBuffer {
+    U16(Arc<CpuAccessibleBuffer<[u16]>>),
+    U32(Arc<CpuAccessibleBuffer<[u32]>>),
 }
 
 // MaterialData holds the Vulkano handles to GPU images - `init` and `read` here alias the same
 // image, however init is used to write the data, while read is used to read
 // the descriptor_set is used to bind on a per-model basis during traversal of the scene graph
+// the normal and specular maps hold data rather than colors, so they aren't sRGB
 pub struct MaterialRenderData<F> {
     pub read: Arc<ImmutableImage<F>>,
     pub init: Arc<dyn ImageAccess>,
+    pub normal_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
+    pub specular_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
     pub descriptor_set: Arc<dyn DescriptorSet + Send + Sync>,
 }
 
@@ -91,11 +102,15 @@ impl<F> MaterialRenderData<F> {
     pub fn new(
         read: Arc<ImmutableImage<F>>,
         init: Arc<dyn ImageAccess>,
+        normal_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
+        specular_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
         descriptor_set: Arc<dyn DescriptorSet + Send + Sync>,
     ) -> Self {
         MaterialRenderData {
             read,
             init,
+            normal_map,
+            specular_map,
             descriptor_set,
         }
     }
@@ -119,6 +134,8 @@ pub struct VulkanoRenderer {
     swapchain: Arc<Swapchain<WinPtr>>,
     images: Vec<Arc<SwapchainImage<WinPtr>>>,
     pipeline: Arc<ThisPipelineType>,
+    draw_mode: DrawMode,
+    shader_watcher: ShaderWatcher,
     framebuffers: Vec<ThisFramebufferType>,
     fps: fps::FPS,
 
@@ -137,6 +154,9 @@ pub struct VulkanoRenderer {
     previous_frame_end: Box<dyn GpuFuture>,
     recreate_swapchain: bool,
     dynamic_state: DynamicState,
+
+    logger: Logger,
+    profiler: Profiler,
 }
 
 impl VulkanoRenderer {
@@ -190,11 +210,14 @@ impl VulkanoRenderer {
         Ok(swapchain)
     }
 
+    // bindings 0 to 3 of set 0: the color texture, the uniforms, the normal and specular maps
     fn create_descriptor_set(
         device: Arc<Device>,
         uniform_buffer: Arc<CpuAccessibleBuffer<vs::ty::Data>>,
         pipeline: Arc<ThisPipelineType>,
-        texture: Arc<ImmutableImage<vulkano::format::R8G8B8A8Srgb>>,
+        texture: Arc<ImmutableImage<R8G8B8A8Srgb>>,
+        normal_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
+        specular_map: Arc<ImmutableImage<R8G8B8A8Unorm>>,
     ) -> Arc<dyn DescriptorSet + Send + Sync> {
         let sampler = vulkano::sampler::Sampler::new(
             device,
@@ -213,16 +236,87 @@ impl VulkanoRenderer {
 
         let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
         let ds = PersistentDescriptorSet::start(layout.clone())
-            .add_sampled_image(texture, sampler)
+            .add_sampled_image(texture, sampler.clone())
             .expect("error loading texture")
             .add_buffer(uniform_buffer)
             .expect("error adding uniform buffer")
+            .add_sampled_image(normal_map, sampler.clone())
+            .expect("error loading normal map")
+            .add_sampled_image(specular_map, sampler)
+            .expect("error loading specular map")
             .build()
             .unwrap();
 
         Arc::new(ds) as Arc<dyn DescriptorSet + Send + Sync>
     }
 
+    fn create_pipeline(
+        device: Arc<Device>,
+        renderpass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        draw_mode: DrawMode,
+        shaders: &ShaderPair,
+    ) -> Result<Arc<ThisPipelineType>, Box<dyn Error>> {
+        let p = GraphicsPipeline::start()
+            .vertex_input_single_buffer()
+            .polygon_mode_fill()
+            .depth_clamp(true)
+            .cull_mode_front()
+            .front_face_counter_clockwise()
+            .vertex_shader(shaders.vertex_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(shaders.fragment_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .blend_alpha_blending();
+
+        let p = match draw_mode {
+            DrawMode::Wireframe(line_width) => p.line_width(line_width).polygon_mode_line(),
+            DrawMode::Points => p.polygon_mode_point(),
+
+            // add more variants here, but for now we default to Textured
+            _ => p.polygon_mode_fill(),
+        };
+        let p = p
+            .render_pass(Subpass::from(renderpass, 0).ok_or("no subpass 0 in the renderpass")?)
+            .build(device)?;
+
+        Ok(Arc::new(p))
+    }
+
+    ///
+    /// Compile the shader sources again, and rebuild the pipeline with them. On failure the
+    /// error is logged, and the current pipeline is kept.
+    ///
+    pub fn reload_shaders(&mut self) {
+        let reloaded = ShaderPair::compile(
+            self.device.clone(),
+            Path::new(VERTEX_SHADER_PATH),
+            Path::new(FRAGMENT_SHADER_PATH),
+        )
+        .and_then(|shaders| {
+            Self::create_pipeline(
+                self.device.clone(),
+                self.renderpass.clone(),
+                self.draw_mode,
+                &shaders,
+            )
+        });
+        match reloaded {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.logger
+                    .info(LOG_TARGET, format!("renderer {} reloaded shaders", self.id));
+            }
+            Err(err) => self.logger.error(
+                LOG_TARGET,
+                format!(
+                    "renderer {} unable to reload shaders, keeping the last good ones - {}",
+                    self.id, err
+                ),
+            ),
+        }
+    }
+
     fn create_framebuffers(
         width: u32,
         height: u32,
@@ -250,6 +344,8 @@ impl VulkanoRenderer {
         win_ptr: WinPtr,
         draw_mode: DrawMode,
         models: Vec<Arc<Model>>,
+        logger: Logger,
+        profiler: Profiler,
     ) -> Result<Self, Box<dyn Error>> {
         let instance = {
             let extensions = vulkano_sdl2::required_extensions(win_ptr).unwrap();
@@ -258,8 +354,9 @@ impl VulkanoRenderer {
                 .expect("Failed to create Vulkan instance. ")
         };
 
-        let debug_callback = DebugCallback::errors_and_warnings(&instance, |msg| {
-            println!("Debug callback: {:?}", msg.description);
+        let debug_logger = logger.clone();
+        let debug_callback = DebugCallback::errors_and_warnings(&instance, move |msg| {
+            debug_logger.warn(LOG_TARGET, format!("Debug callback: {:?}", msg.description));
         })
         .ok();
 
@@ -296,8 +393,28 @@ impl VulkanoRenderer {
             Self::create_swapchain(surface.clone(), device.clone(), queue.clone(), physical)?;
 
         // TODO: as part of asset_loader, we should be loading all the shaders we expect to use in a scene
-        let vs = vs::Shader::load(device.clone()).expect("failed to create vs shader module");
-        let fs = fs::Shader::load(device.clone()).expect("failed to create fs shader module");
+        // prefer the sources on disk, which may be newer than the shaders built into the mod
+        let shaders = match ShaderPair::compile(
+            device.clone(),
+            Path::new(VERTEX_SHADER_PATH),
+            Path::new(FRAGMENT_SHADER_PATH),
+        ) {
+            Ok(shaders) => shaders,
+            Err(err) => {
+                logger.warn(
+                    LOG_TARGET,
+                    format!(
+                        "using built-in shaders, unable to compile sources - {}",
+                        err
+                    ),
+                );
+                ShaderPair::built_in(device.clone())?
+            }
+        };
+        let shader_watcher = ShaderWatcher::new(&[
+            Path::new(VERTEX_SHADER_PATH),
+            Path::new(FRAGMENT_SHADER_PATH),
+        ]);
 
         // ----------------------------------
         // Uniform buffer
@@ -358,7 +475,7 @@ impl VulkanoRenderer {
         )
         .unwrap();
 
-        let renderpass = Arc::new(renderpass); //as Arc<RenderPassAbstract + Send + Sync>;
+        let renderpass = Arc::new(renderpass) as Arc<dyn RenderPassAbstract + Send + Sync>;
         let depth_buffer = Arc::new(depth_buffer); //
         let dimensions = ImageAccess::dimensions(&images[0]);
         let framebuffers = Self::create_framebuffers(
@@ -369,37 +486,8 @@ impl VulkanoRenderer {
             depth_buffer.clone(),
         );
 
-        let p = GraphicsPipeline::start()
-            .vertex_input_single_buffer()
-            .polygon_mode_fill()
-            .depth_clamp(true)
-            .cull_mode_front()
-            .front_face_counter_clockwise()
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs.main_entry_point(), ())
-            .depth_stencil_simple_depth()
-            .blend_alpha_blending();
-
-        let p = match draw_mode {
-            DrawMode::Wireframe(line_width) => p.line_width(line_width).polygon_mode_line(),
-            DrawMode::Points => p.polygon_mode_point(),
-
-            // add more variants here, but for now we default to Textured
-            _ => p.polygon_mode_fill(),
-        };
-        let p = p
-            .render_pass(
-                Subpass::from(
-                    renderpass.clone() as Arc<dyn RenderPassAbstract + Send + Sync>,
-                    0,
-                )
-                .unwrap(),
-            )
-            .build(device.clone())?;
-
-        let pipeline = Arc::new(p);
+        let pipeline =
+            Self::create_pipeline(device.clone(), renderpass.clone(), draw_mode, &shaders)?;
 
         let previous_frame_end = Box::new(now(device.clone())) as Box<dyn GpuFuture>;
         let instance = instance.clone();
@@ -413,12 +501,14 @@ impl VulkanoRenderer {
             swapchain,
             images,
             pipeline,
+            draw_mode,
+            shader_watcher,
             depth_buffer,
             framebuffers,
             uniform_buffer,
             debug_callback,
             previous_frame_end,
-            renderpass: renderpass as Arc<dyn RenderPassAbstract + Send + Sync>,
+            renderpass,
             recreate_swapchain: false, // flag indicating to rebuild the swapchain on the next frame
             model_data: Vec::with_capacity(models.len()),
             render_layer_queue: VecDeque::new(),
@@ -432,6 +522,8 @@ impl VulkanoRenderer {
                 }]),
                 ..DynamicState::none()
             },
+            logger,
+            profiler,
         };
 
         for model in models {
@@ -441,39 +533,34 @@ impl VulkanoRenderer {
         Ok(renderer)
     }
 
-    // save model+material in VulkanoRenderer buffer cache
-    pub fn upload_model(&mut self, model: Arc<game_state::model::Model>) {
-        println!("renderer {} uploading model {}", self.id, model.filename);
-        let mesh = &model.mesh;
-        let vertices: Vec<Vertex> = mesh
-            .vertices
-            .iter()
-            .map(|x| Vertex::from_gs_vertex(*x))
-            .collect();
-
-        let (pixel_buffer, (width, height)) = {
-            let image = model.material.diffuse_map.to_rgba();
-            let dims = image.dimensions();
-            let image_data = image.into_raw();
-
-            let image_data_chunks = image_data.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]);
-
-            // TODO: staging buffer instead
-            let pixel_buffer =
-                vulkano::buffer::cpu_access::CpuAccessibleBuffer::<[[u8; 4]]>::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    false,
-                    image_data_chunks,
-                )
-                .expect("failed to create buffer");
-            (pixel_buffer, dims)
-        };
+    // A texture of `image` - the buffer its texels are copied from, the image to read, and the
+    // access to initialize it through
+    fn create_texture<F: FormatDesc>(
+        &self,
+        image: RgbaImage,
+        format: F,
+    ) -> (
+        Arc<CpuAccessibleBuffer<[[u8; 4]]>>,
+        Arc<ImmutableImage<F>>,
+        Arc<ImmutableImageInitialization<F>>,
+    ) {
+        let (width, height) = image.dimensions();
+        let image_data = image.into_raw();
+        let image_data_chunks = image_data.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]);
+
+        // TODO: staging buffer instead
+        let pixel_buffer = CpuAccessibleBuffer::<[[u8; 4]]>::from_iter(
+            self.device.clone(),
+            BufferUsage::all(),
+            false,
+            image_data_chunks,
+        )
+        .expect("failed to create buffer");
 
         let (texture, texture_init) = ImmutableImage::uninitialized(
             self.device.clone(),
             vulkano::image::Dimensions::Dim2d { width, height },
-            vulkano::format::R8G8B8A8Srgb,
+            format,
             MipmapsCount::One,
             ImageUsage {
                 transfer_source: true, // for blits
@@ -486,13 +573,38 @@ impl VulkanoRenderer {
         )
         .unwrap();
 
-        let texture_init = Arc::new(texture_init);
+        (pixel_buffer, texture, Arc::new(texture_init))
+    }
+
+    // save model+material in VulkanoRenderer buffer cache
+    pub fn upload_model(&mut self, model: Arc<game_state::model::Model>) {
+        self.logger.debug(
+            LOG_TARGET,
+            format!("renderer {} uploading model {}", self.id, model.filename),
+        );
+        let mesh = &model.mesh;
+        let vertices: Vec<Vertex> = mesh
+            .vertices
+            .iter()
+            .map(|x| Vertex::from_gs_vertex(*x))
+            .collect();
+
+        // materials without a normal or specular map get a neutral texel for it
+        let material = &model.material;
+        let (pixel_buffer, texture, texture_init) =
+            self.create_texture(material.unlit_texture(), R8G8B8A8Srgb);
+        let (normal_buffer, normal_map, normal_init) =
+            self.create_texture(material.normal_texture(), R8G8B8A8Unorm);
+        let (specular_buffer, specular_map, specular_init) =
+            self.create_texture(material.specular_texture(), R8G8B8A8Unorm);
 
         let pipeline_set = Self::create_descriptor_set(
             self.device.clone(),
             self.uniform_buffer.clone(),
             self.pipeline.clone(),
             texture.clone(),
+            normal_map.clone(),
+            specular_map.clone(),
         );
 
         let item = ModelData {
@@ -504,17 +616,34 @@ impl VulkanoRenderer {
                 vertices.iter().cloned(),
             )
             .expect("Unable to create buffer"),
-            indices: CpuAccessibleBuffer::from_iter(
-                self.device.clone(),
-                BufferUsage::all(),
-                false,
-                mesh.indices.iter().cloned(),
-            )
-            .expect("Unable to create buffer"),
+            indices: match &mesh.indices {
+                Indices::U16(indices) => IndexBuffer::U16(
+                    CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::all(),
+                        false,
+                        indices.iter().cloned(),
+                    )
+                    .expect("Unable to create buffer"),
+                ),
+                Indices::U32(indices) => IndexBuffer::U32(
+                    CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::all(),
+                        false,
+                        indices.iter().cloned(),
+                    )
+                    .expect("Unable to create buffer"),
+                ),
+            },
             diffuse_map: pixel_buffer,
+            normal_map: normal_buffer,
+            specular_map: specular_buffer,
             material_data: MaterialRenderData::new(
                 texture,
                 texture_init.clone(),
+                normal_map,
+                specular_map,
                 pipeline_set.clone(),
             ),
         };
@@ -532,6 +661,10 @@ impl VulkanoRenderer {
         let cmd_buffer = cmd_buffer_build
             .copy_buffer_to_image(item.diffuse_map.clone(), texture_init)
             .expect("unable to upload texture")
+            .copy_buffer_to_image(item.normal_map.clone(), normal_init)
+            .expect("unable to upload normal map")
+            .copy_buffer_to_image(item.specular_map.clone(), specular_init)
+            .expect("unable to upload specular map")
             .build()
             .expect("unable to build command buffer");
 
@@ -543,7 +676,14 @@ impl VulkanoRenderer {
         let execute = match prev.then_execute(self.queue.clone(), cmd_buffer) {
             Ok(execute) => execute,
             Err(e) => {
-                println!("VulkanoRenderer::upload_model() frame {} - unable to execute command buffer {:?}", self.fps.count(), e);
+                self.logger.error(
+                    LOG_TARGET,
+                    format!(
+                        "VulkanoRenderer::upload_model() frame {} - unable to execute command buffer {:?}",
+                        self.fps.count(),
+                        e
+                    ),
+                );
                 return;
             }
         };
@@ -556,7 +696,8 @@ impl VulkanoRenderer {
                 self.previous_frame_end = Box::new(future) as Box<_>;
             }
             Err(e) => {
-                println!("Error ending frame {:?}", e);
+                self.logger
+                    .error(LOG_TARGET, format!("Error ending frame {:?}", e));
                 self.previous_frame_end =
                     Box::new(vulkano::sync::now(self.device.clone())) as Box<_>;
             }
@@ -570,6 +711,10 @@ impl VulkanoRenderer {
     fn render(&mut self, camera: &CameraFacet) {
         self.previous_frame_end.cleanup_finished();
 
+        if self.shader_watcher.poll() {
+            self.reload_shaders();
+        }
+
         if self.recreate_swapchain {
             //println!("recreating swapchain with dimensions {:?}", size);
             use vulkano::swapchain::SwapchainCreationError;
@@ -619,7 +764,8 @@ impl VulkanoRenderer {
                     self.recreate_swapchain = false;
                 }
                 Err(SwapchainCreationError::UnsupportedDimensions) => {
-                    println!("Unsupported dimensions! {:?}", dims);
+                    self.logger
+                        .warn(LOG_TARGET, format!("Unsupported dimensions! {:?}", dims));
                 }
                 Err(e) => panic!("{:?}", e),
             }
@@ -631,9 +777,12 @@ impl VulkanoRenderer {
             Some(Duration::from_micros(300)),
         ) {
             Ok((num, suboptimal, _)) if num >= self.framebuffers.len() || suboptimal => {
-                println!(
-                    "acquire_next_image returned out of bounds image index {}",
-                    num
+                self.logger.debug(
+                    LOG_TARGET,
+                    format!(
+                        "acquire_next_image returned out of bounds image index {}",
+                        num
+                    ),
                 );
                 self.flag_recreate_swapchain();
                 return;
@@ -644,7 +793,8 @@ impl VulkanoRenderer {
                 return;
             }
             Err(vulkano::swapchain::AcquireError::Timeout) => {
-                println!("swapchain::acquire_next_image() Timeout!");
+                self.logger
+                    .debug(LOG_TARGET, "swapchain::acquire_next_image() Timeout!");
                 return;
             }
             Err(e) => panic!("{:?}", e),
@@ -690,37 +840,23 @@ impl VulkanoRenderer {
             Err(_err) => {} // println!("Error writing to uniform buffer {:?}", err),
         }
 
+        let traversal = self.profiler.scope("scene traversal");
         while let Some(next_layer) = self.render_layer_queue.pop_front() {
             // TODO: refactor this to use asset lookups
             // TODO: refactor this to use WorldEntity collection -> SceneGraph Rc types
             // TODO: asset lookups should store DescriptorSets with associated textures
 
+            next_layer.update_world_matrices();
             let iterator = BreadthFirstIterator::new(next_layer.root.clone());
             for (_node_id, rc) in iterator {
-                let node = &mut rc.borrow_mut();
+                let node = &rc.borrow();
 
-                // TODO: implement a per model -instance- matrix in the graph itself?
-                let model = match node.data.clone() {
-                    Some(model) => model.clone(),
+                let model = match node.data.model.clone() {
+                    Some(model) => model,
                     None => continue,
                 };
                 if let Some(md) = self.model_data.iter().find(|md| md.model.id == model.id) {
-                    let model_mat = model.model_mat;
-
-                    // TODO: update the world matrices from the parent * child's local matrix
-                    // eg. flag dirty a node, which means all children must be updated
-                    // actually save the data in each node
-                    let transform_mat = node
-                        .parent()
-                        .map(|parent| {
-                            let parent_model = parent.borrow().data.clone();
-                            if let Some(parent_model) = parent_model {
-                                parent_model.world_mat * model_mat
-                            } else {
-                                model_mat
-                            }
-                        })
-                        .unwrap_or_else(|| model_mat);
+                    let transform_mat = node.data.world() * model.model_mat;
 
                     // Push constants are leveraged here to send per-model
                     // matrices into the shaders
@@ -728,20 +864,31 @@ impl VulkanoRenderer {
                         model_mat: (viewscale * transform_mat).into(),
                     };
 
-                    cmd_buffer_build = cmd_buffer_build
-                        .draw_indexed(
+                    let drawn = match &md.indices {
+                        IndexBuffer::U16(indices) => cmd_buffer_build.draw_indexed(
                             self.pipeline.clone(),
                             &self.dynamic_state,
                             md.vertices.clone(),
-                            md.indices.clone(),
+                            indices.clone(),
                             md.material_data.descriptor_set.clone(),
                             push_constants, // or () - both leak on win32...
-                        )
-                        .expect("Unable to add command");
+                        ),
+                        IndexBuffer::U32(indices) => cmd_buffer_build.draw_indexed(
+                            self.pipeline.clone(),
+                            &self.dynamic_state,
+                            md.vertices.clone(),
+                            indices.clone(),
+                            md.material_data.descriptor_set.clone(),
+                            push_constants,
+                        ),
+                    };
+                    cmd_buffer_build = drawn.expect("Unable to add command");
                 }
             }
         }
 
+        drop(traversal);
+
         let cmd_buffer = cmd_buffer_build
             .end_render_pass()
             .expect("unable to end renderpass ")
@@ -762,10 +909,13 @@ impl VulkanoRenderer {
                 .then_signal_fence_and_flush(),
             Err(e) => {
                 self.fps.update();
-                println!(
-                    "VulkanoRenderer::render() frame {} - unable to execute command buffer, {:?}",
-                    self.fps.count(),
-                    e
+                self.logger.error(
+                    LOG_TARGET,
+                    format!(
+                        "VulkanoRenderer::render() frame {} - unable to execute command buffer, {:?}",
+                        self.fps.count(),
+                        e
+                    ),
                 );
                 return;
             }
@@ -776,8 +926,9 @@ impl VulkanoRenderer {
                 self.previous_frame_end = Box::new(future) as Box<_>;
             }
             Err(vulkano::sync::FlushError::OutOfDate) => {
-                println!(
-                    "swapchain is out of date, flagging recreate_swapchain=true for next frame"
+                self.logger.debug(
+                    LOG_TARGET,
+                    "swapchain is out of date, flagging recreate_swapchain=true for next frame",
                 );
 
                 self.flag_recreate_swapchain();
@@ -785,7 +936,8 @@ impl VulkanoRenderer {
                     Box::new(vulkano::sync::now(self.device.clone())) as Box<_>;
             }
             Err(e) => {
-                println!("Error ending frame {:?}", e);
+                self.logger
+                    .error(LOG_TARGET, format!("Error ending frame {:?}", e));
                 self.previous_frame_end =
                     Box::new(vulkano::sync::now(self.device.clone())) as Box<_>;
             }
@@ -820,11 +972,20 @@ impl Renderer for VulkanoRenderer {
     fn present(&mut self, camera: &CameraFacet) {
         self.render(camera);
     }
+
+    fn model_updated(&mut self, model: &Arc<game_state::model::Model>) {
+        // frames in flight keep the old buffers alive until they are done with them
+        let before = self.model_data.len();
+        self.model_data.retain(|md| md.model.id != model.id);
+        if self.model_data.len() != before {
+            self.upload_model(model.clone());
+        }
+    }
 }
 
 impl Drop for VulkanoRenderer {
     fn drop(&mut self) {
-        println!("VulkanRenderer drop");
+        self.logger.debug(LOG_TARGET, "VulkanRenderer drop");
     }
 }
 
@@ -0,0 +1,190 @@
+use std::error::Error;
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderModule};
+
+use super::spirv::Interface;
+use super::{fs, vs};
+
+///
+/// Shader sources, relative to the working directory - the same files `vs` and `fs` are built
+/// from
+///
+pub const VERTEX_SHADER_PATH: &str = "assets/shaders/vs.glsl";
+pub const FRAGMENT_SHADER_PATH: &str = "assets/shaders/fs.glsl";
+
+// How often the shader sources are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const ENTRY_POINT: &[u8] = b"main\0";
+
+// SPIR-V of the built-in shaders, compiled by build.rs
+const BUILT_IN_VERTEX: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/vs.spv"));
+const BUILT_IN_FRAGMENT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/fs.spv"));
+
+///
+/// ShaderPair - vertex and fragment shader modules, built into the mod or compiled at runtime
+///
+/// Shaders compiled at runtime are bound with the interface generated for the built-in ones
+/// (`vs::MainInput`, `vs::Layout`, ...), so only their bodies can change: changing inputs,
+/// outputs, uniforms or push constants still needs the mod to be rebuilt. `compile` reflects
+/// the interface of the new shaders and refuses any that differ from the built-in ones.
+///
+#[derive(Clone)]
+pub struct ShaderPair {
+    pub vertex: Arc<ShaderModule>,
+    pub fragment: Arc<ShaderModule>,
+}
+
+impl ShaderPair {
+    ///
+    /// The shaders compiled into the mod at build time
+    ///
+    pub fn built_in(device: Arc<Device>) -> Result<Self, Box<dyn Error>> {
+        let vertex = vs::Shader::load(device.clone())?;
+        let fragment = fs::Shader::load(device)?;
+        Ok(ShaderPair {
+            vertex: vertex.module().clone(),
+            fragment: fragment.module().clone(),
+        })
+    }
+
+    ///
+    /// Compile the shader sources from disk, as long as their interface matches the built-in
+    /// shaders'
+    ///
+    pub fn compile(
+        device: Arc<Device>,
+        vertex_path: &Path,
+        fragment_path: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut compiler = shaderc::Compiler::new().ok_or("unable to create shader compiler")?;
+        Ok(ShaderPair {
+            vertex: compile_module(
+                &mut compiler,
+                device.clone(),
+                vertex_path,
+                shaderc::ShaderKind::Vertex,
+                BUILT_IN_VERTEX,
+            )?,
+            fragment: compile_module(
+                &mut compiler,
+                device,
+                fragment_path,
+                shaderc::ShaderKind::Fragment,
+                BUILT_IN_FRAGMENT,
+            )?,
+        })
+    }
+
+    pub fn vertex_entry_point(
+        &self,
+    ) -> GraphicsEntryPoint<(), vs::MainInput, vs::MainOutput, vs::Layout> {
+        let stages = ShaderStages {
+            vertex: true,
+            ..ShaderStages::none()
+        };
+        unsafe {
+            self.vertex.graphics_entry_point(
+                CStr::from_bytes_with_nul_unchecked(ENTRY_POINT),
+                vs::MainInput,
+                vs::MainOutput,
+                vs::Layout(stages),
+                GraphicsShaderType::Vertex,
+            )
+        }
+    }
+
+    pub fn fragment_entry_point(
+        &self,
+    ) -> GraphicsEntryPoint<(), fs::MainInput, fs::MainOutput, fs::Layout> {
+        let stages = ShaderStages {
+            fragment: true,
+            ..ShaderStages::none()
+        };
+        unsafe {
+            self.fragment.graphics_entry_point(
+                CStr::from_bytes_with_nul_unchecked(ENTRY_POINT),
+                fs::MainInput,
+                fs::MainOutput,
+                fs::Layout(stages),
+                GraphicsShaderType::Fragment,
+            )
+        }
+    }
+}
+
+fn compile_module(
+    compiler: &mut shaderc::Compiler,
+    device: Arc<Device>,
+    path: &Path,
+    kind: shaderc::ShaderKind,
+    built_in: &[u8],
+) -> Result<Arc<ShaderModule>, Box<dyn Error>> {
+    let source = std::fs::read_to_string(path).map_err(|err| format!("{:?}: {}", path, err))?;
+    let file_name = path.to_string_lossy();
+    let spirv = compiler
+        .compile_into_spirv(&source, kind, &file_name, "main", None)
+        .map_err(|err| format!("{}", err))?;
+
+    // the pipeline binds the module with the built-in interface - anything else is undefined
+    let expected = Interface::reflect(built_in)?;
+    let interface =
+        Interface::reflect(spirv.as_binary_u8()).map_err(|err| format!("{:?}: {}", path, err))?;
+    if let Some(mismatch) = interface.mismatch(&expected) {
+        return Err(format!(
+            "{:?} doesn't match the built-in shader's interface - {}",
+            path, mismatch
+        )
+        .into());
+    }
+    Ok(unsafe { ShaderModule::new(device, spirv.as_binary_u8()) }?)
+}
+
+///
+/// ShaderWatcher - notices changes to shader sources, checking at most every POLL_INTERVAL
+///
+pub struct ShaderWatcher {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    last_check: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(paths: &[&Path]) -> Self {
+        let paths = paths.iter().map(|p| p.to_path_buf()).collect::<Vec<_>>();
+        let modified = paths.iter().map(|p| modified(p)).collect();
+        ShaderWatcher {
+            paths,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+
+    ///
+    /// Returns true if any of the sources changed since the last change was reported
+    ///
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let current = self.paths.iter().map(|p| modified(p)).collect::<Vec<_>>();
+        if current == self.modified {
+            return false;
+        }
+        self.modified = current;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
@@ -0,0 +1,381 @@
+//!
+//! Just enough SPIR-V reflection to tell whether two shader modules have the same interface: the
+//! same inputs and outputs at each location, the same descriptors at each set and binding, and
+//! the same push constants - everything a pipeline built for one relies on in the other.
+//!
+
+use std::collections::HashMap;
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+// opcodes
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_TYPE_VOID: u32 = 19;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+
+// decorations
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// storage classes
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+// types nest a few levels deep at most - deeper is taken as a malformed module
+const MAX_TYPE_DEPTH: usize = 16;
+
+///
+/// Interface - what a shader module exchanges with the pipeline, each type described as a string
+///
+/// Inputs and outputs are by location, built-ins aside. Descriptors are by set and binding.
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Interface {
+    pub inputs: Vec<(u32, String)>,
+    pub outputs: Vec<(u32, String)>,
+    pub descriptors: Vec<(u32, u32, String)>,
+    pub push_constants: Vec<String>,
+}
+
+impl Interface {
+    ///
+    /// Reflect the interface of a SPIR-V module, in bytes of either endianness
+    ///
+    pub fn reflect(bytes: &[u8]) -> Result<Self, String> {
+        Module::parse(&words(bytes)?)?.interface()
+    }
+
+    ///
+    /// What differs from the `expected` interface, if anything
+    ///
+    pub fn mismatch(&self, expected: &Interface) -> Option<String> {
+        let differs = |what: &str, actual: &dyn std::fmt::Debug, expected: &dyn std::fmt::Debug| {
+            format!(
+                "{} are {:?}, where {:?} were expected",
+                what, actual, expected
+            )
+        };
+        if self.inputs != expected.inputs {
+            Some(differs("inputs", &self.inputs, &expected.inputs))
+        } else if self.outputs != expected.outputs {
+            Some(differs("outputs", &self.outputs, &expected.outputs))
+        } else if self.descriptors != expected.descriptors {
+            Some(differs(
+                "descriptors",
+                &self.descriptors,
+                &expected.descriptors,
+            ))
+        } else if self.push_constants != expected.push_constants {
+            Some(differs(
+                "push constants",
+                &self.push_constants,
+                &expected.push_constants,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+fn words(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if !bytes.len().is_multiple_of(4) || bytes.len() < HEADER_WORDS * 4 {
+        return Err(format!("{} bytes isn't a SPIR-V module", bytes.len()));
+    }
+    let little = bytes
+        .chunks(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect::<Vec<_>>();
+    if little[0] == MAGIC {
+        Ok(little)
+    } else if little[0].swap_bytes() == MAGIC {
+        Ok(little.into_iter().map(u32::swap_bytes).collect())
+    } else {
+        Err("not a SPIR-V module - the magic number is missing".to_string())
+    }
+}
+
+// decorations of an id or struct member, with their literals
+type Decorations = Vec<(u32, Vec<u32>)>;
+
+// The parts of a module the interface is made of, by result id
+#[derive(Default)]
+struct Module {
+    types: HashMap<u32, (u32, Vec<u32>)>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    variables: Vec<(u32, u32, u32)>, // pointer type, id, storage class
+}
+
+impl Module {
+    fn parse(words: &[u32]) -> Result<Self, String> {
+        let mut module = Module::default();
+        let mut i = HEADER_WORDS;
+        while i < words.len() {
+            let opcode = words[i] & 0xffff;
+            let count = (words[i] >> 16) as usize;
+            if count == 0 || i + count > words.len() {
+                return Err(format!("malformed instruction at word {}", i));
+            }
+            let operands = &words[i + 1..i + count];
+            match opcode {
+                OP_DECORATE if operands.len() >= 2 => module
+                    .decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push((operands[1], operands[2..].to_vec())),
+                OP_MEMBER_DECORATE if operands.len() >= 3 => module
+                    .member_decorations
+                    .entry((operands[0], operands[1]))
+                    .or_default()
+                    .push((operands[2], operands[3..].to_vec())),
+                OP_CONSTANT if operands.len() >= 3 => {
+                    module.constants.insert(operands[1], operands[2]);
+                }
+                OP_VARIABLE if operands.len() >= 3 => {
+                    module
+                        .variables
+                        .push((operands[0], operands[1], operands[2]));
+                }
+                OP_TYPE_VOID..=OP_TYPE_STRUCT | OP_TYPE_POINTER if !operands.is_empty() => {
+                    module
+                        .types
+                        .insert(operands[0], (opcode, operands[1..].to_vec()));
+                }
+                _ => {}
+            }
+            i += count;
+        }
+        Ok(module)
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&id).and_then(|decorations| {
+            decorations
+                .iter()
+                .find(|(d, _)| *d == decoration)
+                .map(|(_, literals)| literals.first().cloned().unwrap_or(0))
+        })
+    }
+
+    fn interface(&self) -> Result<Interface, String> {
+        let mut interface = Interface::default();
+        for &(pointer, id, storage) in &self.variables {
+            let ty = || self.describe(pointer, 0);
+            match storage {
+                STORAGE_INPUT | STORAGE_OUTPUT => {
+                    // built-ins have no location
+                    let location = match self.decoration(id, DECORATION_LOCATION) {
+                        Some(location) => location,
+                        None => continue,
+                    };
+                    if storage == STORAGE_INPUT {
+                        interface.inputs.push((location, ty()?));
+                    } else {
+                        interface.outputs.push((location, ty()?));
+                    }
+                }
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let set = self.decoration(id, DECORATION_DESCRIPTOR_SET).unwrap_or(0);
+                    let binding = self.decoration(id, DECORATION_BINDING).unwrap_or(0);
+                    interface.descriptors.push((set, binding, ty()?));
+                }
+                STORAGE_PUSH_CONSTANT => interface.push_constants.push(ty()?),
+                _ => {}
+            }
+        }
+        interface.inputs.sort();
+        interface.outputs.sort();
+        interface.descriptors.sort();
+        interface.push_constants.sort();
+        Ok(interface)
+    }
+
+    fn describe(&self, id: u32, depth: usize) -> Result<String, String> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(format!("type {} nests too deeply", id));
+        }
+        let (opcode, operands) = self
+            .types
+            .get(&id)
+            .ok_or_else(|| format!("type {} isn't defined", id))?;
+        let operand = |i: usize| {
+            operands
+                .get(i)
+                .cloned()
+                .ok_or_else(|| format!("type {} is missing an operand", id))
+        };
+        let inner = |i: usize| operand(i).and_then(|ty| self.describe(ty, depth + 1));
+        Ok(match *opcode {
+            OP_TYPE_VOID => "void".to_string(),
+            OP_TYPE_BOOL => "bool".to_string(),
+            OP_TYPE_INT if operand(1)? == 0 => format!("u{}", operand(0)?),
+            OP_TYPE_INT => format!("i{}", operand(0)?),
+            OP_TYPE_FLOAT => format!("f{}", operand(0)?),
+            OP_TYPE_VECTOR => format!("vec{}<{}>", operand(1)?, inner(0)?),
+            OP_TYPE_MATRIX => format!("mat{}<{}>", operand(1)?, inner(0)?),
+            // sampled type, then dimensionality, depth, arrayed, multisampled, sampled, format
+            OP_TYPE_IMAGE => format!("image<{}, {:?}>", inner(0)?, &operands[1..]),
+            OP_TYPE_SAMPLER => "sampler".to_string(),
+            OP_TYPE_SAMPLED_IMAGE => format!("sampled {}", inner(0)?),
+            OP_TYPE_ARRAY => {
+                let length = self
+                    .constants
+                    .get(&operand(1)?)
+                    .ok_or_else(|| format!("array type {} has no constant length", id))?;
+                format!("[{}; {}]", inner(0)?, length)
+            }
+            OP_TYPE_RUNTIME_ARRAY => format!("[{}]", inner(0)?),
+            // members with their layout: offset, and the stride and order of matrices
+            OP_TYPE_STRUCT => {
+                let mut members = Vec::new();
+                for (member, ty) in operands.iter().enumerate() {
+                    let mut layout = String::new();
+                    let decorations = self.member_decorations.get(&(id, member as u32));
+                    for (decoration, literals) in decorations.into_iter().flatten() {
+                        match *decoration {
+                            DECORATION_OFFSET => layout += &format!(" @{:?}", literals),
+                            DECORATION_MATRIX_STRIDE => {
+                                layout += &format!(" stride {:?}", literals)
+                            }
+                            DECORATION_ROW_MAJOR => layout += " row major",
+                            _ => {}
+                        }
+                    }
+                    members.push(format!("{}{}", self.describe(*ty, depth + 1)?, layout));
+                }
+                format!("struct {{ {} }}", members.join(", "))
+            }
+            // a variable's type is a pointer to it - the storage class is checked separately
+            OP_TYPE_POINTER => inner(1)?,
+            _ => return Err(format!("type {} has unknown opcode {}", id, opcode)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An instruction, with its word count
+    fn op(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    // A module with a vecN input at location 0, a built-in output, a uniform buffer holding a
+    // mat4 at set 0 binding 1, and a sampled image at binding `image_binding`
+    fn module(input_size: u32, image_binding: u32) -> Vec<u8> {
+        let (float, vec, input_ptr, input, output, output_ptr) = (1, 2, 3, 4, 5, 6);
+        let (vec4, mat4, block, block_ptr, block_var) = (7, 8, 9, 10, 11);
+        let (image, sampled, sampled_ptr, sampled_var) = (12, 13, 14, 15);
+        let instructions = vec![
+            op(OP_DECORATE, &[input, DECORATION_LOCATION, 0]),
+            op(OP_DECORATE, &[block_var, DECORATION_DESCRIPTOR_SET, 0]),
+            op(OP_DECORATE, &[block_var, DECORATION_BINDING, 1]),
+            op(OP_MEMBER_DECORATE, &[block, 0, DECORATION_OFFSET, 0]),
+            op(
+                OP_MEMBER_DECORATE,
+                &[block, 0, DECORATION_MATRIX_STRIDE, 16],
+            ),
+            op(OP_DECORATE, &[sampled_var, DECORATION_DESCRIPTOR_SET, 0]),
+            op(
+                OP_DECORATE,
+                &[sampled_var, DECORATION_BINDING, image_binding],
+            ),
+            op(OP_TYPE_FLOAT, &[float, 32]),
+            op(OP_TYPE_VECTOR, &[vec, float, input_size]),
+            op(OP_TYPE_POINTER, &[input_ptr, STORAGE_INPUT, vec]),
+            op(OP_VARIABLE, &[input_ptr, input, STORAGE_INPUT]),
+            op(OP_TYPE_VECTOR, &[vec4, float, 4]),
+            op(OP_TYPE_POINTER, &[output_ptr, STORAGE_OUTPUT, vec4]),
+            op(OP_VARIABLE, &[output_ptr, output, STORAGE_OUTPUT]),
+            op(OP_TYPE_MATRIX, &[mat4, vec4, 4]),
+            op(OP_TYPE_STRUCT, &[block, mat4]),
+            op(OP_TYPE_POINTER, &[block_ptr, STORAGE_UNIFORM, block]),
+            op(OP_VARIABLE, &[block_ptr, block_var, STORAGE_UNIFORM]),
+            op(OP_TYPE_IMAGE, &[image, float, 1, 0, 0, 0, 1, 0]),
+            op(OP_TYPE_SAMPLED_IMAGE, &[sampled, image]),
+            op(
+                OP_TYPE_POINTER,
+                &[sampled_ptr, STORAGE_UNIFORM_CONSTANT, sampled],
+            ),
+            op(
+                OP_VARIABLE,
+                &[sampled_ptr, sampled_var, STORAGE_UNIFORM_CONSTANT],
+            ),
+        ];
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 16, 0];
+        words.extend(instructions.into_iter().flatten());
+        words
+            .iter()
+            .flat_map(|w| w.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn reflects_the_interface() {
+        let interface = Interface::reflect(&module(3, 2)).unwrap();
+        assert_eq!(interface.inputs, vec![(0, "vec3<f32>".to_string())]);
+        // the output has no location - a built-in
+        assert!(interface.outputs.is_empty());
+        assert_eq!(
+            interface.descriptors,
+            vec![
+                (
+                    0,
+                    1,
+                    "struct { mat4<vec4<f32>> @[0] stride [16] }".to_string()
+                ),
+                (0, 2, "sampled image<f32, [1, 0, 0, 0, 1, 0]>".to_string()),
+            ]
+        );
+
+        // big endian words are read as well
+        let swapped = module(3, 2)
+            .chunks(4)
+            .flat_map(|w| vec![w[3], w[2], w[1], w[0]])
+            .collect::<Vec<_>>();
+        assert_eq!(Interface::reflect(&swapped).unwrap(), interface);
+        assert!(Interface::reflect(&[0; 20]).is_err());
+    }
+
+    #[test]
+    fn changed_interfaces_mismatch() {
+        let built_in = Interface::reflect(&module(3, 2)).unwrap();
+        let same = Interface::reflect(&module(3, 2)).unwrap();
+        assert_eq!(same.mismatch(&built_in), None);
+
+        let input = Interface::reflect(&module(2, 2)).unwrap();
+        assert!(input.mismatch(&built_in).unwrap().starts_with("inputs"));
+        let binding = Interface::reflect(&module(3, 3)).unwrap();
+        assert!(binding
+            .mismatch(&built_in)
+            .unwrap()
+            .starts_with("descriptors"));
+    }
+}
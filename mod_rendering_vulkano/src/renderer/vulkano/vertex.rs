@@ -9,7 +9,6 @@ pub struct Vertex {
 
 impl Vertex {
     pub fn from_gs_vertex(g: GSVertex) -> Self {
-        println!("vertex {:?}", g);
         Vertex {
             position: [g.position.0, g.position.1, g.position.2],
             uv: [g.uvw.0, g.uvw.1],
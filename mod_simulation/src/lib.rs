@@ -1,14 +1,65 @@
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
-use game_state::state;
+use game_state::abi::ModAbi;
+use game_state::info;
+use game_state::state::{self, LogAccess};
+
+// Private simulation state. It lives in this library, so it is lost when the library is
+// reloaded - unless it is handed to the host in save_state and restored in the new version.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static ELAPSED_MICROS: AtomicU64 = AtomicU64::new(0);
+
+const LOG_TARGET: &str = "simulation";
+
+// Bump whenever the layout of the saved state changes
+const SAVE_VERSION: u8 = 1;
+
+#[no_mangle]
+pub extern "C" fn mod_simulation_abi() -> ModAbi {
+    ModAbi::current()
+}
 
 #[no_mangle]
-pub extern "C" fn mod_simulation_load(_s: &mut state::State) {}
+pub extern "C-unwind" fn mod_simulation_load(_s: &mut state::State) {}
 
 #[no_mangle]
-pub extern "C" fn mod_simulation_update(_s: &mut state::State, _dt: &Duration) {
+pub extern "C-unwind" fn mod_simulation_update(_s: &mut state::State, dt: &Duration) {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    ELAPSED_MICROS.fetch_add(dt.as_micros() as u64, Ordering::Relaxed);
     //println!("sim tick, probably need deltatime (since this mod was last ticked)");
 }
 
 #[no_mangle]
-pub extern "C" fn mod_simulation_unload(_s: &mut state::State) {}
+pub extern "C-unwind" fn mod_simulation_unload(_s: &mut state::State) {}
+
+#[no_mangle]
+pub extern "C-unwind" fn mod_simulation_save_state(_s: &mut state::State, saved: &mut Vec<u8>) {
+    saved.push(SAVE_VERSION);
+    saved.extend_from_slice(&TICKS.load(Ordering::Relaxed).to_le_bytes());
+    saved.extend_from_slice(&ELAPSED_MICROS.load(Ordering::Relaxed).to_le_bytes());
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C-unwind" fn mod_simulation_restore_state(s: &mut state::State, saved: &[u8]) {
+    if saved.len() != 17 || saved[0] != SAVE_VERSION {
+        s.get_logger().warn(
+            LOG_TARGET,
+            "discarding saved state from an incompatible version",
+        );
+        return;
+    }
+    let ticks = u64::from_le_bytes(saved[1..9].try_into().unwrap());
+    let elapsed_micros = u64::from_le_bytes(saved[9..17].try_into().unwrap());
+    TICKS.store(ticks, Ordering::Relaxed);
+    ELAPSED_MICROS.store(elapsed_micros, Ordering::Relaxed);
+    info!(
+        s.get_logger(),
+        LOG_TARGET,
+        "restored after {} ticks ({:?} simulated)",
+        ticks,
+        Duration::from_micros(elapsed_micros)
+    );
+}
@@ -0,0 +1,430 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use game_state::state::{DrawMode, GraphicsApi};
+
+///
+/// Config file read by default, from the working directory, when it exists
+///
+pub const CONFIG_FILENAME: &str = "sg_engine.toml";
+
+pub const USAGE: &str = "usage: sg_engine [options]
+
+options:
+    --config <path>       read the config from <path> instead of ./sg_engine.toml
+    --mods-dir <path>     search <path> for mods - may be repeated
+    --mods <a,b,..>       only load the named mods
+    --graphics <api>      create windows for vulkan, opengl or software rendering
+    --frame-rate <fps>    target frame rate, 0 for unlimited
+    --poll-ms <ms>        how often to check mods for a new build
+    --headless            run without windows, input or rendering
+    --help                print this message";
+
+///
+/// EngineConfig - the setup of the sg_engine shell, read from `sg_engine.toml`
+///
+/// Example:
+///
+/// mod_paths = [".", "../my_mods"]
+/// mods = ["asset_loader", "input", "rendering_vulkano", "simulation"]
+/// graphics = "vulkan"
+/// frame_rate = 60
+/// reload_poll_ms = 500
+///
+/// [[window]]
+/// title = "sg-shell 1 (vulkano) wireframe"
+/// width = 800
+/// height = 600
+/// y = 720
+/// draw_mode = "wireframe"
+/// line_width = 3.0
+///
+/// Every field is optional. Without a `mods` list, every mod found is loaded, except the
+/// rendering mods of the graphics APIs not in use. Settings given on the command line override
+/// the file.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    #[serde(rename = "window")]
+    pub windows: Vec<WindowConfig>,
+    pub mods: Option<Vec<String>>,
+    pub mod_paths: Vec<PathBuf>,
+    /// The graphics API windows are created for - must match the rendering mod loaded
+    pub graphics: GraphicsApiName,
+    /// Target frame rate, 0 for unlimited
+    pub frame_rate: u32,
+    pub fixed_step_ms: u64,
+    pub max_steps_per_frame: u32,
+    pub reload_poll_ms: u64,
+    pub headless: bool,
+    /// Threads running background jobs spawned by mods, 0 to run them on the main thread
+    /// once a frame
+    pub job_threads: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub draw_mode: DrawModeName,
+    /// Line width, in wireframe mode
+    pub line_width: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsApiName {
+    Vulkan,
+    OpenGL,
+    Software,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DrawModeName {
+    Wireframe,
+    Points,
+    Textured,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            windows: vec![
+                WindowConfig {
+                    title: "sg-shell 1 (vulkano) wireframe".to_string(),
+                    width: 800,
+                    height: 600,
+                    x: 0,
+                    y: 720,
+                    draw_mode: DrawModeName::Wireframe,
+                    line_width: 3.0,
+                },
+                WindowConfig {
+                    title: "sg-shell 1 (vulkano) textured".to_string(),
+                    width: 1280,
+                    height: 720,
+                    ..Default::default()
+                },
+            ],
+            mods: None,
+            mod_paths: vec![PathBuf::from(".")],
+            graphics: GraphicsApiName::Vulkan,
+            frame_rate: 60,
+            fixed_step_ms: 10,
+            max_steps_per_frame: 5,
+            reload_poll_ms: 500,
+            headless: false,
+            job_threads: 2,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: "sg-shell".to_string(),
+            width: 1280,
+            height: 720,
+            x: 0,
+            y: 0,
+            draw_mode: DrawModeName::Textured,
+            line_width: 3.0,
+        }
+    }
+}
+
+impl GraphicsApiName {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "vulkan" => Some(GraphicsApiName::Vulkan),
+            "opengl" => Some(GraphicsApiName::OpenGL),
+            "software" => Some(GraphicsApiName::Software),
+            _ => None,
+        }
+    }
+
+    ///
+    /// The rendering mod presenting to windows created for this API
+    ///
+    pub fn rendering_mod(self) -> &'static str {
+        match self {
+            GraphicsApiName::Vulkan => "rendering_vulkano",
+            GraphicsApiName::OpenGL => "rendering_opengl",
+            GraphicsApiName::Software => "rendering_software",
+        }
+    }
+
+    pub fn graphics_api(self) -> GraphicsApi {
+        match self {
+            GraphicsApiName::Vulkan => GraphicsApi::Vulkan,
+            GraphicsApiName::OpenGL => GraphicsApi::OpenGL,
+            GraphicsApiName::Software => GraphicsApi::Software,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn draw_mode(&self) -> DrawMode {
+        match self.draw_mode {
+            DrawModeName::Wireframe => DrawMode::Wireframe(self.line_width),
+            DrawModeName::Points => DrawMode::Points,
+            DrawModeName::Textured => DrawMode::Textured,
+        }
+    }
+}
+
+impl EngineConfig {
+    ///
+    /// Read and parse a config file
+    ///
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|err| format!("{:?}: {}", path, err).into())
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let config: EngineConfig = toml::from_str(contents)?;
+        if config.fixed_step_ms == 0 {
+            return Err("fixed_step_ms must be non-zero".into());
+        }
+        if config.max_steps_per_frame == 0 {
+            return Err("max_steps_per_frame must be non-zero".into());
+        }
+        Ok(config)
+    }
+
+    ///
+    /// Mods never to load: without a `mods` list, the rendering mods of the other graphics
+    /// APIs - every rendering mod presents to the windows
+    ///
+    pub fn excluded_mods(&self) -> Vec<String> {
+        if self.mods.is_some() {
+            return Vec::new();
+        }
+        [
+            GraphicsApiName::Vulkan,
+            GraphicsApiName::OpenGL,
+            GraphicsApiName::Software,
+        ]
+        .iter()
+        .filter(|api| **api != self.graphics)
+        .map(|api| api.rendering_mod().to_string())
+        .collect()
+    }
+
+    ///
+    /// The config file named on the command line, or `sg_engine.toml` if there is one, with the
+    /// command line applied on top
+    ///
+    pub fn from_cli(args: &CliArgs) -> Result<Self, Box<dyn Error>> {
+        let mut config = match args.config {
+            Some(ref path) => Self::load(path)?,
+            None if Path::new(CONFIG_FILENAME).exists() => Self::load(Path::new(CONFIG_FILENAME))?,
+            None => Default::default(),
+        };
+        config.apply(args);
+        Ok(config)
+    }
+
+    ///
+    /// Override the config with the settings given on the command line
+    ///
+    pub fn apply(&mut self, args: &CliArgs) {
+        if !args.mod_paths.is_empty() {
+            self.mod_paths = args.mod_paths.clone();
+        }
+        if args.mods.is_some() {
+            self.mods = args.mods.clone();
+        }
+        if let Some(graphics) = args.graphics {
+            self.graphics = graphics;
+        }
+        if let Some(frame_rate) = args.frame_rate {
+            self.frame_rate = frame_rate;
+        }
+        if let Some(reload_poll_ms) = args.reload_poll_ms {
+            self.reload_poll_ms = reload_poll_ms;
+        }
+        self.headless |= args.headless;
+    }
+
+    pub fn fixed_step(&self) -> Duration {
+        Duration::from_millis(self.fixed_step_ms)
+    }
+
+    pub fn reload_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.reload_poll_ms)
+    }
+}
+
+///
+/// CliArgs - settings given on the command line, see `USAGE`
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliArgs {
+    pub config: Option<PathBuf>,
+    pub mod_paths: Vec<PathBuf>,
+    pub mods: Option<Vec<String>>,
+    pub graphics: Option<GraphicsApiName>,
+    pub frame_rate: Option<u32>,
+    pub reload_poll_ms: Option<u64>,
+    pub headless: bool,
+    pub help: bool,
+}
+
+impl CliArgs {
+    ///
+    /// Parse the arguments following the program name
+    ///
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = CliArgs::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} expects a value", arg))
+            };
+            match arg.as_str() {
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--mods-dir" => parsed.mod_paths.push(PathBuf::from(value()?)),
+                "--mods" => {
+                    let mods = value()?
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(String::from)
+                        .collect();
+                    parsed.mods = Some(mods);
+                }
+                "--graphics" => {
+                    let name = value()?;
+                    let graphics = GraphicsApiName::parse(&name).ok_or_else(|| {
+                        format!("{} expects vulkan, opengl or software, not {}", arg, name)
+                    })?;
+                    parsed.graphics = Some(graphics);
+                }
+                "--frame-rate" => parsed.frame_rate = Some(parse_number(&arg, &value()?)?),
+                "--poll-ms" => parsed.reload_poll_ms = Some(parse_number(&arg, &value()?)?),
+                "--headless" => parsed.headless = true,
+                "--help" | "-h" => parsed.help = true,
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, not {}", arg, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_config_file() {
+        let config = EngineConfig::parse(
+            r#"
+            mods = ["input", "simulation"]
+            frame_rate = 144
+            graphics = "opengl"
+
+            [[window]]
+            title = "points"
+            width = 640
+            height = 480
+            draw_mode = "points"
+
+            [[window]]
+            draw_mode = "wireframe"
+            line_width = 1.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.frame_rate, 144);
+        assert_eq!(config.graphics.graphics_api(), GraphicsApi::OpenGL);
+        assert_eq!(config.reload_poll_ms, 500);
+        assert_eq!(config.mod_paths, vec![PathBuf::from(".")]);
+        assert_eq!(
+            config.mods,
+            Some(vec!["input".to_string(), "simulation".to_string()])
+        );
+        assert_eq!(config.windows.len(), 2);
+        assert_eq!(config.windows[0].width, 640);
+        assert_eq!(config.windows[0].draw_mode(), DrawMode::Points);
+        assert_eq!(config.windows[1].draw_mode(), DrawMode::Wireframe(1.5));
+
+        assert!(EngineConfig::parse("frame_rate = \"fast\"").is_err());
+        assert!(EngineConfig::parse("fixed_step_ms = 0").is_err());
+        assert!(EngineConfig::parse("max_steps_per_frame = 0").is_err());
+    }
+
+    #[test]
+    fn only_the_matching_renderer_is_loaded_by_default() {
+        let mut config = EngineConfig::parse("graphics = \"opengl\"").unwrap();
+        assert_eq!(
+            config.excluded_mods(),
+            vec![
+                "rendering_vulkano".to_string(),
+                "rendering_software".to_string()
+            ]
+        );
+        // a mods list is taken as it is
+        config.mods = Some(vec!["rendering_vulkano".to_string()]);
+        assert!(config.excluded_mods().is_empty());
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let mut config = EngineConfig::default();
+        let cli = CliArgs::parse(args(&[
+            "--mods-dir",
+            "a",
+            "--mods-dir",
+            "b",
+            "--mods",
+            "input, simulation",
+            "--poll-ms",
+            "100",
+            "--headless",
+            "--graphics",
+            "software",
+        ]))
+        .unwrap();
+        config.apply(&cli);
+        assert_eq!(
+            config.mod_paths,
+            vec![PathBuf::from("a"), PathBuf::from("b")]
+        );
+        assert_eq!(
+            config.mods,
+            Some(vec!["input".to_string(), "simulation".to_string()])
+        );
+        assert_eq!(config.reload_poll_interval(), Duration::from_millis(100));
+        assert_eq!(config.frame_rate, 60);
+        assert_eq!(config.graphics, GraphicsApiName::Software);
+        assert!(config.headless);
+
+        assert!(CliArgs::parse(args(&["--frame-rate"])).is_err());
+        assert!(CliArgs::parse(args(&["--frame-rate", "lots"])).is_err());
+        assert!(CliArgs::parse(args(&["--fullscreen"])).is_err());
+        assert!(CliArgs::parse(args(&["--graphics", "metal"])).is_err());
+    }
+}
@@ -1 +1,4 @@
+pub mod config;
 pub mod libloader;
+pub mod logging;
+pub mod runner;
@@ -0,0 +1,225 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::manifest::ModManifest;
+
+///
+/// The result of resolving mod dependencies:
+/// - `order` holds manifest indices, dependencies before dependents, ties broken by
+///   (load_order, name) so the order is deterministic
+/// - `rejected` holds the indices of mods that cannot be loaded, with the reason
+///
+#[derive(Debug, Default)]
+pub struct DependencyOrder {
+    pub order: Vec<usize>,
+    pub rejected: Vec<(usize, String)>,
+}
+
+///
+/// Topologically sort manifests by their `depends_on` lists.
+///
+/// Mods depending on unknown mods, mods that are part of a dependency cycle, and anything that
+/// depends on those are rejected rather than loaded in an arbitrary order.
+///
+pub fn resolve(manifests: &[&ModManifest]) -> DependencyOrder {
+    let by_name: HashMap<&str, usize> = manifests
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.name.as_str(), i))
+        .collect();
+
+    let mut rejected: HashMap<usize, String> = HashMap::new();
+    for (i, m) in manifests.iter().enumerate() {
+        if let Some(dep) = m
+            .depends_on
+            .iter()
+            .find(|d| !by_name.contains_key(d.as_str()))
+        {
+            rejected.insert(i, format!("depends on unknown mod '{}'", dep));
+        }
+    }
+
+    // Kahn's algorithm, always picking the lowest (load_order, name) among the ready mods
+    let key = |i: usize| (manifests[i].load_order, manifests[i].name.as_str(), i);
+    let mut remaining_deps: Vec<usize> = manifests.iter().map(|m| m.depends_on.len()).collect();
+    let mut ready: BTreeSet<_> = (0..manifests.len())
+        .filter(|i| remaining_deps[*i] == 0)
+        .map(key)
+        .collect();
+    let mut order = Vec::with_capacity(manifests.len());
+    let mut placed = HashSet::new();
+
+    while let Some(next) = ready.iter().next().cloned() {
+        ready.remove(&next);
+        let (_, name, idx) = next;
+        order.push(idx);
+        placed.insert(idx);
+        for (i, m) in manifests.iter().enumerate() {
+            let count = m.depends_on.iter().filter(|d| d.as_str() == name).count();
+            if count > 0 {
+                remaining_deps[i] -= count;
+                if remaining_deps[i] == 0 {
+                    ready.insert(key(i));
+                }
+            }
+        }
+    }
+
+    // anything left over is part of a cycle, or depends on a rejected mod
+    for i in 0..manifests.len() {
+        if placed.contains(&i) || rejected.contains_key(&i) {
+            continue;
+        }
+        if let Some(cycle) = find_cycle(manifests, &by_name, &placed, i) {
+            if cycle[0] == manifests[i].name {
+                rejected.insert(i, format!("dependency cycle: {}", cycle.join(" -> ")));
+            }
+        }
+    }
+    loop {
+        let mut newly_rejected = Vec::new();
+        for i in (0..manifests.len()).filter(|i| !rejected.contains_key(i)) {
+            let failed_dep = manifests[i]
+                .depends_on
+                .iter()
+                .find(|d| rejected.contains_key(&by_name[d.as_str()]));
+            if let Some(dep) = failed_dep {
+                newly_rejected.push((i, format!("depends on rejected mod '{}'", dep)));
+            }
+        }
+        if newly_rejected.is_empty() {
+            break;
+        }
+        rejected.extend(newly_rejected);
+    }
+
+    let mut rejected = rejected.into_iter().collect::<Vec<_>>();
+    rejected.sort();
+    DependencyOrder {
+        order: order
+            .into_iter()
+            .filter(|i| !rejected.iter().any(|(r, _)| r == i))
+            .collect(),
+        rejected,
+    }
+}
+
+///
+/// Names of every mod that depends on one of `names`, directly or transitively
+///
+pub fn dependents_of(manifests: &[&ModManifest], names: &HashSet<String>) -> HashSet<String> {
+    let mut affected = HashSet::new();
+    loop {
+        let before = affected.len();
+        for m in manifests {
+            let depends = m
+                .depends_on
+                .iter()
+                .any(|d| names.contains(d) || affected.contains(d));
+            if depends {
+                affected.insert(m.name.clone());
+            }
+        }
+        if affected.len() == before {
+            return affected;
+        }
+    }
+}
+
+// Follow unplaced dependencies from `start` until a mod repeats, returning the cycle by name
+fn find_cycle(
+    manifests: &[&ModManifest],
+    by_name: &HashMap<&str, usize>,
+    placed: &HashSet<usize>,
+    start: usize,
+) -> Option<Vec<String>> {
+    let mut path = vec![start];
+    let mut current = start;
+    loop {
+        let next = manifests[current]
+            .depends_on
+            .iter()
+            .filter_map(|d| by_name.get(d.as_str()).cloned())
+            .find(|i| !placed.contains(i))?;
+        if let Some(pos) = path.iter().position(|&i| i == next) {
+            let mut cycle = path[pos..]
+                .iter()
+                .map(|&i| manifests[i].name.clone())
+                .collect::<Vec<_>>();
+            cycle.push(manifests[next].name.clone());
+            return Some(cycle);
+        }
+        path.push(next);
+        current = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(name: &str, load_order: i32, depends_on: &[&str]) -> ModManifest {
+        let mut m = ModManifest::parse(&format!("name = \"{}\"", name)).unwrap();
+        m.load_order = load_order;
+        m.depends_on = depends_on.iter().map(|d| d.to_string()).collect();
+        m
+    }
+
+    fn names(manifests: &[&ModManifest], order: &[usize]) -> Vec<String> {
+        order.iter().map(|&i| manifests[i].name.clone()).collect()
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let rendering = manifest("rendering", 0, &["asset_loader"]);
+        let input = manifest("input", 0, &[]);
+        let asset_loader = manifest("asset_loader", 10, &[]);
+        let manifests = vec![&rendering, &input, &asset_loader];
+
+        let resolved = resolve(&manifests);
+        assert!(resolved.rejected.is_empty());
+        assert_eq!(
+            names(&manifests, &resolved.order),
+            vec!["input", "asset_loader", "rendering"]
+        );
+    }
+
+    #[test]
+    fn rejects_cycles_and_their_dependents() {
+        let a = manifest("a", 0, &["b"]);
+        let b = manifest("b", 0, &["a"]);
+        let c = manifest("c", 0, &["a"]);
+        let d = manifest("d", 0, &[]);
+        let manifests = vec![&a, &b, &c, &d];
+
+        let resolved = resolve(&manifests);
+        assert_eq!(names(&manifests, &resolved.order), vec!["d"]);
+        assert_eq!(resolved.rejected.len(), 3);
+        assert_eq!(resolved.rejected[0].1, "dependency cycle: a -> b -> a");
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies_transitively() {
+        let a = manifest("a", 0, &["missing"]);
+        let b = manifest("b", 0, &["a"]);
+        let manifests = vec![&a, &b];
+
+        let resolved = resolve(&manifests);
+        assert!(resolved.order.is_empty());
+        assert_eq!(resolved.rejected[0].1, "depends on unknown mod 'missing'");
+        assert_eq!(resolved.rejected[1].1, "depends on rejected mod 'a'");
+    }
+
+    #[test]
+    fn finds_transitive_dependents() {
+        let a = manifest("a", 0, &[]);
+        let b = manifest("b", 0, &["a"]);
+        let c = manifest("c", 0, &["b"]);
+        let d = manifest("d", 0, &[]);
+        let manifests = vec![&a, &b, &c, &d];
+
+        let changed = vec!["a".to_string()].into_iter().collect();
+        let affected = dependents_of(&manifests, &changed);
+        assert_eq!(affected.len(), 2);
+        assert!(affected.contains("b") && affected.contains("c"));
+    }
+}
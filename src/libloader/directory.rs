@@ -0,0 +1,437 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use game_state::logging::Logger;
+use game_state::state::{self, LogAccess};
+
+use super::dependency;
+use super::manifest::{ModManifest, TickKind, MANIFEST_FILENAME};
+use super::{LibLoader, LOG_TARGET};
+
+///
+/// ModDirectory - watches one or more directories for mod manifests
+///
+/// Each subdirectory containing a `mod.toml` defines a mod. Every call to `scan()` picks up new
+/// manifests, re-reads changed ones, unloads mods whose manifest has disappeared and finally
+/// checks each library for an update, so mods can be added and removed while running.
+///
+/// Mods are kept in dependency order (see `dependency::resolve`), which is the order they are
+/// loaded and updated in. Mods with missing or cyclic dependencies are reported and not loaded.
+///
+/// With a mod filter set, only the named mods are loaded - other manifests are ignored. Excluded
+/// mods are never loaded.
+///
+pub struct ModDirectory {
+    paths: Vec<PathBuf>,
+    mods: Vec<ModEntry>,
+    filter: Option<HashSet<String>>,
+    excluded: HashSet<String>,
+    filtered: HashMap<PathBuf, Duration>, // manifests left out by the filter, when last read
+    duplicates: HashMap<PathBuf, Duration>, // manifests naming a mod already defined, when last read
+}
+
+struct ModEntry {
+    manifest: ModManifest,
+    manifest_path: PathBuf,
+    manifest_modified: Duration,
+    loader: LibLoader,
+    rejected: Option<String>, // reason this mod can't be loaded, if any
+}
+
+impl ModDirectory {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ModDirectory {
+            paths: vec![path.as_ref().to_path_buf()],
+            mods: Vec::new(),
+            filter: None,
+            excluded: HashSet::new(),
+            filtered: HashMap::new(),
+            duplicates: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Also watch `path` - when two directories define a mod of the same name, the manifest
+    /// found first is used
+    ///
+    pub fn add_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.paths.push(path.as_ref().to_path_buf());
+    }
+
+    ///
+    /// Returns the directories being watched
+    ///
+    pub fn get_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    ///
+    /// Only load the named mods, or every mod found with None. Mods that are already loaded
+    /// and no longer pass the filter are unloaded on the next scan.
+    ///
+    pub fn set_filter(&mut self, names: Option<Vec<String>>) {
+        self.filter = names.map(|names| names.into_iter().collect());
+        self.reread_manifests();
+    }
+
+    ///
+    /// Never load the named mods, whether or not they pass the filter. Mods that are already
+    /// loaded and are now excluded are unloaded on the next scan.
+    ///
+    pub fn set_excluded(&mut self, names: Vec<String>) {
+        self.excluded = names.into_iter().collect();
+        self.reread_manifests();
+    }
+
+    // have the next scan read every manifest again
+    fn reread_manifests(&mut self) {
+        self.filtered.clear();
+        self.duplicates.clear();
+        for entry in self.mods.iter_mut() {
+            entry.manifest_modified = Duration::from_millis(0);
+        }
+    }
+
+    fn passes_filter(&self, name: &str) -> bool {
+        !self.excluded.contains(name)
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|names| names.contains(name))
+    }
+
+    ///
+    /// Loaded mods, in update order
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = &LibLoader> {
+        self.mods
+            .iter()
+            .filter(|m| m.rejected.is_none())
+            .map(|m| &m.loader)
+    }
+
+    ///
+    /// Loaded mods, in update order, for updating
+    ///
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut LibLoader> {
+        self.mods
+            .iter_mut()
+            .filter(|m| m.rejected.is_none())
+            .map(|m| &mut m.loader)
+    }
+
+    ///
+    /// Loaded mods updated at the given rate, in update order
+    ///
+    pub fn iter_mut_by_tick(&mut self, tick: TickKind) -> impl Iterator<Item = &mut LibLoader> {
+        self.mods
+            .iter_mut()
+            .filter(move |m| m.rejected.is_none() && m.manifest.tick == tick)
+            .map(|m| &mut m.loader)
+    }
+
+    ///
+    /// Manifests of all known mods, in update order
+    ///
+    pub fn manifests(&self) -> impl Iterator<Item = &ModManifest> {
+        self.mods.iter().map(|m| &m.manifest)
+    }
+
+    ///
+    /// Synchronize with the mods directory:
+    /// - read new or changed manifests
+    /// - unload mods whose manifest was removed
+    /// - check every mod's library for an update
+    ///
+    /// Before a mod is reloaded or removed, every mod depending on it is unloaded (dependents
+    /// first), and afterwards they are loaded again in dependency order.
+    ///
+    pub fn scan(&mut self, state: &mut state::State) {
+        let mut found = self.find_manifests(state.get_logger());
+
+        // read new and changed manifests
+        let mut changed_manifests = Vec::new();
+        for (path, modified) in found.iter() {
+            let existing = self.mods.iter().position(|m| m.manifest_path == *path);
+            if let Some(idx) = existing {
+                if self.mods[idx].manifest_modified == *modified {
+                    continue;
+                }
+            }
+            if self.filtered.get(path) == Some(modified)
+                || self.duplicates.get(path) == Some(modified)
+            {
+                continue;
+            }
+            match ModManifest::load(path) {
+                Ok(ref manifest) if !self.passes_filter(&manifest.name) => {
+                    self.filtered.insert(path.clone(), *modified);
+                }
+                Ok(manifest) => {
+                    self.filtered.remove(path);
+                    changed_manifests.push((path.clone(), *modified, manifest));
+                }
+                Err(err) => state.get_logger().error(
+                    LOG_TARGET,
+                    format!("unable to read mod manifest {:?} - err: {}", path, err),
+                ),
+            }
+        }
+
+        // filtered out mods are treated as removed
+        found.retain(|(path, _)| !self.filtered.contains_key(path));
+
+        // everything that is about to be dropped, replaced or reloaded
+        let mut changing = HashSet::new();
+        for entry in self.mods.iter() {
+            let removed = !found.iter().any(|(path, _)| *path == entry.manifest_path);
+            let replaced = changed_manifests.iter().any(|(path, _, manifest)| {
+                *path == entry.manifest_path
+                    && (manifest.name != entry.manifest.name
+                        || manifest.library_path() != entry.manifest.library_path())
+            });
+            if removed || replaced || entry.loader.has_update() {
+                changing.insert(entry.manifest.name.clone());
+            }
+        }
+
+        // unload dependents before the mods they depend on change underneath them
+        self.unload_with_dependents(&changing, state);
+
+        // drop mods whose manifest is gone
+        let mut i = 0;
+        while i < self.mods.len() {
+            if found
+                .iter()
+                .any(|(path, _)| *path == self.mods[i].manifest_path)
+            {
+                i += 1;
+            } else {
+                let mut entry = self.mods.remove(i);
+                // a manifest ignored as a duplicate of this mod may be used now
+                self.duplicates.clear();
+                state.get_logger().info(
+                    LOG_TARGET,
+                    format!("mod manifest removed: {:?}", entry.manifest_path),
+                );
+                entry.loader.release(state);
+            }
+        }
+
+        for (path, modified, manifest) in changed_manifests {
+            let duplicate = self
+                .mods
+                .iter()
+                .any(|m| m.manifest_path != path && m.manifest.name == manifest.name);
+            if duplicate {
+                // warned about once, until the manifest changes
+                self.duplicates.insert(path.clone(), modified);
+                state.get_logger().warn(
+                    LOG_TARGET,
+                    format!(
+                        "ignoring mod manifest {:?} - a mod named {} is already defined",
+                        path, manifest.name
+                    ),
+                );
+                continue;
+            }
+
+            let renamed = self
+                .mods
+                .iter()
+                .any(|m| m.manifest_path == path && m.manifest.name != manifest.name);
+            if renamed {
+                // the old name may be taken by a manifest ignored as a duplicate of it
+                self.duplicates.clear();
+            }
+
+            match self.mods.iter().position(|m| m.manifest_path == path) {
+                Some(idx) => {
+                    let entry = &mut self.mods[idx];
+                    entry.manifest_modified = modified;
+                    if entry.manifest.name != manifest.name
+                        || entry.manifest.library_path() != manifest.library_path()
+                    {
+                        // a different library now - drop the old one, check_update loads the new
+                        entry.loader.release(state);
+                        entry.loader = Self::create_loader(&manifest);
+                    }
+                    entry.manifest = manifest;
+                }
+                None => {
+                    let loader = Self::create_loader(&manifest);
+                    self.mods.push(ModEntry {
+                        manifest,
+                        manifest_path: path,
+                        manifest_modified: modified,
+                        loader,
+                        rejected: None,
+                    });
+                }
+            }
+        }
+
+        self.sort_by_dependencies(state);
+        self.load_ready(state);
+    }
+
+    ///
+    /// Find a mod by name, to inspect it or enable it
+    ///
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut LibLoader> {
+        self.mods
+            .iter_mut()
+            .find(|m| m.manifest.name == name)
+            .map(|m| &mut m.loader)
+    }
+
+    ///
+    /// Unload a mod along with everything depending on it, and keep it unloaded until enabled
+    ///
+    pub fn disable(&mut self, name: &str, state: &mut state::State) {
+        self.unload_with_dependents(&Self::names(name), state);
+        if let Some(loader) = self.get_mut(name) {
+            loader.disable(state);
+        }
+    }
+
+    ///
+    /// Load a disabled mod again, along with the mods depending on it
+    ///
+    pub fn enable(&mut self, name: &str, state: &mut state::State) {
+        if let Some(loader) = self.get_mut(name) {
+            loader.enable();
+        }
+        self.load_ready(state);
+    }
+
+    ///
+    /// Reload a faulted mod without waiting for a new build, reloading its dependents around it
+    ///
+    pub fn retry(&mut self, name: &str, state: &mut state::State) {
+        self.unload_with_dependents(&Self::names(name), state);
+        if let Some(loader) = self.get_mut(name) {
+            loader.retry(state);
+        }
+        self.load_ready(state);
+    }
+
+    fn names(name: &str) -> HashSet<String> {
+        vec![name.to_string()].into_iter().collect()
+    }
+
+    // Unload the given mods and every mod depending on them, dependents first
+    fn unload_with_dependents(&mut self, names: &HashSet<String>, state: &mut state::State) {
+        if names.is_empty() {
+            return;
+        }
+        let manifests = self.mods.iter().map(|m| &m.manifest).collect::<Vec<_>>();
+        let mut affected = dependency::dependents_of(&manifests, names);
+        affected.extend(names.iter().cloned());
+        for entry in self.mods.iter_mut().rev() {
+            if affected.contains(&entry.manifest.name) {
+                entry.loader.unload(state);
+            }
+        }
+    }
+
+    // (Re)load in dependency order, only once everything a mod depends on is active
+    fn load_ready(&mut self, state: &mut state::State) {
+        for i in 0..self.mods.len() {
+            if self.mods[i].rejected.is_some() {
+                continue;
+            }
+            let dependencies_active = self.mods[i].manifest.depends_on.iter().all(|dep| {
+                self.mods
+                    .iter()
+                    .any(|m| m.manifest.name == *dep && m.loader.is_active())
+            });
+            let loader = &mut self.mods[i].loader;
+            if dependencies_active {
+                loader.check_update(state);
+            } else {
+                loader.unload(state);
+            }
+        }
+    }
+
+    // Order mods by their dependencies, releasing any that can't be satisfied
+    fn sort_by_dependencies(&mut self, state: &mut state::State) {
+        let resolved = {
+            let manifests = self.mods.iter().map(|m| &m.manifest).collect::<Vec<_>>();
+            dependency::resolve(&manifests)
+        };
+
+        let mut rejected = resolved.rejected;
+        for (idx, reason) in rejected.iter().rev() {
+            let entry = &mut self.mods[*idx];
+            if entry.rejected.as_ref() != Some(reason) {
+                state.get_logger().error(
+                    LOG_TARGET,
+                    format!("unable to load mod {} - {}", entry.manifest.name, reason),
+                );
+            }
+            entry.loader.release(state);
+        }
+
+        let mut slots = self.mods.drain(..).map(Some).collect::<Vec<_>>();
+        for idx in resolved.order {
+            let mut entry = slots[idx].take().unwrap();
+            entry.rejected = None;
+            self.mods.push(entry);
+        }
+        for (idx, reason) in rejected.drain(..) {
+            let mut entry = slots[idx].take().unwrap();
+            entry.rejected = Some(reason);
+            self.mods.push(entry);
+        }
+    }
+
+    ///
+    /// Release every mod, in reverse update order
+    ///
+    pub fn unload_all(&mut self, state: &mut state::State) {
+        while let Some(mut entry) = self.mods.pop() {
+            entry.loader.release(state);
+        }
+    }
+
+    fn create_loader(manifest: &ModManifest) -> LibLoader {
+        let library = manifest.library_path();
+        LibLoader::new(&library.to_string_lossy(), &manifest.name)
+    }
+
+    // Collect (manifest path, last modified) for every mod subdirectory, directory by directory
+    fn find_manifests(&self, logger: &Logger) -> Vec<(PathBuf, Duration)> {
+        let mut found = Vec::new();
+        for path in self.paths.iter() {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    logger.error(
+                        LOG_TARGET,
+                        format!("unable to read mods directory {:?} - err: {}", path, err),
+                    );
+                    continue;
+                }
+            };
+
+            let mut in_path = Vec::new();
+            for entry in entries.filter_map(Result::ok) {
+                let manifest_path = entry.path().join(MANIFEST_FILENAME);
+                let modified = fs::metadata(&manifest_path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+                if let Some(modified) = modified {
+                    in_path.push((manifest_path, modified));
+                }
+            }
+            in_path.sort();
+            found.extend(in_path);
+        }
+        found
+    }
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+///
+/// Name of the manifest file expected in each mod's directory
+///
+pub const MANIFEST_FILENAME: &str = "mod.toml";
+
+///
+/// ModManifest - the runtime definition of a mod, read from a `mod.toml`
+///
+/// Example:
+///
+/// name = "input"
+/// library = "target/{profile}/deps/{prefix}mod_input{suffix}"
+/// load_order = 40
+/// depends_on = ["asset_loader"]
+/// access = ["InputAccess", "WindowAccess", "WorldAccess"]
+/// tick = "frame"
+///
+/// `library` is resolved relative to the directory containing the manifest, and may use the
+/// `{profile}`, `{prefix}` and `{suffix}` placeholders. When omitted, it defaults to the path
+/// `load_mod!` would use for a mod of the same name.
+///
+/// Mods listed in `depends_on` are always loaded before, and unloaded after, this mod.
+/// `load_order` only breaks ties between mods that don't depend on each other.
+///
+/// `tick` is "fixed" for mods updated at the runner's fixed rate (simulation), or "frame" - the
+/// default - for mods updated once per frame (input, rendering).
+///
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModManifest {
+    pub name: String,
+    #[serde(default)]
+    pub library: Option<String>,
+    #[serde(default)]
+    pub load_order: i32,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub access: Vec<String>,
+    #[serde(default)]
+    pub tick: TickKind,
+
+    // Directory the manifest was read from
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+///
+/// How often a mod is updated
+///
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TickKind {
+    Fixed,
+    #[default]
+    Frame,
+}
+
+impl ModManifest {
+    ///
+    /// Read and parse a manifest file
+    ///
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let mut manifest = Self::parse(&contents)?;
+        manifest.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(manifest)
+    }
+
+    ///
+    /// Parse a manifest from a string, without any directory information
+    ///
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let manifest: ModManifest = toml::from_str(contents)?;
+        if manifest.name.is_empty() {
+            return Err("mod manifest must define a non-empty name".into());
+        }
+        Ok(manifest)
+    }
+
+    ///
+    /// Path to the shared library this manifest describes
+    ///
+    pub fn library_path(&self) -> PathBuf {
+        let library = match self.library {
+            Some(ref library) => library.clone(),
+            None if cfg!(windows) => format!("target/{{profile}}/mod_{}.dll", self.name),
+            None => format!("target/{{profile}}/deps/libmod_{}.so", self.name),
+        };
+        let library = library
+            .replace("{profile}", build_profile())
+            .replace("{prefix}", std::env::consts::DLL_PREFIX)
+            .replace("{suffix}", std::env::consts::DLL_SUFFIX);
+        self.dir.join(library)
+    }
+}
+
+#[cfg(debug_assertions)]
+fn build_profile() -> &'static str {
+    "debug"
+}
+
+#[cfg(not(debug_assertions))]
+fn build_profile() -> &'static str {
+    "release"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_manifest() {
+        let manifest = ModManifest::parse(
+            r#"
+            name = "input"
+            library = "target/{profile}/deps/{prefix}mod_input{suffix}"
+            load_order = 40
+            access = ["InputAccess", "WorldAccess"]
+            tick = "fixed"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "input");
+        assert_eq!(manifest.load_order, 40);
+        assert_eq!(manifest.access, vec!["InputAccess", "WorldAccess"]);
+        assert_eq!(manifest.tick, TickKind::Fixed);
+
+        let path = manifest.library_path();
+        let path = path.to_str().unwrap();
+        assert!(path.starts_with(&format!("target/{}/deps/", build_profile())));
+        assert!(path.ends_with(&format!("mod_input{}", std::env::consts::DLL_SUFFIX)));
+    }
+
+    #[test]
+    fn defaults_library_path_from_name() {
+        let mut manifest = ModManifest::parse(r#"name = "simulation""#).unwrap();
+        manifest.dir = PathBuf::from("mod_simulation");
+        assert_eq!(manifest.load_order, 0);
+        assert!(manifest.access.is_empty());
+        assert_eq!(manifest.tick, TickKind::Frame);
+        assert!(manifest
+            .library_path()
+            .starts_with(Path::new("mod_simulation/target")));
+    }
+
+    #[test]
+    fn rejects_empty_name() {
+        assert!(ModManifest::parse(r#"name = """#).is_err());
+        assert!(ModManifest::parse(r#"load_order = 1"#).is_err());
+    }
+}
@@ -1,12 +1,25 @@
+use std::any::Any;
 use std::fs;
 use std::io::Error;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
+use std::thread;
 use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use ansi_term::Color::{Cyan, Green, Yellow};
-use game_state::state;
+use game_state::abi::{self, ModAbi, ModResult, ModStatus};
+use game_state::state::{self, JobAccess, LogAccess};
 use libloading::{Library, Symbol};
 
+mod dependency;
+mod directory;
+mod manifest;
+
+pub use self::directory::ModDirectory;
+pub use self::manifest::{ModManifest, TickKind, MANIFEST_FILENAME};
+
+// log target for everything reported by the loader
+const LOG_TARGET: &str = "libloader";
+
 ///
 /// TODO:
 ///     Add async futures layer over this - allowing module calls to be composed
@@ -14,14 +27,30 @@ use libloading::{Library, Symbol};
 ///
 ///     (*)- Perhaps load modules into an evmap for lock-free concurrency?
 ///
-/// TODO: support a dynamically *defined* and dynamically loaded lib
-/// --> Load module definitions at runtime, even watch a mod folder and load them based on a def
-///
 /// Mods support:
 ///
 /// Mods need to be named mod_<mod-name>, and must be unique.
-/// Each mod defines a set of extern "C" functions that are called
-/// at specific lifecycle points.
+/// Each mod defines a set of extern "C-unwind" functions that are called
+/// at specific lifecycle points, and exports mod_<mod-name>_abi so the
+/// host can verify it was built against the same game_state.
+///
+/// A mod may also export mod_<mod-name>_save_state and mod_<mod-name>_restore_state to carry
+/// its private state across a reload: the blob saved before "unload" is handed back to the
+/// next "load", typically to a newly built version of the mod.
+///
+/// Lifecycle functions may instead be exported as mod_<mod-name>_<event>_v2, returning a
+/// game_state::abi::ModResult so the host learns when a mod fails. v1 functions returning ()
+/// are still supported.
+///
+/// Background jobs a mod spawned through JobAccess are cancelled when it is unloaded or
+/// faulted, before its library can be dropped.
+///
+/// Lifecycle calls are made behind a panic barrier: a mod that panics, or reports an error, is
+/// marked as faulted and skipped until a new build of its library is loaded, or the host
+/// retries it.
+///
+/// Mods can be loaded explicitly with `load_mod!`, or defined at runtime by a `mod.toml`
+/// manifest in a watched directory (see `ModDirectory`).
 ///
 /// Usage:
 /// let a_mod = load_mod!(modnamehere);
@@ -31,6 +60,14 @@ use libloading::{Library, Symbol};
 ///     a_mod.tick(&mut s);
 /// }
 ///
+/// let mut mods = ModDirectory::new(".");
+/// loop {
+///     mods.scan(&mut s);
+///     for m in mods.iter() {
+///         m.update(&mut s, &dt);
+///     }
+/// }
+///
 
 ///
 /// Macro for loading platform-specific shared lib (dll/so)
@@ -76,6 +113,12 @@ pub struct LibLoader {
     version: u64, // Keep track of how many times we've loaded,
     // as we use this in the filename for the temp copy
     mod_name: String,
+    active: bool,               // "load" has been called, without a matching "unload"
+    refused: Option<Duration>,  // modified time of a library that failed to open or the ABI check
+    faulted: Option<String>,    // why the loaded library was halted, after a panic or an error
+    last_error: Option<String>, // most recent error reported by, or about, the mod
+    disabled: bool,             // the host asked for the mod not to be loaded
+    saved: Option<Vec<u8>>,     // state saved by the mod on "unload", restored on the next "load"
 }
 
 impl LibLoader {
@@ -97,69 +140,207 @@ impl LibLoader {
             version: 0,
             mod_name: mod_name.to_string(),
             modified,
+            active: false,
+            refused: None,
+            faulted: None,
+            last_error: None,
+            disabled: false,
+            saved: None,
+        }
+    }
+
+    ///
+    /// Returns true if a library is currently loaded
+    ///
+    pub fn is_loaded(&self) -> bool {
+        self.lib.is_some()
+    }
+
+    ///
+    /// Returns true if the mod has been sent "load", and not yet "unload"
+    ///
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    ///
+    /// Returns the reason the currently loaded library was halted, if it panicked or returned
+    /// an error. A faulted mod is not updated again until a newer build is loaded, or retry()
+    ///
+    pub fn get_fault(&self) -> Option<&str> {
+        self.faulted.as_deref()
+    }
+
+    ///
+    /// Returns the most recent error - a panic, an error or retry returned by a v2 lifecycle
+    /// call, or a library refused by the ABI check
+    ///
+    pub fn get_last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    ///
+    /// Returns true if the host has disabled the mod
+    ///
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    ///
+    /// Unload the mod, and keep it unloaded until enable() is called.
+    /// The library stays loaded, and is still checked for updates.
+    ///
+    pub fn disable(&mut self, state: &mut state::State) {
+        self.unload(state);
+        self.disabled = true;
+    }
+
+    ///
+    /// Allow a disabled mod to be loaded again, by the next check_update()
+    ///
+    pub fn enable(&mut self) {
+        self.disabled = false;
+    }
+
+    ///
+    /// Unload a faulted mod and clear the fault, so the next check_update() loads it again
+    /// without waiting for a new build
+    ///
+    pub fn retry(&mut self, state: &mut state::State) {
+        self.unload(state);
+        self.faulted = None;
+    }
+
+    ///
+    /// Returns true if check_update() would (re)load or drop the library
+    ///
+    pub fn has_update(&self) -> bool {
+        let modified = fs::metadata(&self.filename)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        match modified {
+            Some(modified) => {
+                (self.lib.is_none() && self.refused != Some(modified)) || self.modified != modified
+            }
+            None => self.lib.is_some(),
         }
     }
 
+    ///
+    /// Call the "unload" lifecycle event if active, and drop the library along with any saved
+    /// state. A later call to check_update() will load it again.
+    ///
+    pub fn release(&mut self, state: &mut state::State) {
+        self.unload(state);
+        self.lib = None;
+        self.faulted = None;
+        self.saved = None;
+        self.modified = Duration::from_millis(0);
+    }
+
     ///
     /// Check for an update of the lib on disk.
     /// If there has been a change:
     /// - copy it to the tmp directory
-    /// - call "unload" lifecycle event on the current mod if there is one
-    /// - load the new library
-    /// - call "load" lifecycle event on the newly loaded library, passing &mut State
+    /// - open it, and verify it was built against the same game_state as the host
+    /// - save the current mod's state, and call its "unload" lifecycle event if there is one
+    /// - swap in the new library
+    /// - call "load" lifecycle event on the newly loaded library, passing &mut State,
+    ///   and hand it the saved state
+    ///
+    /// Otherwise, the mod is loaded if it isn't active, unless it is disabled or faulted.
     ///
     pub fn check_update(&mut self, state: &mut state::State) {
         let source = Path::new(&self.filename);
-        let file_stem = source.file_stem().unwrap().to_str().unwrap();
-
-        match fs::metadata(&source) {
-            Ok(new_meta) => {
-                let modified = new_meta
-                    .modified()
-                    .expect("Unable to retrieve last modified date.");
-
-                let duration: Duration = modified
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Unable to get time.");
+        let file_stem = match source.file_stem().and_then(|stem| stem.to_str()) {
+            Some(file_stem) => file_stem.to_string(),
+            None => {
+                state.get_logger().error(
+                    LOG_TARGET,
+                    format!("{:?} is not a library file name", source),
+                );
+                return;
+            }
+        };
 
-                if self.lib.is_none() || self.modified != duration {
+        let modified = fs::metadata(source).map(|meta| {
+            meta.modified()
+                .map_err(|err| err.to_string())
+                .and_then(|modified| {
+                    modified
+                        .duration_since(UNIX_EPOCH)
+                        .map_err(|err| err.to_string())
+                })
+        });
+        match modified {
+            Ok(Err(err)) => state.get_logger().error(
+                LOG_TARGET,
+                format!(
+                    "unable to read the modification time of {} - {}",
+                    self.filename, err
+                ),
+            ),
+            Ok(Ok(duration)) => {
+                let retry = self.lib.is_none() && self.refused != Some(duration);
+                if retry || self.modified != duration {
                     self.modified = duration;
                     let new_filename = format!("target/{}_{}.so", file_stem, self.version);
 
-                    match fs::copy(&source, Path::new(&new_filename)) {
-                        Ok(_) => {
-                            if self.lib.is_some() {
-                                self.unload(state);
-                            }
-                            match Library::new(&new_filename) {
-                                Ok(lib) => {
+                    match fs::copy(source, Path::new(&new_filename)) {
+                        Ok(_) => match Library::new(&new_filename) {
+                            Ok(lib) => match self.check_abi(&lib) {
+                                Ok(()) => {
+                                    self.unload(state);
                                     self.version += 1;
                                     self.lib = Some(lib);
+                                    self.refused = None;
+                                    self.faulted = None;
                                     self.load(state);
                                 }
-                                Err(err) => println!(
-                                    "Unable to open new library: {} - err: {}",
-                                    new_filename, err
-                                ),
+                                Err(err) => {
+                                    self.refused = Some(duration);
+                                    state.get_logger().error(LOG_TARGET, err.as_str());
+                                    self.last_error = Some(err);
+                                }
+                            },
+                            Err(err) => {
+                                // like a refused library, not retried until it changes
+                                self.refused = Some(duration);
+                                state.get_logger().error(
+                                    LOG_TARGET,
+                                    format!(
+                                        "Unable to open new library: {} - err: {}",
+                                        new_filename, err
+                                    ),
+                                );
                             }
-                        }
-                        Err(err) => println!(
-                            "Error copying file, target: {} - err: {}",
-                            new_filename, err
+                        },
+                        Err(err) => state.get_logger().error(
+                            LOG_TARGET,
+                            format!(
+                                "Error copying file, target: {} - err: {}",
+                                new_filename, err
+                            ),
                         ),
                     }
                 }
             }
             Err(err) => {
                 // reset our state to allow any new lib to be loaded if one appears
-                if self.lib.is_some() {
-                    self.unload(state);
-                }
+                self.unload(state);
                 self.lib = None;
+                self.faulted = None;
+                self.saved = None;
                 self.modified = Duration::from_millis(0);
-                println!("unable to stat file! {}", err);
+                state
+                    .get_logger()
+                    .warn(LOG_TARGET, format!("unable to stat file! {}", err));
             }
         }
+
+        // a mod that was enabled or retried, or asked to retry its "load", is loaded here
+        self.load(state);
     }
 
     ///
@@ -167,34 +348,168 @@ impl LibLoader {
     ///
     /// Call to the mod to update the state with the "update" normative lifecycle event
     ///
-    pub fn update(&self, state: &mut state::State, delta_time: &Duration) -> Duration {
-        let method_name = format!("mod_{}_update", self.mod_name);
+    pub fn update(&mut self, state: &mut state::State, delta_time: &Duration) -> Duration {
+        if !self.active || self.faulted.is_some() {
+            return Duration::from_millis(0);
+        }
         // todo:
         let start_time = Instant::now();
-        self.call_update(&method_name, state, delta_time);
+        match self.call_update(state, delta_time) {
+            Ok(()) => {}
+            Err(CallError::Retry(message)) => {
+                state.get_logger().debug(
+                    LOG_TARGET,
+                    format!("{} skipped an update: {}", self.mod_name, message),
+                );
+                self.last_error = Some(message);
+            }
+            Err(CallError::Failed(message)) => self.fault(state, "Failed", message),
+            Err(CallError::Panicked(message)) => self.fault(state, "Faulted", message),
+        }
         start_time.elapsed()
     }
 
     ///
     /// load()
     ///
-    /// Trigger the "load" lifecycle event
+    /// Trigger the "load" lifecycle event, if a library is loaded and not already active,
+    /// then restore any state saved by the previous "unload"
     ///
-    fn load(&self, state: &mut state::State) {
-        let method_name = format!("mod_{}_load", self.mod_name);
-        self.message("Loaded");
-        self.call(&method_name, state);
+    fn load(&mut self, state: &mut state::State) {
+        if self.lib.is_none() || self.active || self.disabled || self.faulted.is_some() {
+            return;
+        }
+        self.message(state, "Loaded");
+        // even a panicking load may have touched State, so stay active to be sent "unload"
+        self.active = true;
+        match self.call(state, "load") {
+            Ok(()) => {}
+            Err(CallError::Retry(message)) => {
+                // the mod cleaned up after itself - the next scan tries again
+                self.active = false;
+                state.get_logger().warn(
+                    LOG_TARGET,
+                    format!("{} not loaded, will retry: {}", self.mod_name, message),
+                );
+                self.last_error = Some(message);
+                return;
+            }
+            Err(CallError::Failed(message)) => {
+                self.active = false;
+                self.fault(state, "Failed", message);
+                return;
+            }
+            Err(CallError::Panicked(message)) => {
+                self.fault(state, "Faulted", message);
+                return;
+            }
+        }
+        if let Some(saved) = self.saved.take() {
+            if let Err(fault) = self.call_restore_state(state, &saved) {
+                self.fault(state, "Faulted", fault);
+            }
+        }
     }
 
     ///
     /// unload()
     ///
-    /// Trigger the unload lifecycle event
+    /// Save the mod's state and trigger the unload lifecycle event, if active.
+    /// The library itself stays loaded.
     ///
-    fn unload(&self, state: &mut state::State) {
-        let method_name = format!("mod_{}_unload", self.mod_name);
-        self.message("Unloaded");
-        self.call(&method_name, state);
+    fn unload(&mut self, state: &mut state::State) {
+        if !self.active {
+            self.cancel_jobs(state);
+            return;
+        }
+        // the state of a faulted mod can't be trusted, so it starts over
+        self.saved = None;
+        if self.faulted.is_none() {
+            match self.call_save_state(state) {
+                Ok(saved) => self.saved = saved,
+                Err(fault) => self.fault(state, "Faulted", fault),
+            }
+        }
+        self.message(state, "Unloaded");
+        self.active = false;
+        match self.call(state, "unload") {
+            Ok(()) => {}
+            Err(CallError::Retry(message)) | Err(CallError::Failed(message)) => {
+                state.get_logger().error(
+                    LOG_TARGET,
+                    format!("{} failed to unload cleanly: {}", self.mod_name, message),
+                );
+                self.last_error = Some(message);
+            }
+            Err(CallError::Panicked(message)) => self.fault(state, "Faulted", message),
+        }
+        self.cancel_jobs(state);
+    }
+
+    // Jobs run code from the mod's library, so none may outlive it
+    fn cancel_jobs(&self, state: &state::State) {
+        let cancelled = state.get_jobs().cancel(&self.mod_name);
+        if cancelled > 0 {
+            state.get_logger().debug(
+                LOG_TARGET,
+                format!("cancelled {} jobs of {}", cancelled, self.mod_name),
+            );
+        }
+    }
+
+    ///
+    /// fault()
+    ///
+    /// Halt the loaded library after a panic or a failure it reported, so it is skipped until
+    /// a new build is loaded or the host retries it
+    ///
+    fn fault(&mut self, state: &state::State, label: &str, message: String) {
+        state.get_logger().error(
+            LOG_TARGET,
+            format!("[{} {}: {}]", label, self.mod_name, message),
+        );
+        self.last_error = Some(message.clone());
+        self.faulted = Some(message);
+        self.cancel_jobs(state);
+    }
+
+    ///
+    /// check_abi()
+    ///
+    /// Verify a newly opened library exports mod_<name>_abi, matching the host's game_state.
+    /// Calling into a mod built against a different State layout is undefined behavior.
+    ///
+    fn check_abi(&self, lib: &Library) -> Result<(), String> {
+        let method_name = format!("mod_{}_abi", self.mod_name);
+        let host_abi = ModAbi::current();
+        let mod_abi = unsafe {
+            let maybe_func: Result<Symbol<unsafe extern "C" fn() -> ModAbi>, Error> =
+                lib.get(method_name.as_bytes());
+            match maybe_func {
+                Ok(func) => func(),
+                Err(_) => {
+                    return Err(format!(
+                        "Refusing to load {}: it does not export {}. \
+                         Add it (see game_state::abi::ModAbi) and rebuild the mod.",
+                        self.filename, method_name
+                    ))
+                }
+            }
+        };
+
+        if mod_abi != host_abi {
+            return Err(format!(
+                "Refusing to load {}: it was built against a different game_state \
+                 (mod: {}, host: {}, host rustc: {}). \
+                 Rebuild mod_{} (e.g. ./rebuild-mods) after any change to game_state.",
+                self.filename,
+                mod_abi,
+                host_abi,
+                abi::RUSTC_VERSION,
+                self.mod_name
+            ));
+        }
+        Ok(())
     }
 
     ///
@@ -203,64 +518,182 @@ impl LibLoader {
     /// Print an ansi_term colored message to the terminal.
     /// (used to signal changes in mod versions)
     ///
-    fn message(&self, message: &str) {
+    fn message(&self, state: &state::State, message: &str) {
         let source = Path::new(&self.filename);
         let file_stem = source.file_stem().unwrap().to_str().unwrap();
-        println!(
-            "{}{} {} (version {}, {}){}",
-            Green.bold().paint("["),
-            Green.bold().paint(message),
-            Yellow.paint(file_stem),
-            Cyan.paint(format!("{}", self.version)),
-            Cyan.paint(format!("{:?}", source)),
-            Green.bold().paint("]")
+        state.get_logger().info(
+            LOG_TARGET,
+            format!(
+                "[{} {} (version {}, {:?})]",
+                message, file_stem, self.version, source
+            ),
         );
     }
 
     ///
     /// call_update()
     ///
-    /// call a method in the module by name, passing &mut State and a delta_time duration
+    /// Call mod_<name>_update_v2, or mod_<name>_update for v1 mods, passing &mut State and a
+    /// delta_time duration.
     ///
-    fn call_update(&self, method_name: &str, state: &mut state::State, delta_time: &Duration) {
-        match self.lib {
-            Some(ref lib) => unsafe {
-                let method = method_name.as_bytes();
-
-                let maybe_func: Result<
-                    Symbol<unsafe extern "C" fn(&mut state::State, &Duration)>,
-                    Error,
-                > = lib.get(method);
+    fn call_update(
+        &self,
+        state: &mut state::State,
+        delta_time: &Duration,
+    ) -> Result<(), CallError> {
+        let lib = match self.lib {
+            Some(ref lib) => lib,
+            None => return Ok(()),
+        };
+        let method_name = format!("mod_{}_update", self.mod_name);
+        unsafe {
+            let v2: Result<
+                Symbol<unsafe extern "C-unwind" fn(&mut state::State, &Duration) -> ModResult>,
+                Error,
+            > = lib.get(format!("{}_v2", method_name).as_bytes());
+            if let Ok(func) = v2 {
+                return CallError::check(panic::catch_unwind(AssertUnwindSafe(|| {
+                    func(state, delta_time)
+                })));
+            }
 
-                match maybe_func {
-                    Ok(func) => func(state, delta_time),
-                    Err(_) => println!(
-                        "Unable to call function: {} - method does not exist in lib: {:?}",
-                        method_name, lib
-                    ),
+            let v1: Result<
+                Symbol<unsafe extern "C-unwind" fn(&mut state::State, &Duration)>,
+                Error,
+            > = lib.get(method_name.as_bytes());
+            match v1 {
+                Ok(func) => panic::catch_unwind(AssertUnwindSafe(|| func(state, delta_time)))
+                    .map_err(|payload| CallError::Panicked(panic_message(payload))),
+                Err(_) => {
+                    state.get_logger().error(
+                        LOG_TARGET,
+                        format!(
+                            "Unable to call function: {} - method does not exist in lib: {:?}",
+                            method_name, lib
+                        ),
+                    );
+                    Ok(())
                 }
-            },
-            None => println!("Cannot call method {} - lib not found", method_name),
+            }
         }
     }
 
-    fn call(&self, method_name: &str, state: &mut state::State) {
-        match self.lib {
-            Some(ref lib) => unsafe {
-                let method = method_name.as_bytes();
+    ///
+    /// call()
+    ///
+    /// Call the "load" or "unload" lifecycle event, preferring mod_<name>_<event>_v2
+    ///
+    fn call(&self, state: &mut state::State, event: &str) -> Result<(), CallError> {
+        let lib = match self.lib {
+            Some(ref lib) => lib,
+            None => return Ok(()),
+        };
+        let method_name = format!("mod_{}_{}", self.mod_name, event);
+        unsafe {
+            let v2: Result<
+                Symbol<unsafe extern "C-unwind" fn(&mut state::State) -> ModResult>,
+                Error,
+            > = lib.get(format!("{}_v2", method_name).as_bytes());
+            if let Ok(func) = v2 {
+                return CallError::check(panic::catch_unwind(AssertUnwindSafe(|| func(state))));
+            }
 
-                let maybe_func: Result<Symbol<unsafe extern "C" fn(&mut state::State)>, Error> =
-                    lib.get(method);
+            let v1: Result<Symbol<unsafe extern "C-unwind" fn(&mut state::State)>, Error> =
+                lib.get(method_name.as_bytes());
+            match v1 {
+                Ok(func) => panic::catch_unwind(AssertUnwindSafe(|| func(state)))
+                    .map_err(|payload| CallError::Panicked(panic_message(payload))),
+                Err(e) => {
+                    state.get_logger().error(
+                        LOG_TARGET,
+                        format!(
+                            "Unable to call function: {} - method does not exist in lib: {:?} - {:?}",
+                            method_name, lib, e
+                        ),
+                    );
+                    Ok(())
+                }
+            }
+        }
+    }
 
-                match maybe_func {
-                    Ok(func) => func(state),
-                    Err(e) => println!(
-                        "Unable to call function: {} - method does not exist in lib: {:?} - {:?}",
-                        method_name, lib, e
-                    ),
+    // Ask the mod for its state, if it exports mod_<name>_save_state
+    fn call_save_state(&self, state: &mut state::State) -> Result<Option<Vec<u8>>, String> {
+        let lib = match self.lib {
+            Some(ref lib) => lib,
+            None => return Ok(None),
+        };
+        let method_name = format!("mod_{}_save_state", self.mod_name);
+        unsafe {
+            let maybe_func: Result<
+                Symbol<unsafe extern "C-unwind" fn(&mut state::State, &mut Vec<u8>)>,
+                Error,
+            > = lib.get(method_name.as_bytes());
+            match maybe_func {
+                Ok(func) => {
+                    let mut saved = Vec::new();
+                    panic::catch_unwind(AssertUnwindSafe(|| func(state, &mut saved)))
+                        .map_err(panic_message)?;
+                    Ok(Some(saved))
                 }
-            },
-            None => println!("Cannot call method {} - lib not found", method_name),
+                Err(_) => Ok(None),
+            }
+        }
+    }
+
+    // Hand saved state back to the mod, if it exports mod_<name>_restore_state. The slice is
+    // passed as Rust lays it out, which mods agree on - ModAbi pins the compiler.
+    #[allow(improper_ctypes_definitions)]
+    fn call_restore_state(&self, state: &mut state::State, saved: &[u8]) -> Result<(), String> {
+        let lib = match self.lib {
+            Some(ref lib) => lib,
+            None => return Ok(()),
+        };
+        let method_name = format!("mod_{}_restore_state", self.mod_name);
+        unsafe {
+            let maybe_func: Result<
+                Symbol<unsafe extern "C-unwind" fn(&mut state::State, &[u8])>,
+                Error,
+            > = lib.get(method_name.as_bytes());
+            match maybe_func {
+                Ok(func) => panic::catch_unwind(AssertUnwindSafe(|| func(state, saved)))
+                    .map_err(panic_message),
+                Err(_) => Ok(()),
+            }
+        }
+    }
+}
+
+// Why a lifecycle call didn't succeed
+enum CallError {
+    Retry(String),
+    Failed(String),
+    Panicked(String),
+}
+
+impl CallError {
+    // Interpret the result of a v2 lifecycle call
+    fn check(result: thread::Result<ModResult>) -> Result<(), CallError> {
+        let (status, message) = result
+            .map_err(|payload| CallError::Panicked(panic_message(payload)))?
+            .into_parts();
+        let message = message.unwrap_or_else(|| "no reason given".to_string());
+        match status {
+            ModStatus::Ok => Ok(()),
+            ModStatus::Retry => Err(CallError::Retry(message)),
+            ModStatus::Error => Err(CallError::Failed(message)),
         }
     }
 }
+
+// Extract the message from a panic payload. The payload was allocated by the mod, so this
+// must happen (and the payload be dropped) while its library is still loaded.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked with a non-string payload".to_string()
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use ansi_term::Color::{Cyan, Green, Purple, Red, Yellow};
+use game_state::logging::{Level, LogSink, Record};
+
+///
+/// Writes records to stdout, colored by level
+///
+pub struct TerminalSink;
+
+impl LogSink for TerminalSink {
+    fn write(&mut self, record: &Record) {
+        let level = format!("{:<5}", record.level);
+        let level = match record.level {
+            Level::Error => Red.bold().paint(level),
+            Level::Warn => Yellow.bold().paint(level),
+            Level::Info => Green.paint(level),
+            Level::Debug => Cyan.paint(level),
+            Level::Trace => Purple.paint(level),
+        };
+        println!(
+            "{} {}: {}",
+            level,
+            Yellow.paint(&record.target),
+            record.message
+        );
+    }
+
+    fn flush(&mut self) {
+        io::stdout().flush().ok();
+    }
+}
+
+///
+/// Appends records to a file, one per line, prefixed with seconds since the epoch
+///
+pub struct FileSink {
+    writer: LineWriter<File>,
+}
+
+impl FileSink {
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink {
+            writer: LineWriter::new(file),
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, record: &Record) {
+        let time = record.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        // a LineWriter, so nothing is lost if a mod exits the process
+        writeln!(
+            self.writer,
+            "{}.{:03} {:<5} {}: {}",
+            time.as_secs(),
+            time.subsec_millis(),
+            record.level,
+            record.target,
+            record.message
+        )
+        .ok();
+    }
+
+    fn flush(&mut self) {
+        self.writer.flush().ok();
+    }
+}
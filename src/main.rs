@@ -1,93 +1,145 @@
-#[macro_use]
-extern crate engine;
-use engine::libloader::LibLoader;
+use engine::config::{CliArgs, EngineConfig, USAGE};
+use engine::libloader::ModDirectory;
+use engine::logging::{FileSink, TerminalSink};
+use engine::runner::Runner;
 
-use game_state::state::DrawMode;
 use game_state::state::State;
-use std::time::{Duration, Instant};
+use std::env;
+use std::process;
+use std::time::Instant;
 
-use std::thread;
-
-use game_state::state::WindowAccess;
+use game_state::state::{JobAccess, LogAccess, ProfilerAccess, WindowAccess};
 
 fn main() {
-    let mut state = State::default();
+    // sg_engine.toml, or the file given with --config, overridden by the command line
+    let args = CliArgs::parse(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(2);
+    });
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    let config = EngineConfig::from_cli(&args).unwrap_or_else(|err| {
+        eprintln!("unable to read config - {}", err);
+        process::exit(2);
+    });
+
+    // headless runs without SDL video: no windows, no input, no rendering
+    let headless = config.headless;
+    let builder = State::builder()
+        .job_threads(config.job_threads)
+        .graphics_api(config.graphics.graphics_api());
+    let mut state = if headless {
+        builder.headless().build()
+    } else {
+        builder.build()
+    }
+    .expect("unable to create State");
+
+    // SG_LOG filters the log, e.g. "warn,input=debug" - SG_LOG_FILE also writes it to a file
+    let logger = state.get_logger().clone();
+    logger.add_sink(Box::new(TerminalSink));
+    if let Ok(path) = env::var("SG_LOG_FILE") {
+        match FileSink::new(&path) {
+            Ok(sink) => logger.add_sink(Box::new(sink)),
+            Err(err) => logger.error(
+                "engine",
+                format!("unable to open log file {} - {}", path, err),
+            ),
+        }
+    }
+    if let Ok(filter) = env::var("SG_LOG") {
+        if let Err(err) = logger.apply_filter(&filter) {
+            logger.error("engine", format!("invalid SG_LOG filter - {}", err));
+        }
+    }
 
     // TODO mod_audio
     // TODO mod_gui
     // TODO mod_network
 
-    state.add_window(
-        800,
-        600,
-        "sg-shell 1 (vulkano) wireframe",
-        0,
-        720,
-        DrawMode::Wireframe(3.0),
-    );
-    state.add_window(
-        1280,
-        720,
-        "sg-shell 1 (vulkano) textured",
-        0,
-        0,
-        DrawMode::Textured,
-    );
-
-    let mut mods = Vec::new();
-    mods.push(load_mod!(gamepad));
-    mods.push(load_mod!(asset_loader));
-    mods.push(load_mod!(simulation));
-
-    mods.push(load_mod!(rendering_vulkano));
-    mods.push(load_mod!(input));
+    if !headless {
+        for window in config.windows.iter() {
+            let result = state.add_window(
+                window.width,
+                window.height,
+                &window.title,
+                window.x,
+                window.y,
+                window.draw_mode(),
+            );
+            if let Err(err) = result {
+                logger.error("engine", format!("unable to create window - {}", err));
+            }
+        }
+    }
 
-    for m in mods.iter_mut() {
-        m.check_update(&mut state);
+    // each mod_<name>/mod.toml in the mod paths defines a mod to load - SG_MODS_DIR adds a path
+    let mut mod_paths = config.mod_paths.clone();
+    if let Ok(path) = env::var("SG_MODS_DIR") {
+        mod_paths.insert(0, path.into());
     }
+    let mut mods = ModDirectory::new(mod_paths.first().cloned().unwrap_or_else(|| ".".into()));
+    for path in mod_paths.iter().skip(1) {
+        mods.add_path(path);
+    }
+    mods.set_filter(config.mods.clone());
+    mods.set_excluded(config.excluded_mods());
+    mods.scan(&mut state);
 
+    // simulation ticks at a fixed rate (100Hz by default), catching up by a few ticks a frame
+    let mut runner = Runner::new(config.fixed_step(), config.max_steps_per_frame);
+    if config.frame_rate > 0 {
+        runner = runner.with_frame_rate(config.frame_rate);
+    }
     let mut frame = 0;
-    let frame_budget = 16000u128; // for 60 fps
-    let mut last_update = Instant::now();
+    let mut last_scan = Instant::now();
 
     loop {
-        // TODO: gather delta time instead
-
-        let mut total_time = 0;
-        for m in mods.iter() {
-            let start_update = Instant::now();
-            let duration = m.update(&mut state, &(start_update - last_update));
-            if frame % 300 == 0 {
-                print!(
-                    "|> {}: {total_time:>6} μs ",
-                    name = m.get_name(),
-                    total_time = duration.as_micros()
-                );
-            }
-            total_time += duration.as_micros();
+        let stats = runner.frame(&mut mods, &mut state);
+        if config.job_threads == 0 {
+            // without workers, jobs spawned by mods only make progress here
+            state.get_jobs().run_pending();
         }
-        last_update = Instant::now();
+        let profiler = state.get_profiler();
         if frame % 300 == 0 {
-            println!(
-                "|>= total time: {total_time:>6} μs",
-                total_time = total_time
+            // per-mod timing over the profiler's history
+            let mut frame_times = String::new();
+            for name in profiler.top_level_scopes() {
+                if let Some(s) = profiler.stats(&name) {
+                    frame_times += &format!(
+                        "|> {}: {:>6} μs avg, {:>6} μs p99, {:>6} μs min ",
+                        name,
+                        s.avg.as_micros(),
+                        s.p99.as_micros(),
+                        s.min.as_micros()
+                    );
+                }
+            }
+            logger.info(
+                "engine",
+                format!(
+                    "{}|>= this frame: {:>6} μs ({} fixed ticks)",
+                    frame_times,
+                    stats.total.as_micros(),
+                    stats.steps
+                ),
             );
         }
-        if frame % 30 == 0 {
-            for m in mods.iter_mut() {
-                m.check_update(&mut state);
+        if profiler.take_dump_request() {
+            let path = format!("sg-trace-{}.json", frame);
+            match profiler.dump_chrome_trace(&path) {
+                Ok(()) => logger.info("engine", format!("wrote trace to {}", path)),
+                Err(err) => logger.error("engine", format!("unable to write {} - {}", path, err)),
             }
         }
+        if last_scan.elapsed() >= config.reload_poll_interval() {
+            mods.scan(&mut state);
+            last_scan = Instant::now();
+        }
         frame += 1;
 
-        let wait = if frame_budget >= total_time {
-            (frame_budget - total_time) / 1000
-        } else {
-            0
-        };
-
-        if wait > 0 {
-            thread::sleep(Duration::from_millis(wait as u64));
-        }
+        runner.wait_for_next_frame();
     }
 }
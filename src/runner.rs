@@ -0,0 +1,204 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game_state::state::{ProfilerAccess, State, TimeAccess};
+
+use crate::libloader::{ModDirectory, TickKind};
+
+///
+/// FixedStep - turns variable frame times into a whole number of fixed steps
+///
+/// Elapsed time is accumulated, and consumed one step at a time. At most `max_steps` are run
+/// per frame: after a long stall (a breakpoint, a reload) the backlog is dropped rather than
+/// trying to catch up, which would only make the next frame longer still.
+///
+pub struct FixedStep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+}
+
+impl FixedStep {
+    pub fn new(step: Duration, max_steps: u32) -> Self {
+        assert!(
+            step > Duration::from_millis(0),
+            "fixed step must be non-zero"
+        );
+        FixedStep {
+            step,
+            max_steps,
+            accumulator: Duration::from_millis(0),
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    ///
+    /// Add the time elapsed since the last frame, returning the number of steps to run
+    ///
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if self.accumulator >= self.step {
+            let behind = self.accumulator.as_nanos() % self.step.as_nanos();
+            self.accumulator = Duration::from_nanos(behind as u64);
+        }
+        steps
+    }
+
+    ///
+    /// How far we are between the last step and the next, from 0 to 1
+    ///
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+///
+/// Timing of a single frame, for reporting
+///
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    /// Fixed steps run this frame
+    pub steps: u32,
+    /// Time spent updating each mod, summed over all of its updates this frame
+    pub mod_times: Vec<(String, Duration)>,
+    /// Time spent updating all mods
+    pub total: Duration,
+}
+
+impl FrameStats {
+    fn add(&mut self, name: &str, duration: Duration) {
+        match self.mod_times.iter_mut().find(|(n, _)| n == name) {
+            Some((_, total)) => *total += duration,
+            None => self.mod_times.push((name.to_string(), duration)),
+        }
+        self.total += duration;
+    }
+}
+
+///
+/// Runner - drives the mods in a ModDirectory, one frame at a time
+///
+/// Each frame, mods with `tick = "fixed"` are updated zero or more times with the fixed step,
+/// then every other mod is updated once with the frame's delta time. The frame and tick
+/// counters and the interpolation alpha are published to mods through `TimeAccess`, and each
+/// mod's update is recorded as a top-level scope in the profiler.
+///
+/// let mut runner = Runner::new(Duration::from_millis(10), 5).with_frame_rate(60);
+/// loop {
+///     runner.frame(&mut mods, &mut state);
+///     runner.wait_for_next_frame();
+/// }
+///
+pub struct Runner {
+    fixed_step: FixedStep,
+    frame_time: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl Runner {
+    pub fn new(fixed_step: Duration, max_steps_per_frame: u32) -> Self {
+        Runner {
+            fixed_step: FixedStep::new(fixed_step, max_steps_per_frame),
+            frame_time: None,
+            last_frame: None,
+        }
+    }
+
+    ///
+    /// Limit the frame rate - wait_for_next_frame() sleeps off the rest of each frame
+    ///
+    pub fn with_frame_rate(mut self, fps: u32) -> Self {
+        self.frame_time = Some(Duration::from_nanos(1_000_000_000 / u64::from(fps.max(1))));
+        self
+    }
+
+    ///
+    /// Run one frame: fixed steps first, then the per-frame update
+    ///
+    pub fn frame(&mut self, mods: &mut ModDirectory, state: &mut State) -> FrameStats {
+        let now = Instant::now();
+        let frame_dt = self
+            .last_frame
+            .map(|last| now - last)
+            .unwrap_or_else(|| Duration::from_millis(0));
+        self.last_frame = Some(now);
+
+        let profiler = state.get_profiler().clone();
+        profiler.begin_frame(state.get_time().frame);
+
+        let step = self.fixed_step.step();
+        let steps = self.fixed_step.advance(frame_dt);
+        {
+            let time = state.get_time_mut();
+            time.fixed_dt = step;
+            time.frame_dt = frame_dt;
+        }
+
+        let mut stats = FrameStats {
+            steps,
+            ..Default::default()
+        };
+        for _ in 0..steps {
+            for m in mods.iter_mut_by_tick(TickKind::Fixed) {
+                let _scope = profiler.scope(m.get_name());
+                let duration = m.update(state, &step);
+                stats.add(m.get_name(), duration);
+            }
+            state.get_time_mut().tick += 1;
+        }
+
+        state.get_time_mut().alpha = self.fixed_step.alpha();
+        for m in mods.iter_mut_by_tick(TickKind::Frame) {
+            let _scope = profiler.scope(m.get_name());
+            let duration = m.update(state, &frame_dt);
+            stats.add(m.get_name(), duration);
+        }
+        state.get_time_mut().frame += 1;
+
+        stats
+    }
+
+    ///
+    /// Sleep until the next frame is due, if the frame rate is limited
+    ///
+    pub fn wait_for_next_frame(&self) {
+        if let (Some(frame_time), Some(last_frame)) = (self.frame_time, self.last_frame) {
+            let elapsed = last_frame.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_partial_steps() {
+        let mut fixed = FixedStep::new(Duration::from_millis(10), 5);
+        assert_eq!(fixed.advance(Duration::from_millis(4)), 0);
+        assert_eq!(fixed.advance(Duration::from_millis(4)), 0);
+        assert_eq!(fixed.advance(Duration::from_millis(4)), 1);
+        assert!((fixed.alpha() - 0.2).abs() < 1e-4);
+        assert_eq!(fixed.advance(Duration::from_millis(25)), 2);
+        assert!((fixed.alpha() - 0.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn drops_backlog_beyond_max_steps() {
+        let mut fixed = FixedStep::new(Duration::from_millis(10), 3);
+        assert_eq!(fixed.advance(Duration::from_millis(1005)), 3);
+        assert!((fixed.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(fixed.advance(Duration::from_millis(5)), 1);
+    }
+}